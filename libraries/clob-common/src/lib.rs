@@ -10,7 +10,8 @@ pub type EthAddress = [u8; 20];
 pub type TraderId = ActorId;
 /// Tokens remain 20-byte Ethereum addresses.
 pub type TokenId = EthAddress; // Match existing tests usage
-pub type OrderId = u128;
+/// Same width as `matching_engine::OrderId` so ids pass between crates unchanged.
+pub type OrderId = u64;
 pub type Price = u128;
 pub type Quantity = u128;
 
@@ -27,6 +28,31 @@ pub fn mul_div_ceil(a: u128, b: u128, denom: u128) -> u128 {
     rounded / denom
 }
 
+/// Program version as `(major, minor, patch)`.
+pub type Version = (u16, u16, u16);
+
+/// Builds a [`Version`] from the `CARGO_PKG_VERSION_*` strings at compile time.
+pub const fn parse_version(major: &str, minor: &str, patch: &str) -> Version {
+    (
+        parse_version_part(major),
+        parse_version_part(minor),
+        parse_version_part(patch),
+    )
+}
+
+const fn parse_version_part(part: &str) -> u16 {
+    let bytes = part.as_bytes();
+    let mut value: u16 = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let digit = bytes[i];
+        assert!(digit.is_ascii_digit(), "InvalidVersion");
+        value = value * 10 + (digit - b'0') as u16;
+        i += 1;
+    }
+    value
+}
+
 pub fn actor_to_eth(actor: ActorId) -> EthAddress {
     Address::from(actor).into()
 }
@@ -125,6 +151,15 @@ pub const SHOWCASE_PREFUNDED_ETH_ADDRESSES: [EthAddress; 20] = [
     ],
 ];
 
+/// Why a market refused to credit a deposit pushed by a vault.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Encode, Decode, TypeInfo)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub enum DepositError {
+    /// The caller is not the vault bound to the deposited token.
+    UnauthorizedCaller,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Encode, Decode, TypeInfo)]
 #[codec(crate = sails_rs::scale_codec)]
 #[scale_info(crate = sails_rs::scale_info)]
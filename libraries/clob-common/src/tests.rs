@@ -1,5 +1,5 @@
 #[cfg(test)]
-use crate::{actor_to_eth, eth_to_actor, EthAddress};
+use crate::{actor_to_eth, eth_to_actor, parse_version, EthAddress};
 
 #[test]
 fn test_eth_actor_conversion() {
@@ -23,3 +23,9 @@ fn test_actor_is_right_aligned() {
         "ActorId should be RIGHT-aligned for Ethereum compatibility"
     );
 }
+
+#[test]
+fn test_parse_version() {
+    assert_eq!(parse_version("0", "1", "0"), (0, 1, 0));
+    assert_eq!(parse_version("12", "305", "7"), (12, 305, 7));
+}
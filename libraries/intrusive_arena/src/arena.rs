@@ -1,35 +1,74 @@
 use core::{fmt, mem};
-use sails_rs::Vec;
-
-#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
-pub struct Index(u32);
+use sails_rs::{prelude::*, Vec};
+
+/// Slot plus the generation it was handed out in. Once the slot is removed its
+/// generation moves on, so an old Index no longer resolves even after the slot is reused.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode, TypeInfo)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct Index {
+    slot: u32,
+    gen: u32,
+}
 
 impl Index {
-    pub const fn new(raw: u32) -> Self {
-        Self(raw)
+    pub const fn new(slot: u32) -> Self {
+        Self { slot, gen: 0 }
+    }
+
+    pub const fn with_generation(slot: u32, gen: u32) -> Self {
+        Self { slot, gen }
     }
 
     pub fn as_usize(self) -> usize {
-        self.0 as usize
+        self.slot as usize
+    }
+
+    pub fn generation(self) -> u32 {
+        self.gen
     }
 }
 
 impl fmt::Debug for Index {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Index({})", self.0)
+        write!(f, "Index({}@{})", self.slot, self.gen)
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Encode, Decode, TypeInfo)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
 pub enum Entry<T> {
     Occupied(T),
     Free(Option<Index>),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Encode, Decode, TypeInfo)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+struct Slot<T> {
+    gen: u32,
+    entry: Entry<T>,
+}
+
+impl<T> Slot<T> {
+    fn index(&self, slot: usize) -> Index {
+        Index::with_generation(slot as u32, self.gen)
+    }
+}
+
+/// Encodes slot by slot, free list included, so every `Index` stays valid after a
+/// decode round trip.
+#[derive(Debug, Clone, PartialEq, Encode, Decode, TypeInfo)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
 pub struct Arena<T> {
-    storage: Vec<Entry<T>>,
+    storage: Vec<Slot<T>>,
     free_head: Option<Index>,
+    occupied: u32,
+    /// Generation for appended slots. `compact` raises it past the slots it truncates, so
+    /// a handle to a released slot never matches a slot appended later.
+    append_gen: u32,
 }
 
 impl<T> Default for Arena<T> {
@@ -37,6 +76,8 @@ impl<T> Default for Arena<T> {
         Self {
             storage: Vec::new(),
             free_head: None,
+            occupied: 0,
+            append_gen: 0,
         }
     }
 }
@@ -50,16 +91,39 @@ impl<T> Arena<T> {
         Self {
             storage: Vec::with_capacity(cap),
             free_head: None,
+            occupied: 0,
+            append_gen: 0,
         }
     }
 
+    /// Number of occupied slots.
+    pub fn len(&self) -> usize {
+        self.occupied as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.occupied == 0
+    }
+
+    /// Slots on the free list, waiting to be reused.
+    pub fn free_slots(&self) -> usize {
+        self.storage.len() - self.len()
+    }
+
+    /// Slots in the backing storage, occupied or free.
+    pub fn capacity(&self) -> usize {
+        self.storage.len()
+    }
+
     /// Allocate a new value and return its stable Index.
     pub fn alloc(&mut self, value: T) -> Index {
+        self.occupied += 1;
         if let Some(idx) = self.free_head {
             // Reuse a free slot
-            let entry = self.storage.get_mut(idx.as_usize()).unwrap_or_else(|| {
+            let slot = self.storage.get_mut(idx.as_usize()).unwrap_or_else(|| {
                 panic!("Corrupted free list: free_head out of bounds: {:?}", idx)
             });
+            let entry = &mut slot.entry;
 
             match entry {
                 Entry::Free(next_free) => {
@@ -81,50 +145,109 @@ impl<T> Arena<T> {
                 .try_into()
                 .unwrap_or_else(|_| panic!("Arena overflow: too many elements (>{})", u32::MAX));
 
-            let idx = Index(idx_u32);
-            self.storage.push(Entry::Occupied(value));
+            let idx = Index::with_generation(idx_u32, self.append_gen);
+            self.storage.push(Slot {
+                gen: self.append_gen,
+                entry: Entry::Occupied(value),
+            });
             idx
         }
     }
 
+    /// `None` when the slot is free or `index` is from an earlier generation of it.
     pub fn get(&self, index: Index) -> Option<&T> {
-        match self.storage.get(index.as_usize())? {
-            Entry::Occupied(val) => Some(val),
-            Entry::Free(_) => None,
+        let slot = self.storage.get(index.as_usize())?;
+        match &slot.entry {
+            Entry::Occupied(val) if slot.gen == index.gen => Some(val),
+            _ => None,
         }
     }
 
     pub fn get_mut(&mut self, index: Index) -> Option<&mut T> {
-        match self.storage.get_mut(index.as_usize())? {
-            Entry::Occupied(val) => Some(val),
-            Entry::Free(_) => None,
+        let slot = self.storage.get_mut(index.as_usize())?;
+        match &mut slot.entry {
+            Entry::Occupied(val) if slot.gen == index.gen => Some(val),
+            _ => None,
         }
     }
 
     /// Remove a value from the arena, returning it if the slot was occupied.
     ///
-    /// Slot becomes free and is pushed to the free-list head (LIFO).
+    /// Slot becomes free and is pushed to the free-list head (LIFO). Its generation is
+    /// bumped, so `index` and any copy of it stop resolving.
     pub fn remove(&mut self, index: Index) -> Option<T> {
         let slot = self.storage.get_mut(index.as_usize())?;
+        if slot.gen != index.gen || matches!(slot.entry, Entry::Free(_)) {
+            return None;
+        }
 
-        // swap the slot with Free(free_head), moving out the old entry
-        let old_entry = mem::replace(slot, Entry::Free(self.free_head));
+        let Entry::Occupied(val) = mem::replace(&mut slot.entry, Entry::Free(self.free_head))
+        else {
+            unreachable!()
+        };
+        slot.gen = slot.gen.wrapping_add(1);
+        self.free_head = Some(slot.index(index.as_usize()));
+        self.occupied -= 1;
+        Some(val)
+    }
 
-        match old_entry {
-            Entry::Occupied(val) => {
-                self.free_head = Some(index);
-                Some(val)
-            }
-            Entry::Free(next) => {
-                // restore original free entry (so we don't break free list)
-                *slot = Entry::Free(next);
-                None
+    pub fn dealloc(&mut self, index: Index) {
+        let _ = self.remove(index);
+    }
+
+    /// Moves every occupied entry to the front, keeping their order, and releases the
+    /// free slots. Returns `(old, new)` for each entry that moved so callers can rewrite
+    /// indices they keep elsewhere, including links stored inside the values.
+    ///
+    /// Every moved index is stale afterwards: only run this when no operation is holding
+    /// an `Index` across the call. Stale handles resolve to `None`, never to another value.
+    pub fn compact(&mut self) -> Vec<(Index, Index)> {
+        let mut remap = Vec::new();
+        let mut next = 0;
+        for i in 0..self.storage.len() {
+            if matches!(self.storage[i].entry, Entry::Occupied(_)) {
+                if i != next {
+                    // both slots change hands, so both move to a fresh generation
+                    let old = self.storage[i].index(i);
+                    let entry = mem::replace(&mut self.storage[i].entry, Entry::Free(None));
+                    self.storage[i].gen = self.storage[i].gen.wrapping_add(1);
+                    let dest = &mut self.storage[next];
+                    dest.gen = dest.gen.wrapping_add(1);
+                    dest.entry = entry;
+                    remap.push((old, dest.index(next)));
+                }
+                next += 1;
             }
         }
+        // every slot past `next` is free and already past its issued handles
+        if let Some(gen) = self.storage[next..].iter().map(|slot| slot.gen).max() {
+            self.append_gen = self.append_gen.max(gen);
+        }
+        self.storage.truncate(next);
+        self.storage.shrink_to_fit();
+        self.free_head = None;
+        remap
     }
 
-    pub fn dealloc(&mut self, index: Index) {
-        let _ = self.remove(index);
+    /// Occupied slots in index order, each with its Index.
+    pub fn iter(&self) -> impl Iterator<Item = (Index, &T)> {
+        self.storage
+            .iter()
+            .enumerate()
+            .filter_map(|(i, slot)| match &slot.entry {
+                Entry::Occupied(val) => Some((slot.index(i), val)),
+                Entry::Free(_) => None,
+            })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Index, &mut T)> {
+        self.storage.iter_mut().enumerate().filter_map(|(i, slot)| {
+            let idx = slot.index(i);
+            match &mut slot.entry {
+                Entry::Occupied(val) => Some((idx, val)),
+                Entry::Free(_) => None,
+            }
+        })
     }
 }
 
@@ -143,20 +266,25 @@ mod tests {
             }
             seen.push(i);
 
-            let entry = a
+            let slot = a
                 .storage
                 .get(i.as_usize())
                 .unwrap_or_else(|| panic!("free_head points out of bounds: {:?}", i));
+            assert_eq!(
+                slot.gen, i.gen,
+                "free list holds a stale generation: {:?}",
+                i
+            );
 
-            match entry {
+            match &slot.entry {
                 Entry::Free(next) => cur = *next,
                 Entry::Occupied(_) => panic!("free list points to occupied slot: {:?}", i),
             }
         }
 
-        for (idx, entry) in a.storage.iter().enumerate() {
-            if matches!(entry, Entry::Free(_)) {
-                let i = Index::new(idx as u32);
+        for (idx, slot) in a.storage.iter().enumerate() {
+            if matches!(slot.entry, Entry::Free(_)) {
+                let i = slot.index(idx);
                 assert!(
                     seen.contains(&i),
                     "Free slot not reachable from free_head: {:?}",
@@ -179,7 +307,7 @@ mod tests {
 
         let i3 = a.alloc(40);
         // should reuse the freed slot (LIFO)
-        assert_eq!(i3, i1);
+        assert_eq!(i3.as_usize(), i1.as_usize());
         assert_eq!(a.get(i3), Some(&40));
 
         assert_eq!(a.get(i0), Some(&10));
@@ -230,7 +358,7 @@ mod tests {
 
         // free-list must work
         let i2 = a.alloc(3);
-        assert_eq!(i2, i0); // LIFO reuse
+        assert_eq!(i2.as_usize(), i0.as_usize()); // LIFO reuse
         assert_eq!(a.get(i2), Some(&3));
         assert_eq!(a.get(i1), Some(&2));
         assert_arena_invariants(&a);
@@ -253,15 +381,15 @@ mod tests {
 
         // free-list: i2 -> i1
         let j0 = a.alloc(100);
-        assert_eq!(j0, i2);
+        assert_eq!(j0.as_usize(), i2.as_usize());
         assert_arena_invariants(&a);
 
         let j1 = a.alloc(200);
-        assert_eq!(j1, i1);
+        assert_eq!(j1.as_usize(), i1.as_usize());
         assert_arena_invariants(&a);
 
         let j2 = a.alloc(300);
-        assert_ne!(j2, i0);
+        assert_ne!(j2.as_usize(), i0.as_usize());
         assert_arena_invariants(&a);
     }
 
@@ -318,4 +446,142 @@ mod tests {
 
         assert_eq!(a.storage.len(), len_before);
     }
+
+    #[test]
+    fn footprint_tracks_occupied_and_free_slots() {
+        let mut a = Arena::with_capacity(8);
+        let idx: Vec<_> = (0..5).map(|i| a.alloc(i)).collect();
+        a.remove(idx[1]);
+        a.remove(idx[3]);
+        a.remove(idx[3]);
+
+        assert_eq!(a.len(), 3);
+        assert_eq!(a.free_slots(), 2);
+        assert_eq!(a.capacity(), 5);
+
+        a.alloc(9);
+        assert_eq!((a.len(), a.free_slots()), (4, 1));
+    }
+
+    #[test]
+    fn len_ignores_repeated_removes_and_counts_reuse() {
+        let mut a = Arena::new();
+        assert!(a.is_empty());
+
+        let i0 = a.alloc(1);
+        let i1 = a.alloc(2);
+        assert_eq!(a.len(), 2);
+
+        a.dealloc(i0);
+        a.dealloc(i0);
+        assert_eq!(a.remove(i0), None);
+        assert_eq!((a.len(), a.free_slots()), (1, 1));
+
+        let i2 = a.alloc(3);
+        assert_eq!(i2.as_usize(), i0.as_usize());
+        assert_eq!((a.len(), a.free_slots(), a.capacity()), (2, 0, 2));
+
+        a.remove(i2);
+        a.remove(i1);
+        a.remove(i1);
+        assert!(a.is_empty());
+        assert_eq!((a.free_slots(), a.capacity()), (2, 2));
+        assert_arena_invariants(&a);
+    }
+
+    #[test]
+    fn iter_yields_only_occupied_slots() {
+        let mut a = Arena::new();
+        let idx: Vec<_> = (0..5).map(|v| a.alloc(v * 10)).collect();
+        a.remove(idx[1]);
+        a.remove(idx[3]);
+
+        let live: Vec<_> = a.iter().map(|(i, v)| (i, *v)).collect();
+        assert_eq!(live, [(idx[0], 0), (idx[2], 20), (idx[4], 40)]);
+
+        // LIFO reuse puts the new value in slot 3
+        let reused = a.alloc(99);
+        assert_eq!(reused.as_usize(), idx[3].as_usize());
+        for (_, v) in a.iter_mut() {
+            *v += 1;
+        }
+        let live: Vec<_> = a.iter().map(|(i, v)| (i, *v)).collect();
+        assert_eq!(
+            live,
+            [(idx[0], 1), (idx[2], 21), (reused, 100), (idx[4], 41)]
+        );
+        assert_arena_invariants(&a);
+    }
+
+    #[test]
+    fn compact_releases_free_slots_and_remaps_live_ones() {
+        let mut a = Arena::new();
+        let idx: Vec<_> = (0..1000).map(|v| a.alloc(v)).collect();
+        for &i in idx.iter().step_by(2) {
+            a.remove(i);
+        }
+        assert_eq!(a.capacity(), 1000);
+
+        let remap = a.compact();
+        assert_eq!(a.capacity(), 500);
+        assert_eq!((a.len(), a.free_slots()), (500, 0));
+        assert_arena_invariants(&a);
+
+        // slot 0 was freed, so every live value shifted down, slot 1 included
+        assert_eq!(remap.len(), 500);
+        for (k, &old) in idx.iter().enumerate().skip(1).step_by(2) {
+            let &(_, new) = remap.iter().find(|(from, _)| *from == old).unwrap();
+            assert_eq!(a.get(new), Some(&(k as i32)));
+        }
+
+        // a compacted arena appends again instead of reusing
+        let i = a.alloc(-1);
+        assert_eq!(i.as_usize(), 500);
+        assert!(a.compact().is_empty());
+    }
+
+    #[test]
+    fn index_from_before_compact_does_not_resolve() {
+        let mut a = Arena::new();
+        let freed = a.alloc(0);
+        let first = a.alloc(1);
+        let second = a.alloc(2);
+        a.remove(freed);
+
+        // `first` moves into slot 0, then `second` into the slot `first` vacated
+        let remap = a.compact();
+        assert_eq!(remap.len(), 2);
+        assert_eq!(a.get(first), None);
+        assert_eq!(a.get(second), None);
+        assert_eq!(a.get(freed), None);
+
+        // slot 2 was truncated; appending it again must not revive `second`'s old handle
+        let appended = a.alloc(3);
+        assert_eq!(appended.as_usize(), second.as_usize());
+        assert_eq!(a.get(second), None);
+        assert_eq!(a.get(appended), Some(&3));
+        for (old, new) in remap {
+            assert_eq!(a.get(old), None);
+            assert!(a.get(new).is_some());
+        }
+        assert_arena_invariants(&a);
+    }
+
+    #[test]
+    fn stale_index_does_not_resolve_after_slot_reuse() {
+        let mut a = Arena::new();
+        let old = a.alloc(1);
+        assert_eq!(a.remove(old), Some(1));
+
+        let new = a.alloc(2);
+        assert_eq!(new.as_usize(), old.as_usize());
+        assert_ne!(new.generation(), old.generation());
+
+        assert_eq!(a.get(old), None);
+        assert_eq!(a.get_mut(old), None);
+        assert_eq!(a.remove(old), None);
+        assert_eq!(a.get(new), Some(&2));
+        assert_eq!(a.len(), 1);
+        assert_arena_invariants(&a);
+    }
 }
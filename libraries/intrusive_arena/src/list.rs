@@ -1,6 +1,9 @@
 use crate::{Arena, Index};
+use sails_rs::prelude::*;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Encode, Decode, TypeInfo)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
 pub struct Node<T> {
     pub value: T,
     pub prev: Option<Index>,
@@ -19,7 +22,9 @@ impl<T> Node<T> {
 
 /// Intrusive doubly-linked list that stores only head/tail indices.
 /// Nodes live in `Arena<Node<T>>`.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Encode, Decode, TypeInfo)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
 pub struct List {
     pub head: Option<Index>,
     pub tail: Option<Index>,
@@ -434,4 +439,39 @@ mod tests {
             assert_eq!(list.peek_back(&arena).copied(), exp_back);
         }
     }
+
+    #[test]
+    fn arena_and_list_survive_an_encode_decode_round_trip() {
+        let mut arena: Arena<Node<u64>> = Arena::new();
+        let mut bids = List::new();
+        let mut asks = List::new();
+        let idx: Vec<_> = (0..6).map(|v| bids.push_back(&mut arena, v)).collect();
+        asks.push_front(&mut arena, 100);
+        asks.push_front(&mut arena, 101);
+        bids.remove(&mut arena, idx[1]);
+        bids.remove(&mut arena, idx[4]);
+        // reuse one freed slot so a generation has moved on
+        let reused = asks.push_back(&mut arena, 102);
+
+        let bytes = (&arena, &bids, &asks).encode();
+        let (mut arena2, mut bids2, mut asks2) =
+            <(Arena<Node<u64>>, List, List)>::decode(&mut &bytes[..]).unwrap();
+
+        assert_eq!(arena2, arena);
+        assert_eq!((&bids2, &asks2), (&bids, &asks));
+        assert_eq!(arena2.len(), arena.len());
+        assert_list_invariants(&bids2, &arena2);
+        assert_list_invariants(&asks2, &arena2);
+        assert_eq!(arena2.get(reused).map(|n| n.value), Some(102));
+        assert!(arena2.get(idx[4]).is_none());
+
+        // the decoded free list hands out the same slots in the same order
+        assert_eq!(
+            bids2.push_back(&mut arena2, 7),
+            bids.push_back(&mut arena, 7)
+        );
+        let drained: Vec<_> = core::iter::from_fn(|| bids2.pop_front(&mut arena2)).collect();
+        assert_eq!(drained, [0, 2, 3, 5, 7]);
+        assert_eq!(asks2.pop_back(&mut arena2), Some(102));
+    }
 }
@@ -1,6 +1,9 @@
 use sails_rs::U256;
 
-use crate::types::{MakerView, RestingOrder, Side};
+use crate::{
+    math::DEFAULT_PRICE_EXPONENT,
+    types::{MakerView, RestingOrder, Side},
+};
 
 /// Book interface required by the matching engine.
 pub trait Book {
@@ -37,4 +40,9 @@ pub trait Book {
 
     /// Insert Limit remainder as a resting order.
     fn insert_resting(&mut self, o: RestingOrder);
+
+    /// Decimal exponent prices are scaled by when converting base to quote.
+    fn price_exponent(&self) -> u32 {
+        DEFAULT_PRICE_EXPONENT
+    }
 }
@@ -2,10 +2,11 @@ use sails_rs::{Vec, U256};
 
 use crate::{
     book::Book,
-    math::{calc_quote_ceil, calc_quote_floor},
+    math::{calc_base_floor_scaled, calc_quote_ceil_scaled, calc_quote_floor_scaled},
     types::{
-        BookInvariant, Completion, EngineLimits, ExecutionReport, IncomingOrder,
-        InvalidOrderReason, MakerView, MatchError, OrderKind, RestingOrder, Side, Trade,
+        BookInvariant, Completion, EngineLimits, ExecutionReport, ExpiredMaker, IncomingOrder,
+        InvalidOrderReason, MakerView, MatchError, OrderKind, RestingOrder, Side, StpMode,
+        StpRelease, Trade,
     },
 };
 
@@ -16,8 +17,25 @@ fn crosses(taker_side: Side, taker_limit: U256, maker_price: U256) -> bool {
     }
 }
 
+fn within_worst_price(order: &IncomingOrder, maker_price: U256) -> bool {
+    order
+        .worst_price
+        .is_none_or(|worst| crosses(order.side, worst, maker_price))
+}
+
+/// True once a taker has used up its sweep allowance.
+fn sweep_capped(limits: &EngineLimits, fills: usize, traded: U256) -> bool {
+    (limits.sweep_max_fills != 0 && fills >= limits.sweep_max_fills as usize)
+        || (!limits.sweep_max_base.is_zero() && traded >= limits.sweep_max_base)
+}
+
+/// Market BUY with `amount_base == 0`: spend `max_quote` instead of buying a fixed base amount.
+fn is_quote_budget_buy(order: &IncomingOrder) -> bool {
+    order.kind == OrderKind::Market && order.side == Side::Buy && order.amount_base.is_zero()
+}
+
 fn validate(order: &IncomingOrder) -> Result<(), MatchError> {
-    if order.amount_base.is_zero() {
+    if order.amount_base.is_zero() && !is_quote_budget_buy(order) {
         return Err(MatchError::InvalidOrder(InvalidOrderReason::ZeroAmountBase));
     }
 
@@ -51,6 +69,26 @@ fn validate(order: &IncomingOrder) -> Result<(), MatchError> {
         ));
     }
 
+    if order.worst_price.is_some()
+        && !matches!(order.kind, OrderKind::Market | OrderKind::ImmediateOrCancel)
+    {
+        return Err(MatchError::InvalidOrder(
+            InvalidOrderReason::WorstPriceOnlyForMarketOrIoc,
+        ));
+    }
+
+    if !order.min_quote.is_zero() && (order.kind != OrderKind::Market || order.side != Side::Sell) {
+        return Err(MatchError::InvalidOrder(
+            InvalidOrderReason::MinQuoteOnlyForMarketSell,
+        ));
+    }
+
+    if order.expires_at != 0 && order.kind != OrderKind::Limit {
+        return Err(MatchError::InvalidOrder(
+            InvalidOrderReason::ExpiryOnlyForLimit,
+        ));
+    }
+
     Ok(())
 }
 
@@ -95,6 +133,9 @@ pub fn preview_market_buy_budget_strict<B: Book>(
     let mut price_opt = book.best_price(maker_side);
 
     while let Some(price) = price_opt {
+        if !within_worst_price(order, price) {
+            break;
+        }
         let mut h = book
             .level_head(maker_side, price)
             .ok_or(MatchError::BrokenBook(BookInvariant::BestPriceHasNoHead))?;
@@ -112,23 +153,131 @@ pub fn preview_market_buy_budget_strict<B: Book>(
                 .ok_or(MatchError::BrokenBook(BookInvariant::LevelHeadMissingMaker))?;
             validate_maker_view(&maker, maker_side, price)?;
 
-            let fill = remaining.min(maker.remaining_base);
+            // `execute` drops expired makers without filling them
+            if !maker.is_expired(limits.now) {
+                let fill = remaining.min(maker.remaining_base);
 
-            let q = calc_quote_floor(fill, price)?;
-            required_quote = required_quote
-                .checked_add(q)
-                .ok_or(MatchError::AddOverflow)?;
+                let q = calc_quote_floor_scaled(fill, price, book.price_exponent())?;
+                required_quote = required_quote
+                    .checked_add(q)
+                    .ok_or(MatchError::AddOverflow)?;
 
-            if required_quote > order.max_quote {
-                return Err(MatchError::MarketBuyMaxQuoteExceeded);
+                if required_quote > order.max_quote {
+                    return Err(MatchError::MarketBuyMaxQuoteExceeded);
+                }
+
+                remaining = remaining
+                    .checked_sub(fill)
+                    .ok_or(MatchError::SubUnderflow)?;
+                if remaining.is_zero() {
+                    return Ok(());
+                }
             }
 
-            remaining = remaining
-                .checked_sub(fill)
-                .ok_or(MatchError::SubUnderflow)?;
-            if remaining.is_zero() {
+            match book.next_in_level(h) {
+                Some(next) => {
+                    if next == h {
+                        return Err(MatchError::BrokenBook(BookInvariant::NextInLevelSelfLoop));
+                    }
+                    h = next;
+                }
+                None => break,
+            }
+        }
+
+        price_opt = book.next_price(maker_side, price);
+        if let Some(next_price) = price_opt {
+            if next_price == price {
+                return Err(MatchError::BrokenBook(
+                    BookInvariant::NextPriceDidNotAdvance,
+                ));
+            }
+        }
+    }
+
+    // A worst-price bound turns the strict fill into a partial one: the rest cancels.
+    if order.worst_price.is_some() {
+        return Ok(());
+    }
+
+    Err(MatchError::MarketBuyInsufficientLiquidity)
+}
+
+/// Checks that a market SELL would realize at least `min_quote` from the bids it can reach,
+/// walking them as `execute` would (worst price, sweep limits, self-trade prevention).
+pub fn preview_market_sell_min_quote<B: Book>(
+    book: &B,
+    order: &IncomingOrder,
+    limits: EngineLimits,
+) -> Result<(), MatchError> {
+    if order.kind != OrderKind::Market || order.side != Side::Sell {
+        return Err(MatchError::InvalidOrder(
+            InvalidOrderReason::PreviewOnlyForMarketSellMinQuote,
+        ));
+    }
+
+    let maker_side = Side::Buy; // bids
+    let mut remaining = order.amount_base;
+    let mut realized_quote = U256::zero();
+    let mut traded = U256::zero();
+    let mut fills: usize = 0;
+
+    let mut scanned: u32 = 0;
+    let mut price_opt = book.best_price(maker_side);
+
+    'walk: while let Some(price) = price_opt {
+        if !within_worst_price(order, price) {
+            break;
+        }
+        let mut h = book
+            .level_head(maker_side, price)
+            .ok_or(MatchError::BrokenBook(BookInvariant::BestPriceHasNoHead))?;
+
+        loop {
+            if realized_quote >= order.min_quote {
                 return Ok(());
             }
+            if remaining.is_zero() || sweep_capped(&limits, fills, traded) {
+                break 'walk;
+            }
+
+            scanned += 1;
+            if scanned > limits.max_preview_scans {
+                return Err(MatchError::ScanLimitReached {
+                    max_scanned: limits.max_preview_scans,
+                });
+            }
+
+            let maker = book
+                .get_maker(h)
+                .ok_or(MatchError::BrokenBook(BookInvariant::LevelHeadMissingMaker))?;
+            validate_maker_view(&maker, maker_side, price)?;
+
+            if maker.is_expired(limits.now) {
+                // dropped by `execute` without a fill
+            } else if maker.owner == order.owner && order.stp != StpMode::None {
+                match order.stp {
+                    StpMode::CancelResting => {}
+                    StpMode::DecrementBoth => {
+                        remaining = remaining.saturating_sub(maker.remaining_base);
+                    }
+                    _ => break 'walk,
+                }
+            } else {
+                let mut fill = remaining.min(maker.remaining_base);
+                if !limits.sweep_max_base.is_zero() {
+                    fill = fill.min(limits.sweep_max_base - traded);
+                }
+                let q = calc_quote_floor_scaled(fill, price, book.price_exponent())?;
+                realized_quote = realized_quote
+                    .checked_add(q)
+                    .ok_or(MatchError::AddOverflow)?;
+                traded = traded.checked_add(fill).ok_or(MatchError::AddOverflow)?;
+                fills += 1;
+                remaining = remaining
+                    .checked_sub(fill)
+                    .ok_or(MatchError::SubUnderflow)?;
+            }
 
             match book.next_in_level(h) {
                 Some(next) => {
@@ -151,7 +300,11 @@ pub fn preview_market_buy_budget_strict<B: Book>(
         }
     }
 
-    Err(MatchError::MarketBuyInsufficientLiquidity)
+    if realized_quote >= order.min_quote {
+        Ok(())
+    } else {
+        Err(MatchError::MarketSellMinQuoteNotMet)
+    }
 }
 
 /// Preview fillability for FOK without mutating the book.
@@ -159,6 +312,19 @@ pub fn preview_fillable<B: Book>(
     book: &B,
     order: &IncomingOrder,
     max_scanned: u32,
+    now: u64,
+) -> Result<bool, MatchError> {
+    fillable_within(book, order, max_scanned, now, 0)
+}
+
+/// `preview_fillable` that also fails once filling would take more than `max_fills`
+/// makers (0 = unlimited), the point where `execute`'s sweep cap would halt the order.
+fn fillable_within<B: Book>(
+    book: &B,
+    order: &IncomingOrder,
+    max_scanned: u32,
+    now: u64,
+    max_fills: u32,
 ) -> Result<bool, MatchError> {
     if order.kind != OrderKind::FillOrKill {
         return Err(MatchError::InvalidOrder(
@@ -170,6 +336,7 @@ pub fn preview_fillable<B: Book>(
     let mut remaining = order.amount_base;
 
     let mut scanned = 0;
+    let mut fills = 0;
 
     // start from best maker price
     let mut price_opt = book.best_price(maker_side);
@@ -194,13 +361,19 @@ pub fn preview_fillable<B: Book>(
                 .ok_or(MatchError::BrokenBook(BookInvariant::LevelHeadMissingMaker))?;
 
             validate_maker_view(&maker, maker_side, price)?;
-            let fill = remaining.min(maker.remaining_base);
-
-            remaining = remaining
-                .checked_sub(fill)
-                .ok_or(MatchError::SubUnderflow)?;
-            if remaining.is_zero() {
-                return Ok(true);
+            if !maker.is_expired(now) {
+                let fill = remaining.min(maker.remaining_base);
+
+                remaining = remaining
+                    .checked_sub(fill)
+                    .ok_or(MatchError::SubUnderflow)?;
+                if remaining.is_zero() {
+                    return Ok(true);
+                }
+                fills += 1;
+                if max_fills != 0 && fills >= max_fills {
+                    return Ok(false);
+                }
             }
             match book.next_in_level(h) {
                 Some(next) => {
@@ -225,12 +398,179 @@ pub fn preview_fillable<B: Book>(
     Ok(false)
 }
 
+/// Preview how a limit `order` splits into `(filled_base, rest_base)` without mutating
+/// the book. Sweep limits end the fill early and cancel the rest, exactly as `execute` does;
+/// a `reduce_only` order never rests.
+pub fn preview_limit_rest<B: Book>(
+    book: &B,
+    order: &IncomingOrder,
+    limits: EngineLimits,
+) -> Result<(U256, U256), MatchError> {
+    let maker_side = order.side.opposite();
+    let mut remaining = order.amount_base;
+    let mut fills: usize = 0;
+    let mut scanned = 0;
+
+    let mut price_opt = book.best_price(maker_side);
+    'levels: while let Some(price) = price_opt {
+        if remaining.is_zero() || !crosses(order.side, order.limit_price, price) {
+            break;
+        }
+        let mut h = book
+            .level_head(maker_side, price)
+            .ok_or(MatchError::BrokenBook(BookInvariant::BestPriceHasNoHead))?;
+        loop {
+            let traded = order
+                .amount_base
+                .checked_sub(remaining)
+                .ok_or(MatchError::SubUnderflow)?;
+            if sweep_capped(&limits, fills, traded) {
+                return Ok((traded, U256::zero()));
+            }
+
+            scanned += 1;
+            if scanned > limits.max_preview_scans {
+                return Err(MatchError::ScanLimitReached {
+                    max_scanned: limits.max_preview_scans,
+                });
+            }
+
+            let maker = book
+                .get_maker(h)
+                .ok_or(MatchError::BrokenBook(BookInvariant::LevelHeadMissingMaker))?;
+            validate_maker_view(&maker, maker_side, price)?;
+
+            if !maker.is_expired(limits.now) {
+                let mut fill = remaining.min(maker.remaining_base);
+                if !limits.sweep_max_base.is_zero() {
+                    fill = fill.min(limits.sweep_max_base - traded);
+                }
+                remaining = remaining
+                    .checked_sub(fill)
+                    .ok_or(MatchError::SubUnderflow)?;
+                fills += 1;
+                if remaining.is_zero() {
+                    break 'levels;
+                }
+            }
+            match book.next_in_level(h) {
+                Some(next) => {
+                    if next == h {
+                        return Err(MatchError::BrokenBook(BookInvariant::NextInLevelSelfLoop));
+                    }
+                    h = next;
+                }
+                None => break,
+            }
+        }
+        price_opt = book.next_price(maker_side, price);
+        if let Some(next_price) = price_opt {
+            if next_price == price {
+                return Err(MatchError::BrokenBook(
+                    BookInvariant::NextPriceDidNotAdvance,
+                ));
+            }
+        }
+    }
+
+    let filled = order
+        .amount_base
+        .checked_sub(remaining)
+        .ok_or(MatchError::SubUnderflow)?;
+    // `execute` checks the caps before looking for liquidity, so a capped order cancels
+    // its remainder even when nothing else crosses.
+    if order.reduce_only || (!remaining.is_zero() && sweep_capped(&limits, fills, filled)) {
+        return Ok((filled, U256::zero()));
+    }
+    Ok((filled, remaining))
+}
+
+/// Preview how much of `order` would meet the taker's own resting orders.
+/// Walks the crossing makers in matching order without mutating the book.
+pub fn preview_self_trade<B: Book>(
+    book: &B,
+    order: &IncomingOrder,
+    max_scanned: u32,
+    now: u64,
+) -> Result<U256, MatchError> {
+    let maker_side = order.side.opposite();
+    let mut remaining = order.amount_base;
+    let mut blocked = U256::zero();
+
+    let mut scanned = 0;
+
+    let mut price_opt = book.best_price(maker_side);
+    while let Some(price) = price_opt {
+        if order.kind != OrderKind::Market && !crosses(order.side, order.limit_price, price) {
+            break;
+        }
+        let mut h = book
+            .level_head(maker_side, price)
+            .ok_or(MatchError::BrokenBook(BookInvariant::BestPriceHasNoHead))?;
+        loop {
+            scanned += 1;
+
+            if scanned > max_scanned {
+                return Err(MatchError::ScanLimitReached { max_scanned });
+            }
+
+            let maker = book
+                .get_maker(h)
+                .ok_or(MatchError::BrokenBook(BookInvariant::LevelHeadMissingMaker))?;
+
+            validate_maker_view(&maker, maker_side, price)?;
+            if !maker.is_expired(now) {
+                let fill = remaining.min(maker.remaining_base);
+                if maker.owner == order.owner {
+                    blocked = blocked.checked_add(fill).ok_or(MatchError::AddOverflow)?;
+                }
+
+                remaining = remaining
+                    .checked_sub(fill)
+                    .ok_or(MatchError::SubUnderflow)?;
+                if remaining.is_zero() {
+                    return Ok(blocked);
+                }
+            }
+            match book.next_in_level(h) {
+                Some(next) => {
+                    if next == h {
+                        return Err(MatchError::BrokenBook(BookInvariant::NextInLevelSelfLoop));
+                    }
+                    h = next;
+                }
+                None => break,
+            }
+        }
+        price_opt = book.next_price(maker_side, price);
+        if let Some(next_price) = price_opt {
+            if next_price == price {
+                return Err(MatchError::BrokenBook(
+                    BookInvariant::NextPriceDidNotAdvance,
+                ));
+            }
+        }
+    }
+
+    Ok(blocked)
+}
+
 /// Matching algorithm:
 /// - price-time priority (best price, FIFO within level)
 /// - Market ignores limit_price
+/// - worst_price (Market/IOC) stops matching at that price and cancels the rest
 /// - Limit places remainder
-/// - IOC cancels remainder
-/// - FOK prechecks via preview_fillable; if not fillable => no mutations
+/// - IOC and a `reduce_only` Limit cancel remainder
+/// - FOK prechecks via preview_fillable, counting makers against `sweep_max_fills`;
+///   if not fillable => no mutations
+/// - sweep limits stop matching once hit and cancel the rest of any non-FOK kind;
+///   a FOK larger than `sweep_max_base` is rejected up front
+/// - self-trade prevention applies `order.stp` to makers owned by the taker;
+///   a FOK that would meet one is rejected up front
+/// - a market SELL with `min_quote` prechecks via preview_market_sell_min_quote and is
+///   rejected with no mutations if the reachable bids can't pay that much
+/// - makers whose `expires_at` is before `limits.now` are removed unfilled and reported
+///   in `expired`; the previews skip them the same way
 pub fn execute<B: Book>(
     book: &mut B,
     order: &IncomingOrder,
@@ -238,18 +578,41 @@ pub fn execute<B: Book>(
 ) -> Result<ExecutionReport, MatchError> {
     validate(order)?;
 
-    let is_strict_market_buy = order.kind == OrderKind::Market && order.side == Side::Buy;
+    let by_quote = is_quote_budget_buy(order);
+    let is_strict_market_buy =
+        order.kind == OrderKind::Market && order.side == Side::Buy && !by_quote;
     if is_strict_market_buy {
         preview_market_buy_budget_strict(book, order, limits)?;
     }
+    let guards_min_quote = !order.min_quote.is_zero();
+    if guards_min_quote {
+        preview_market_sell_min_quote(book, order, limits)?;
+    }
 
     // FOK precheck: MUST NOT mutate the book when failing
     if order.kind == OrderKind::FillOrKill {
-        let ok = preview_fillable(book, order, limits.max_preview_scans)?;
+        let over_sweep =
+            !limits.sweep_max_base.is_zero() && order.amount_base > limits.sweep_max_base;
+        // a FOK can't be guaranteed filled once self-trade prevention skips own makers
+        let self_blocked = order.stp != StpMode::None
+            && !preview_self_trade(book, order, limits.max_preview_scans, limits.now)?.is_zero();
+        let ok = !over_sweep
+            && !self_blocked
+            && fillable_within(
+                book,
+                order,
+                limits.max_preview_scans,
+                limits.now,
+                limits.sweep_max_fills,
+            )?;
         if !ok {
             return Ok(ExecutionReport {
                 trades: Vec::new(),
+                spent_quote: U256::zero(),
+                spent_base: U256::zero(),
                 completion: Completion::Rejected,
+                stp_released: Vec::new(),
+                expired: Vec::new(),
             });
         }
     }
@@ -261,13 +624,32 @@ pub fn execute<B: Book>(
     let track_limit_buy_quote = order.kind == OrderKind::Limit && order.side == Side::Buy;
     let mut remaining_quote = if track_limit_buy_quote {
         // reserve for whole order on LIMIT price (ceil)
-        calc_quote_ceil(order.amount_base, order.limit_price)?
+        calc_quote_ceil_scaled(order.amount_base, order.limit_price, book.price_exponent())?
     } else {
         U256::zero()
     };
 
-    while !remaining.is_zero() {
-        if trades.len() >= limits.max_trades as usize {
+    let mut stp_released: Vec<StpRelease> = Vec::new();
+    let mut expired: Vec<ExpiredMaker> = Vec::new();
+    // set once self-trade prevention skipped a maker the strict market-buy preview counted on
+    let mut stp_hit = false;
+
+    let mut halted = false;
+    let mut traded = U256::zero();
+    // quote-budget buy: set once the leftover budget cannot buy a single base atom
+    let mut budget_spent = false;
+
+    while by_quote || !remaining.is_zero() {
+        if by_quote && spent_quote >= order.max_quote {
+            budget_spent = true;
+            break;
+        }
+        if sweep_capped(&limits, trades.len(), traded) {
+            halted = true;
+            break;
+        }
+
+        if trades.len() + stp_released.len() + expired.len() >= limits.max_trades as usize {
             return Err(MatchError::TradeLimitReached {
                 max_trades: limits.max_trades,
             });
@@ -282,6 +664,9 @@ pub fn execute<B: Book>(
         if order.kind != OrderKind::Market && !crosses(order.side, order.limit_price, price) {
             break;
         }
+        if !within_worst_price(order, price) {
+            break;
+        }
 
         let h = book
             .level_head(maker_side, price)
@@ -293,19 +678,111 @@ pub fn execute<B: Book>(
 
         validate_maker_view(&maker, maker_side, price)?;
 
-        let fill = remaining.min(maker.remaining_base);
+        if maker.is_expired(limits.now) {
+            book.remove_maker(h);
+            expired.push(maker.into());
+            continue;
+        }
 
-        let quote = calc_quote_floor(fill, price)?;
-        if is_strict_market_buy {
-            spent_quote = spent_quote
-                .checked_add(quote)
-                .ok_or(MatchError::AddOverflow)?;
-            if spent_quote > order.max_quote {
-                // after successfull preview it must be impossible
-                return Err(MatchError::MarketBuyBudgetCheckInconsistent);
+        if maker.owner == order.owner && order.stp != StpMode::None {
+            match order.stp {
+                StpMode::CancelResting => {
+                    book.remove_maker(h);
+                    stp_released.push(StpRelease {
+                        maker_order_id: maker.id,
+                        removed: true,
+                        released_base: if maker.side == Side::Sell {
+                            maker.remaining_base
+                        } else {
+                            U256::zero()
+                        },
+                        released_quote: maker.reserved_quote,
+                    });
+                    stp_hit = true;
+                    continue;
+                }
+                // a quote-budget buy has no base to decrement, so it stops as well
+                StpMode::DecrementBoth if !by_quote => {
+                    let cut = remaining.min(maker.remaining_base);
+                    let maker_new = maker.remaining_base - cut;
+                    let mut released_quote = U256::zero();
+                    if maker.side == Side::Buy {
+                        released_quote = if maker_new.is_zero() {
+                            maker.reserved_quote
+                        } else {
+                            calc_quote_floor_scaled(cut, price, book.price_exponent())?
+                        };
+                        let new_rq = maker
+                            .reserved_quote
+                            .checked_sub(released_quote)
+                            .ok_or(MatchError::SubUnderflow)?;
+                        book.set_maker_reserved_quote(h, new_rq);
+                    }
+                    if maker_new.is_zero() {
+                        book.remove_maker(h);
+                    } else {
+                        book.set_maker_remaining(h, maker_new);
+                    }
+                    stp_released.push(StpRelease {
+                        maker_order_id: maker.id,
+                        removed: maker_new.is_zero(),
+                        released_base: if maker.side == Side::Sell {
+                            cut
+                        } else {
+                            U256::zero()
+                        },
+                        released_quote,
+                    });
+
+                    if track_limit_buy_quote {
+                        let cut_quote =
+                            calc_quote_floor_scaled(cut, order.limit_price, book.price_exponent())?;
+                        remaining_quote = remaining_quote
+                            .checked_sub(cut_quote)
+                            .ok_or(MatchError::SubUnderflow)?;
+                    }
+                    remaining = remaining.checked_sub(cut).ok_or(MatchError::SubUnderflow)?;
+                    continue;
+                }
+                _ => {
+                    halted = true;
+                    break;
+                }
             }
         }
 
+        let budget_bound = by_quote || (is_strict_market_buy && stp_hit);
+        let mut fill = if by_quote {
+            maker.remaining_base
+        } else {
+            remaining.min(maker.remaining_base)
+        };
+        if budget_bound {
+            let budget_left = order
+                .max_quote
+                .checked_sub(spent_quote)
+                .ok_or(MatchError::SubUnderflow)?;
+            let affordable = calc_base_floor_scaled(budget_left, price, book.price_exponent())?;
+            fill = fill.min(affordable);
+        }
+        if !limits.sweep_max_base.is_zero() {
+            fill = fill.min(limits.sweep_max_base - traded);
+        }
+
+        let quote = calc_quote_floor_scaled(fill, price, book.price_exponent())?;
+        if budget_bound && quote.is_zero() {
+            // leftover budget is dust at this price; settlement refunds it
+            budget_spent = true;
+            break;
+        }
+        spent_quote = spent_quote
+            .checked_add(quote)
+            .ok_or(MatchError::AddOverflow)?;
+        if (is_strict_market_buy || by_quote) && spent_quote > order.max_quote {
+            // after successfull preview it must be impossible
+            return Err(MatchError::MarketBuyBudgetCheckInconsistent);
+        }
+
         if track_limit_buy_quote {
             remaining_quote = remaining_quote
                 .checked_sub(quote)
@@ -344,24 +821,60 @@ pub fn execute<B: Book>(
         }
 
         // update taker
-        remaining = remaining
-            .checked_sub(fill)
-            .ok_or(MatchError::SubUnderflow)?;
+        traded = traded.checked_add(fill).ok_or(MatchError::AddOverflow)?;
+        if !by_quote {
+            remaining = remaining
+                .checked_sub(fill)
+                .ok_or(MatchError::SubUnderflow)?;
+        }
     }
 
     // finalize
-    if is_strict_market_buy && !remaining.is_zero() {
+    if guards_min_quote && spent_quote < order.min_quote {
+        // after successfull preview it must be impossible
+        return Err(MatchError::MarketSellMinQuoteCheckInconsistent);
+    }
+    if halted {
+        return Ok(ExecutionReport {
+            trades,
+            spent_quote,
+            spent_base: traded,
+            completion: Completion::Cancelled {
+                remaining_base: remaining,
+            },
+            stp_released,
+            expired,
+        });
+    }
+    if is_strict_market_buy && order.worst_price.is_none() && !stp_hit && !remaining.is_zero() {
         // after successfull preview it must be impossible
         return Err(MatchError::MarketBuyLiquidityCheckInconsistent);
     }
+    if by_quote && !budget_spent {
+        // book or price bound ran out first; the unspent budget is refunded
+        return Ok(ExecutionReport {
+            trades,
+            spent_quote,
+            spent_base: traded,
+            completion: Completion::Cancelled {
+                remaining_base: U256::zero(),
+            },
+            stp_released,
+            expired,
+        });
+    }
     if remaining.is_zero() {
         return Ok(ExecutionReport {
             trades,
+            spent_quote,
+            spent_base: traded,
             completion: Completion::Filled,
+            stp_released,
+            expired,
         });
     }
     match order.kind {
-        OrderKind::Limit => {
+        OrderKind::Limit if !order.reduce_only => {
             let remaining_quote = if track_limit_buy_quote {
                 remaining_quote
             } else {
@@ -374,22 +887,35 @@ pub fn execute<B: Book>(
                 price: order.limit_price,
                 remaining_base: remaining,
                 remaining_quote,
+                expires_at: order.expires_at,
             });
 
             Ok(ExecutionReport {
                 trades,
+                spent_quote,
+                spent_base: traded,
                 completion: Completion::Placed {
                     remaining_base: remaining,
                     remaining_quote,
+                    filled_base: traded,
+                    filled_quote: spent_quote,
                 },
+                stp_released,
+                expired,
+            })
+        }
+        OrderKind::Limit | OrderKind::Market | OrderKind::ImmediateOrCancel => {
+            Ok(ExecutionReport {
+                trades,
+                spent_quote,
+                spent_base: traded,
+                completion: Completion::Cancelled {
+                    remaining_base: remaining,
+                },
+                stp_released,
+                expired,
             })
         }
-        OrderKind::Market | OrderKind::ImmediateOrCancel => Ok(ExecutionReport {
-            trades,
-            completion: Completion::Cancelled {
-                remaining_base: remaining,
-            },
-        }),
         OrderKind::FillOrKill => Err(MatchError::FokCheckInconsistent),
     }
 }
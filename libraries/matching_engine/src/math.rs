@@ -1,19 +1,44 @@
 use sails_rs::U256;
 
 use crate::types::MatchError;
-// 1e30 precision
-const PRICE_PRECISION: u128 = 1_000_000_000_000_000_000_000_000_000_000_000;
-/// quote = floor(base * price / PRICE_PRECISION)
+
+/// Default price exponent: prices are quote atoms per base atom scaled by 1e30.
+pub const DEFAULT_PRICE_EXPONENT: u32 = 30;
+/// Largest exponent whose precision still leaves room for `base * price` in U256.
+pub const MAX_PRICE_EXPONENT: u32 = 38;
+
+fn price_precision(price_exponent: u32) -> U256 {
+    U256::exp10(price_exponent as usize)
+}
+
+/// quote = floor(base * price / 10^DEFAULT_PRICE_EXPONENT)
 pub fn calc_quote_floor(base: U256, price: U256) -> Result<U256, MatchError> {
-    let mul = base.checked_mul(price).ok_or(MatchError::MulOverflow)?;
-    let precision: U256 = U256::from(PRICE_PRECISION);
-    Ok(mul / precision)
+    calc_quote_floor_scaled(base, price, DEFAULT_PRICE_EXPONENT)
 }
 
-/// quote = ceil(base * price / PRICE_PRECISION)
+/// quote = ceil(base * price / 10^DEFAULT_PRICE_EXPONENT)
 pub fn calc_quote_ceil(base: U256, price: U256) -> Result<U256, MatchError> {
+    calc_quote_ceil_scaled(base, price, DEFAULT_PRICE_EXPONENT)
+}
+
+/// quote = floor(base * price / 10^price_exponent)
+pub fn calc_quote_floor_scaled(
+    base: U256,
+    price: U256,
+    price_exponent: u32,
+) -> Result<U256, MatchError> {
+    let mul = base.checked_mul(price).ok_or(MatchError::MulOverflow)?;
+    Ok(mul / price_precision(price_exponent))
+}
+
+/// quote = ceil(base * price / 10^price_exponent)
+pub fn calc_quote_ceil_scaled(
+    base: U256,
+    price: U256,
+    price_exponent: u32,
+) -> Result<U256, MatchError> {
     let mul = base.checked_mul(price).ok_or(MatchError::MulOverflow)?;
-    let precision: U256 = U256::from(PRICE_PRECISION);
+    let precision = price_precision(price_exponent);
     let q = mul / precision;
     let rem = mul % precision;
     if rem.is_zero() {
@@ -22,3 +47,15 @@ pub fn calc_quote_ceil(base: U256, price: U256) -> Result<U256, MatchError> {
         q.checked_add(U256::one()).ok_or(MatchError::AddOverflow)
     }
 }
+
+/// base = floor(quote * 10^price_exponent / price), `price` must be non-zero
+pub fn calc_base_floor_scaled(
+    quote: U256,
+    price: U256,
+    price_exponent: u32,
+) -> Result<U256, MatchError> {
+    let mul = quote
+        .checked_mul(price_precision(price_exponent))
+        .ok_or(MatchError::MulOverflow)?;
+    Ok(mul / price)
+}
@@ -9,11 +9,18 @@ use std::panic;
 
 use crate::{
     book::Book,
-    engine::{execute, preview_fillable},
-    math::calc_quote_floor,
+    engine::{
+        execute, preview_fillable, preview_limit_rest, preview_market_sell_min_quote,
+        preview_self_trade,
+    },
+    math::{
+        calc_quote_ceil, calc_quote_ceil_scaled, calc_quote_floor, calc_quote_floor_scaled,
+        DEFAULT_PRICE_EXPONENT,
+    },
     types::{
-        BookInvariant, Completion, EngineLimits, IncomingOrder, MakerView, MatchError, OrderKind,
-        RestingOrder, Side,
+        BookInvariant, Completion, EngineLimits, ExecutionReport, ExpiredMaker, IncomingOrder,
+        InvalidOrderReason, MakerView, MatchError, OrderKind, RestingOrder, Side, StpMode,
+        TriggerOrder,
     },
 };
 
@@ -169,6 +176,7 @@ impl Book for MockBook {
             price: o.price,
             remaining_base: o.remaining_base,
             reserved_quote: o.remaining_quote,
+            expires_at: o.expires_at,
         });
     }
 }
@@ -191,6 +199,7 @@ fn maker(id: u64, side: Side, price: u64, base: u64, owner: u64) -> MakerView {
         price: u(price),
         remaining_base: u(base),
         reserved_quote,
+        expires_at: 0,
     }
 }
 
@@ -211,6 +220,11 @@ fn taker(
         limit_price: u(limit_price),
         amount_base: u(base),
         max_quote: u(max_quote),
+        worst_price: None,
+        stp: StpMode::None,
+        min_quote: U256::zero(),
+        expires_at: 0,
+        reduce_only: false,
     }
 }
 
@@ -223,6 +237,7 @@ fn limit_no_cross_places_remainder() {
     let limits = EngineLimits {
         max_trades: 100,
         max_preview_scans: 1_000,
+        ..Default::default()
     };
     let order = taker(10, Side::Buy, OrderKind::Limit, 90, 7, 9, 0);
 
@@ -246,6 +261,7 @@ fn limit_cross_partially_then_place_remainder() {
     let limits = EngineLimits {
         max_trades: 100,
         max_preview_scans: 1_000,
+        ..Default::default()
     };
     let order = taker(10, Side::Buy, OrderKind::Limit, 100, 8, 9, 0);
 
@@ -272,6 +288,7 @@ fn ioc_cross_partially_then_cancel_remainder() {
     let limits = EngineLimits {
         max_trades: 100,
         max_preview_scans: 1_000,
+        ..Default::default()
     };
     let order = taker(10, Side::Buy, OrderKind::ImmediateOrCancel, 100, 8, 9, 0);
 
@@ -296,6 +313,7 @@ fn market_sell_consumes_best_bids_in_order() {
     let limits = EngineLimits {
         max_trades: 100,
         max_preview_scans: 1_000,
+        ..Default::default()
     };
     let order = taker(10, Side::Sell, OrderKind::Market, 0, 15, 9, 0);
 
@@ -327,6 +345,7 @@ fn fok_rejects_without_mutating_book() {
     let limits = EngineLimits {
         max_trades: 100,
         max_preview_scans: 10_000,
+        ..Default::default()
     };
     let order = taker(10, Side::Buy, OrderKind::FillOrKill, 100, 8, 9, 0);
 
@@ -347,6 +366,7 @@ fn fok_fills_across_levels() {
     let limits = EngineLimits {
         max_trades: 100,
         max_preview_scans: 10_000,
+        ..Default::default()
     };
     let order = taker(10, Side::Buy, OrderKind::FillOrKill, 101, 8, 9, 0);
 
@@ -373,6 +393,7 @@ fn fifo_same_price_consumes_in_order() {
     let limits = EngineLimits {
         max_trades: 100,
         max_preview_scans: 10_000,
+        ..Default::default()
     };
     let order = taker(10, Side::Buy, OrderKind::Market, 0, 4, 9, 1_000_000);
 
@@ -397,7 +418,7 @@ fn preview_scan_limit_hits() {
 
     let order = taker(10, Side::Buy, OrderKind::FillOrKill, 100, 20, 9, 0);
 
-    let err = preview_fillable(&book, &order, 5).unwrap_err();
+    let err = preview_fillable(&book, &order, 5, 0).unwrap_err();
     assert!(matches!(err, MatchError::ScanLimitReached { .. }));
 }
 
@@ -410,6 +431,7 @@ fn broken_book_best_price_without_head_is_error() {
     let limits = EngineLimits {
         max_trades: 100,
         max_preview_scans: 1_000,
+        ..Default::default()
     };
     let order = taker(10, Side::Buy, OrderKind::Market, 0, 1, 9, 1_000_000);
 
@@ -428,6 +450,7 @@ fn quote_is_floor_like_engine() {
     let limits = EngineLimits {
         max_trades: 100,
         max_preview_scans: 1_000,
+        ..Default::default()
     };
     let order = taker(10, Side::Buy, OrderKind::Market, 0, 7, 9, 1_000_000);
 
@@ -445,9 +468,15 @@ fn invalid_zero_amount_is_rejected() {
     let limits = EngineLimits {
         max_trades: 100,
         max_preview_scans: 1_000,
+        ..Default::default()
     };
 
-    let order = taker(1, Side::Buy, OrderKind::Market, 0, 0, 9, 1_000_000);
+    // a zero-base market BUY spends its quote budget instead; every other order is invalid
+    let order = taker(1, Side::Sell, OrderKind::Market, 0, 0, 9, 0);
+    let err = execute(&mut book, &order, limits).unwrap_err();
+    assert!(matches!(err, MatchError::InvalidOrder(_)));
+
+    let order = taker(2, Side::Buy, OrderKind::Limit, 100, 0, 9, 0);
     let err = execute(&mut book, &order, limits).unwrap_err();
     assert!(matches!(err, MatchError::InvalidOrder(_)));
 }
@@ -458,6 +487,7 @@ fn invalid_non_market_zero_limit_price() {
     let limits = EngineLimits {
         max_trades: 100,
         max_preview_scans: 1_000,
+        ..Default::default()
     };
 
     let order = taker(1, Side::Buy, OrderKind::Limit, 0, 10, 9, 0);
@@ -474,6 +504,7 @@ fn limit_buy_does_not_take_worse_than_limit() {
     let limits = EngineLimits {
         max_trades: 100,
         max_preview_scans: 1_000,
+        ..Default::default()
     };
     let order = taker(10, Side::Buy, OrderKind::Limit, 100, 5, 9, 0);
 
@@ -498,6 +529,7 @@ fn trade_limit_reached() {
     let limits = EngineLimits {
         max_trades: 3,
         max_preview_scans: 1_000,
+        ..Default::default()
     };
     let order = taker(10, Side::Buy, OrderKind::Market, 0, 10, 9, 1_000_000);
 
@@ -515,6 +547,7 @@ fn trade_prices_monotonic_for_buy() {
     let limits = EngineLimits {
         max_trades: 100,
         max_preview_scans: 1_000,
+        ..Default::default()
     };
     let order = taker(10, Side::Buy, OrderKind::Market, 0, 7, 9, 1_000_000);
 
@@ -534,6 +567,7 @@ fn trade_prices_monotonic_for_sell() {
     let limits = EngineLimits {
         max_trades: 100,
         max_preview_scans: 1_000,
+        ..Default::default()
     };
     let order = taker(10, Side::Sell, OrderKind::Market, 0, 7, 9, 0);
 
@@ -542,3 +576,787 @@ fn trade_prices_monotonic_for_sell() {
         assert!(w[0].price >= w[1].price);
     }
 }
+
+#[test]
+fn preview_self_trade_counts_own_makers_within_crossing_range() {
+    let mut book = MockBook::new();
+    book.push_maker(maker(1, Side::Sell, 100, 3, 9));
+    book.push_maker(maker(2, Side::Sell, 100, 4, 1));
+    book.push_maker(maker(3, Side::Sell, 101, 5, 9));
+    book.push_maker(maker(4, Side::Sell, 105, 5, 9));
+
+    // Crosses 100 and 101 only; 3 + 3 of the 10 base would hit owner 9's own asks.
+    let order = taker(10, Side::Buy, OrderKind::Limit, 101, 10, 9, 0);
+    assert_eq!(preview_self_trade(&book, &order, 100, 0).unwrap(), u(6));
+
+    // A smaller order stops before reaching the second own maker.
+    let order = taker(11, Side::Buy, OrderKind::Limit, 101, 5, 9, 0);
+    assert_eq!(preview_self_trade(&book, &order, 100, 0).unwrap(), u(3));
+
+    // Another owner is never blocked, and the book is untouched.
+    let order = taker(12, Side::Buy, OrderKind::Limit, 105, 20, 7, 0);
+    assert_eq!(
+        preview_self_trade(&book, &order, 100, 0).unwrap(),
+        U256::zero()
+    );
+    assert_eq!(book.maker_remaining_at_head(Side::Sell, u(100)), Some(u(3)));
+}
+
+#[test]
+fn quote_scaling_follows_price_exponent() {
+    // 2 base atoms at 2.5 quote atoms per base atom, expressed with two exponents.
+    let base = u(2);
+    let price_e30 = U256::from(25u64) * U256::exp10(29);
+    let price_e2 = u(250);
+
+    assert_eq!(calc_quote_floor(base, price_e30).unwrap(), u(5));
+    assert_eq!(calc_quote_floor_scaled(base, price_e30, 30).unwrap(), u(5));
+    assert_eq!(calc_quote_floor_scaled(base, price_e2, 2).unwrap(), u(5));
+
+    // 3 base atoms -> 7.5 quote atoms: floor and ceil must agree across exponents.
+    let base = u(3);
+    assert_eq!(calc_quote_floor_scaled(base, price_e30, 30).unwrap(), u(7));
+    assert_eq!(calc_quote_floor_scaled(base, price_e2, 2).unwrap(), u(7));
+    assert_eq!(calc_quote_ceil_scaled(base, price_e30, 30).unwrap(), u(8));
+    assert_eq!(calc_quote_ceil_scaled(base, price_e2, 2).unwrap(), u(8));
+}
+
+#[test]
+fn default_quote_math_matches_scaled_at_default_exponent() {
+    let prices = [
+        u(1),
+        U256::exp10(30),
+        U256::from(2_000_000_000u64) * U256::exp10(18) + u(7),
+    ];
+    let bases = [u(0), u(1), u(3), U256::exp10(18), U256::from(u128::MAX)];
+    for price in prices {
+        for base in bases {
+            assert_eq!(
+                calc_quote_floor(base, price),
+                calc_quote_floor_scaled(base, price, DEFAULT_PRICE_EXPONENT)
+            );
+            assert_eq!(
+                calc_quote_ceil(base, price),
+                calc_quote_ceil_scaled(base, price, DEFAULT_PRICE_EXPONENT)
+            );
+        }
+    }
+}
+
+#[test]
+fn trigger_touch_direction_and_market_conversion() {
+    let buy = TriggerOrder {
+        id: 7,
+        owner: 9u64.into(),
+        side: Side::Buy,
+        trigger_price: u(100),
+        amount_base: u(5),
+        max_quote: u(1_000),
+    };
+    assert!(!buy.is_touched(u(99)));
+    assert!(buy.is_touched(u(100)));
+    assert!(buy.is_touched(u(101)));
+
+    let sell = TriggerOrder {
+        side: Side::Sell,
+        max_quote: U256::zero(),
+        ..buy.clone()
+    };
+    assert!(sell.is_touched(u(99)));
+    assert!(sell.is_touched(u(100)));
+    assert!(!sell.is_touched(u(101)));
+
+    // Converted order executes as a strict market buy within its budget.
+    let mut book = MockBook::new();
+    book.push_maker(maker(1, Side::Sell, 100, 3, 1));
+    book.push_maker(maker(2, Side::Sell, 120, 3, 2));
+
+    let order = buy.to_market();
+    assert_eq!(order.id, 7);
+    assert_eq!(order.kind, OrderKind::Market);
+
+    let limits = EngineLimits {
+        max_trades: 100,
+        max_preview_scans: 1_000,
+        ..Default::default()
+    };
+    let rep = execute(&mut book, &order, limits).unwrap();
+    assert_eq!(rep.trades.len(), 2);
+    assert!(matches!(rep.completion, Completion::Filled));
+}
+
+#[test]
+fn worst_price_stops_market_sell_and_cancels_rest() {
+    let mut book = MockBook::new();
+    book.push_maker(maker(1, Side::Buy, 100, 3, 1));
+    book.push_maker(maker(2, Side::Buy, 99, 3, 2));
+    book.push_maker(maker(3, Side::Buy, 98, 3, 3));
+
+    let limits = EngineLimits {
+        max_trades: 100,
+        max_preview_scans: 1_000,
+        ..Default::default()
+    };
+    let mut order = taker(10, Side::Sell, OrderKind::Market, 0, 8, 9, 0);
+    order.worst_price = Some(u(99));
+
+    let rep = execute(&mut book, &order, limits).unwrap();
+    assert_eq!(rep.trades.len(), 2);
+    assert_eq!(rep.trades[0].price, u(100));
+    assert_eq!(rep.trades[1].price, u(99));
+    match rep.completion {
+        Completion::Cancelled { remaining_base } => assert_eq!(remaining_base, u(2)),
+        x => panic!("unexpected completion: {:?}", x),
+    }
+
+    // The level beyond the bound is untouched.
+    assert_eq!(book.maker_remaining_at_head(Side::Buy, u(98)), Some(u(3)));
+}
+
+#[test]
+fn worst_price_turns_strict_market_buy_into_partial_fill() {
+    let mut book = MockBook::new();
+    book.push_maker(maker(1, Side::Sell, 100, 3, 1));
+    book.push_maker(maker(2, Side::Sell, 110, 3, 2));
+
+    let limits = EngineLimits {
+        max_trades: 100,
+        max_preview_scans: 1_000,
+        ..Default::default()
+    };
+    let mut order = taker(10, Side::Buy, OrderKind::Market, 0, 5, 9, 1_000_000);
+    order.worst_price = Some(u(105));
+
+    let rep = execute(&mut book, &order, limits).unwrap();
+    assert_eq!(rep.trades.len(), 1);
+    assert_eq!(rep.trades[0].amount_base, u(3));
+    assert!(matches!(
+        rep.completion,
+        Completion::Cancelled { remaining_base } if remaining_base == u(2)
+    ));
+    assert_eq!(book.maker_remaining_at_head(Side::Sell, u(110)), Some(u(3)));
+
+    // A bound on a Limit order is rejected.
+    let mut limit = taker(11, Side::Buy, OrderKind::Limit, 110, 1, 9, 0);
+    limit.worst_price = Some(u(105));
+    assert!(matches!(
+        execute(&mut book, &limit, limits),
+        Err(MatchError::InvalidOrder(
+            InvalidOrderReason::WorstPriceOnlyForMarketOrIoc
+        ))
+    ));
+}
+
+#[test]
+fn sweep_limits_cancel_remainder_instead_of_erroring() {
+    let mut book = MockBook::new();
+    book.push_maker(maker(1, Side::Sell, 100, 3, 1));
+    book.push_maker(maker(2, Side::Sell, 100, 3, 2));
+    book.push_maker(maker(3, Side::Sell, 101, 3, 3));
+
+    let limits = EngineLimits {
+        max_trades: 100,
+        max_preview_scans: 1_000,
+        sweep_max_fills: 0,
+        sweep_max_base: u(4),
+        now: 0,
+    };
+    // A limit taker is capped too: nothing of it rests on the book.
+    let order = taker(10, Side::Buy, OrderKind::Limit, 101, 9, 9, 0);
+    let rep = execute(&mut book, &order, limits).unwrap();
+    assert_eq!(rep.trades.len(), 2);
+    assert_eq!(rep.trades[1].amount_base, u(1));
+    assert!(matches!(
+        rep.completion,
+        Completion::Cancelled { remaining_base } if remaining_base == u(5)
+    ));
+    assert_eq!(book.maker_remaining_at_head(Side::Sell, u(100)), Some(u(2)));
+    assert_eq!(book.best_price(Side::Buy), None);
+
+    let limits = EngineLimits {
+        sweep_max_fills: 1,
+        sweep_max_base: U256::zero(),
+        ..limits
+    };
+    let order = taker(11, Side::Sell, OrderKind::Market, 0, 5, 9, 0);
+    book.push_maker(maker(4, Side::Buy, 99, 2, 4));
+    book.push_maker(maker(5, Side::Buy, 98, 2, 5));
+    let rep = execute(&mut book, &order, limits).unwrap();
+    assert_eq!(rep.trades.len(), 1);
+    assert!(matches!(
+        rep.completion,
+        Completion::Cancelled { remaining_base } if remaining_base == u(3)
+    ));
+}
+
+#[test]
+fn fill_or_kill_is_rejected_when_sweep_max_fills_would_halt_it() {
+    let mut book = MockBook::new();
+    book.push_maker(maker(1, Side::Sell, 100, 3, 1));
+    book.push_maker(maker(2, Side::Sell, 100, 3, 2));
+    book.push_maker(maker(3, Side::Sell, 101, 3, 3));
+
+    let limits = EngineLimits {
+        max_trades: 100,
+        max_preview_scans: 1_000,
+        sweep_max_fills: 2,
+        sweep_max_base: U256::zero(),
+        now: 0,
+    };
+    let order = taker(10, Side::Buy, OrderKind::FillOrKill, 101, 9, 9, 0);
+    let rep = execute(&mut book, &order, limits).unwrap();
+    assert!(rep.trades.is_empty());
+    assert!(matches!(rep.completion, Completion::Rejected));
+    assert_eq!(book.maker_remaining_at_head(Side::Sell, u(100)), Some(u(3)));
+    assert_eq!(book.maker_remaining_at_head(Side::Sell, u(101)), Some(u(3)));
+
+    // Two makers are enough for 6, so the same cap lets it through.
+    let order = taker(11, Side::Buy, OrderKind::FillOrKill, 101, 6, 9, 0);
+    let rep = execute(&mut book, &order, limits).unwrap();
+    assert_eq!(rep.trades.len(), 2);
+    assert!(matches!(rep.completion, Completion::Filled));
+}
+
+#[test]
+fn placed_limit_buy_reports_filled_and_resting_parts() {
+    let mut book = MockBook::new();
+    // Prices large enough that fills carry whole quote atoms.
+    book.push_maker(maker(
+        1,
+        Side::Sell,
+        2_000_000_000_000_000_000,
+        3_000_000_000_000,
+        1,
+    ));
+    book.push_maker(maker(
+        2,
+        Side::Sell,
+        2_100_000_000_000_000_000,
+        2_000_000_000_000,
+        2,
+    ));
+
+    let limits = EngineLimits {
+        max_trades: 100,
+        max_preview_scans: 1_000,
+        ..Default::default()
+    };
+    let order = taker(
+        10,
+        Side::Buy,
+        OrderKind::Limit,
+        2_100_000_000_000_000_000,
+        8_000_000_000_000,
+        9,
+        0,
+    );
+
+    let rep = execute(&mut book, &order, limits).unwrap();
+    assert_eq!(rep.trades.len(), 2);
+
+    // 3e12 @ 2e18 -> 6, 2e12 @ 2.1e18 -> 4; the 3e12 rest reserves ceil(16.8) - 10.
+    match rep.completion {
+        Completion::Placed {
+            remaining_base,
+            remaining_quote,
+            filled_base,
+            filled_quote,
+        } => {
+            assert_eq!(filled_base, u(5_000_000_000_000));
+            assert_eq!(filled_quote, u(10));
+            assert_eq!(remaining_base, u(3_000_000_000_000));
+            assert_eq!(remaining_quote, u(7));
+        }
+        x => panic!("unexpected completion: {:?}", x),
+    }
+}
+
+#[test]
+fn preview_limit_rest_matches_execute_split() {
+    let mut book = MockBook::new();
+    book.push_maker(maker(1, Side::Sell, 100, 3, 1));
+    book.push_maker(maker(2, Side::Sell, 101, 2, 2));
+    book.push_maker(maker(3, Side::Sell, 105, 4, 3));
+
+    let limits = EngineLimits {
+        max_trades: 100,
+        max_preview_scans: 1_000,
+        ..Default::default()
+    };
+    let order = taker(10, Side::Buy, OrderKind::Limit, 101, 8, 9, 0);
+
+    let (filled, rest) = preview_limit_rest(&book, &order, limits).unwrap();
+    assert_eq!((filled, rest), (u(5), u(3)));
+    // Read-only.
+    assert_eq!(book.maker_remaining_at_head(Side::Sell, u(100)), Some(u(3)));
+
+    let rep = execute(&mut book, &order, limits).unwrap();
+    assert!(matches!(
+        rep.completion,
+        Completion::Placed { remaining_base, filled_base, .. }
+            if remaining_base == rest && filled_base == filled
+    ));
+
+    // A sweep cap cancels the rest instead of resting it.
+    let capped = EngineLimits {
+        sweep_max_fills: 1,
+        ..limits
+    };
+    let order = taker(11, Side::Buy, OrderKind::Limit, 105, 6, 9, 0);
+    assert_eq!(
+        preview_limit_rest(&book, &order, capped).unwrap(),
+        (u(4), U256::zero())
+    );
+}
+
+#[test]
+fn market_buy_by_quote_spends_budget_across_levels() {
+    let mut book = MockBook::new();
+    book.push_maker(maker(
+        1,
+        Side::Sell,
+        2_000_000_000_000_000_000,
+        10_000_000_000_000,
+        1,
+    ));
+    book.push_maker(maker(
+        2,
+        Side::Sell,
+        3_000_000_000_000_000_000,
+        10_000_000_000_000,
+        2,
+    ));
+
+    let limits = EngineLimits {
+        max_trades: 100,
+        max_preview_scans: 1_000,
+        ..Default::default()
+    };
+    // amount_base == 0: spend exactly 35 quote atoms instead of buying a fixed base amount
+    let order = taker(10, Side::Buy, OrderKind::Market, 0, 0, 9, 35);
+
+    let rep = execute(&mut book, &order, limits).unwrap();
+    assert_eq!(rep.trades.len(), 2);
+
+    // 1e13 @ 2e18 -> 20, then the 15 left buys 5e12 @ 3e18
+    assert_eq!(rep.trades[0].amount_base, u(10_000_000_000_000));
+    assert_eq!(rep.trades[0].amount_quote, u(20));
+    assert_eq!(rep.trades[1].amount_base, u(5_000_000_000_000));
+    assert_eq!(rep.trades[1].amount_quote, u(15));
+
+    match rep.completion {
+        Completion::Filled => {}
+        x => panic!("unexpected completion: {:?}", x),
+    }
+    assert_eq!(
+        book.maker_remaining_at_head(Side::Sell, u(3_000_000_000_000_000_000)),
+        Some(u(5_000_000_000_000))
+    );
+}
+
+#[test]
+fn market_buy_by_quote_stops_on_dust_or_empty_book() {
+    let limits = EngineLimits {
+        max_trades: 100,
+        max_preview_scans: 1_000,
+        ..Default::default()
+    };
+
+    // 8 quote at 3e18 buys floor(8e30 / 3e18) base for 7; the last atom is dust
+    let mut book = MockBook::new();
+    book.push_maker(maker(
+        1,
+        Side::Sell,
+        3_000_000_000_000_000_000,
+        10_000_000_000_000,
+        1,
+    ));
+    let order = taker(10, Side::Buy, OrderKind::Market, 0, 0, 9, 8);
+
+    let rep = execute(&mut book, &order, limits).unwrap();
+    assert_eq!(rep.trades.len(), 1);
+    assert_eq!(rep.trades[0].amount_base, u(2_666_666_666_666));
+    assert_eq!(rep.trades[0].amount_quote, u(7));
+    match rep.completion {
+        Completion::Filled => {}
+        x => panic!("unexpected completion: {:?}", x),
+    }
+
+    // budget larger than the book: take everything, cancel with nothing left to buy
+    let mut book = MockBook::new();
+    book.push_maker(maker(
+        1,
+        Side::Sell,
+        2_000_000_000_000_000_000,
+        10_000_000_000_000,
+        1,
+    ));
+    let order = taker(11, Side::Buy, OrderKind::Market, 0, 0, 9, 100);
+
+    let rep = execute(&mut book, &order, limits).unwrap();
+    assert_eq!(rep.trades.len(), 1);
+    assert_eq!(rep.trades[0].amount_quote, u(20));
+    match rep.completion {
+        Completion::Cancelled { remaining_base } => assert!(remaining_base.is_zero()),
+        x => panic!("unexpected completion: {:?}", x),
+    }
+    assert!(book
+        .peek_level(Side::Sell, u(2_000_000_000_000_000_000))
+        .is_none());
+}
+
+#[test]
+fn stp_modes_handle_self_owned_best_maker() {
+    let limits = EngineLimits {
+        max_trades: 100,
+        max_preview_scans: 1_000,
+        ..Default::default()
+    };
+    // owner 9 rests the best ask; owner 2 sits one tick behind
+    let book_with_own_ask = |own_base: u64| {
+        let mut book = MockBook::new();
+        book.push_maker(maker(1, Side::Sell, 100, own_base, 9));
+        book.push_maker(maker(2, Side::Sell, 101, 5, 2));
+        book
+    };
+    let own_taker = |stp: StpMode| IncomingOrder {
+        stp,
+        ..taker(10, Side::Buy, OrderKind::Limit, 101, 4, 9, 0)
+    };
+
+    // CancelResting: own ask is pulled, the taker fills against owner 2
+    let mut book = book_with_own_ask(5);
+    let rep = execute(&mut book, &own_taker(StpMode::CancelResting), limits).unwrap();
+    assert_eq!(rep.completion, Completion::Filled);
+    assert_eq!(rep.trades.len(), 1);
+    assert_eq!(rep.trades[0].maker_order_id, 2);
+    assert_eq!(rep.trades[0].amount_base, u(4));
+    assert_eq!(rep.stp_released.len(), 1);
+    assert!(rep.stp_released[0].removed);
+    assert_eq!(rep.stp_released[0].released_base, u(5));
+    assert!(book.peek_level(Side::Sell, u(100)).is_none());
+
+    // CancelTaker: nothing trades and the own ask stays
+    let mut book = book_with_own_ask(5);
+    let rep = execute(&mut book, &own_taker(StpMode::CancelTaker), limits).unwrap();
+    assert!(rep.trades.is_empty());
+    assert_eq!(
+        rep.completion,
+        Completion::Cancelled {
+            remaining_base: u(4)
+        }
+    );
+    assert_eq!(book.maker_remaining_at_head(Side::Sell, u(100)), Some(u(5)));
+
+    // DecrementBoth: 3 cancels out on both sides, the last 1 trades with owner 2
+    let mut book = book_with_own_ask(3);
+    let rep = execute(&mut book, &own_taker(StpMode::DecrementBoth), limits).unwrap();
+    assert_eq!(rep.completion, Completion::Filled);
+    assert_eq!(rep.trades.len(), 1);
+    assert_eq!(rep.trades[0].amount_base, u(1));
+    assert_eq!(rep.stp_released[0].released_base, u(3));
+    assert!(book.peek_level(Side::Sell, u(100)).is_none());
+    assert_eq!(book.maker_remaining_at_head(Side::Sell, u(101)), Some(u(4)));
+
+    // None keeps the old behaviour and self-matches
+    let mut book = book_with_own_ask(5);
+    let rep = execute(&mut book, &own_taker(StpMode::None), limits).unwrap();
+    assert_eq!(rep.trades[0].maker_order_id, 1);
+    assert!(rep.stp_released.is_empty());
+}
+
+#[test]
+fn stp_rejects_fok_that_would_meet_own_maker() {
+    let mut book = MockBook::new();
+    book.push_maker(maker(1, Side::Sell, 100, 2, 9));
+    book.push_maker(maker(2, Side::Sell, 100, 5, 2));
+
+    let limits = EngineLimits {
+        max_trades: 100,
+        max_preview_scans: 1_000,
+        ..Default::default()
+    };
+    let order = IncomingOrder {
+        stp: StpMode::CancelResting,
+        ..taker(10, Side::Buy, OrderKind::FillOrKill, 100, 4, 9, 0)
+    };
+
+    let rep = execute(&mut book, &order, limits).unwrap();
+    assert_eq!(rep.completion, Completion::Rejected);
+    assert!(rep.trades.is_empty());
+    assert_eq!(book.maker_remaining_at_head(Side::Sell, u(100)), Some(u(2)));
+}
+
+#[test]
+fn market_sell_min_quote_rejects_without_mutating_book() {
+    let limits = EngineLimits {
+        max_trades: 100,
+        max_preview_scans: 1_000,
+        ..Default::default()
+    };
+    let thin_bids = || {
+        let mut book = MockBook::new();
+        book.push_maker(maker(
+            1,
+            Side::Buy,
+            2_000_000_000_000_000_000,
+            10_000_000_000_000,
+            1,
+        ));
+        book.push_maker(maker(
+            2,
+            Side::Buy,
+            1_000_000_000_000_000_000,
+            10_000_000_000_000,
+            2,
+        ));
+        book
+    };
+    let sell = |base: u64, min_quote: u64| IncomingOrder {
+        min_quote: u(min_quote),
+        ..taker(10, Side::Sell, OrderKind::Market, 0, base, 9, 0)
+    };
+
+    // 1e13 @ 2e18 -> 20, then 5e12 @ 1e18 -> 5: 25 realized
+    let mut book = thin_bids();
+    let order = sell(15_000_000_000_000, 26);
+    assert_eq!(
+        preview_market_sell_min_quote(&book, &order, limits).unwrap_err(),
+        MatchError::MarketSellMinQuoteNotMet
+    );
+    let err = execute(&mut book, &order, limits).unwrap_err();
+    assert_eq!(err, MatchError::MarketSellMinQuoteNotMet);
+    assert_eq!(
+        book.maker_remaining_at_head(Side::Buy, u(2_000_000_000_000_000_000)),
+        Some(u(10_000_000_000_000))
+    );
+    assert_eq!(
+        book.maker_remaining_at_head(Side::Buy, u(1_000_000_000_000_000_000)),
+        Some(u(10_000_000_000_000))
+    );
+
+    // draining every bid still only yields 30
+    let mut book = thin_bids();
+    let err = execute(&mut book, &sell(40_000_000_000_000, 31), limits).unwrap_err();
+    assert_eq!(err, MatchError::MarketSellMinQuoteNotMet);
+    assert_eq!(
+        book.maker_remaining_at_head(Side::Buy, u(2_000_000_000_000_000_000)),
+        Some(u(10_000_000_000_000))
+    );
+
+    // exactly met: the order executes as usual
+    let mut book = thin_bids();
+    let rep = execute(&mut book, &sell(15_000_000_000_000, 25), limits).unwrap();
+    assert_eq!(rep.completion, Completion::Filled);
+    let realized = rep
+        .trades
+        .iter()
+        .fold(U256::zero(), |acc, tr| acc + tr.amount_quote);
+    assert_eq!(realized, u(25));
+
+    // min_quote belongs to market sells only
+    let order = IncomingOrder {
+        min_quote: u(1),
+        ..taker(11, Side::Sell, OrderKind::Limit, 100, 5, 9, 0)
+    };
+    let err = execute(&mut thin_bids(), &order, limits).unwrap_err();
+    assert_eq!(
+        err,
+        MatchError::InvalidOrder(InvalidOrderReason::MinQuoteOnlyForMarketSell)
+    );
+}
+
+#[test]
+fn expired_makers_are_dropped_instead_of_filled() {
+    let limits = EngineLimits {
+        max_trades: 100,
+        max_preview_scans: 1_000,
+        now: 50,
+        ..Default::default()
+    };
+    // ask 1 lapsed at 40; ask 3 runs out at 50, which is still live; ask 2 never expires
+    let book_with_stale_ask = || {
+        let mut book = MockBook::new();
+        book.push_maker(MakerView {
+            expires_at: 40,
+            ..maker(1, Side::Sell, 100, 5, 1)
+        });
+        book.push_maker(MakerView {
+            expires_at: 50,
+            ..maker(3, Side::Sell, 100, 5, 3)
+        });
+        book.push_maker(maker(2, Side::Sell, 101, 5, 2));
+        book
+    };
+
+    let mut book = book_with_stale_ask();
+    let order = taker(10, Side::Buy, OrderKind::Limit, 101, 8, 9, 0);
+    let rep = execute(&mut book, &order, limits).unwrap();
+    assert_eq!(rep.completion, Completion::Filled);
+    let fills: Vec<_> = rep
+        .trades
+        .iter()
+        .map(|tr| (tr.maker_order_id, tr.amount_base))
+        .collect();
+    assert_eq!(fills, vec![(3, u(5)), (2, u(3))]);
+    assert_eq!(
+        rep.expired,
+        vec![ExpiredMaker {
+            maker_order_id: 1,
+            owner: 1u64.into(),
+            released_base: u(5),
+            released_quote: U256::zero(),
+        }]
+    );
+    assert!(book.peek_level(Side::Sell, u(100)).is_none());
+
+    // a FOK only counts live liquidity and is rejected without touching the stale ask
+    let mut book = book_with_stale_ask();
+    let fok = taker(11, Side::Buy, OrderKind::FillOrKill, 101, 12, 9, 0);
+    assert!(preview_fillable(&book, &fok, 100, 40).unwrap());
+    assert!(!preview_fillable(&book, &fok, 100, limits.now).unwrap());
+    let rep = execute(&mut book, &fok, limits).unwrap();
+    assert_eq!(rep.completion, Completion::Rejected);
+    assert_eq!(book.maker_remaining_at_head(Side::Sell, u(100)), Some(u(5)));
+
+    // the resting remainder of a limit keeps its expiry
+    let mut book = MockBook::new();
+    let order = IncomingOrder {
+        expires_at: 60,
+        ..taker(12, Side::Buy, OrderKind::Limit, 90, 4, 9, 0)
+    };
+    execute(&mut book, &order, limits).unwrap();
+    let rested = book.peek_level(Side::Buy, u(90)).unwrap().front().unwrap();
+    assert_eq!(rested.expires_at, 60);
+
+    // any other kind has nothing to rest, so an expiry is refused
+    let order = IncomingOrder {
+        expires_at: 60,
+        ..taker(13, Side::Buy, OrderKind::ImmediateOrCancel, 101, 1, 9, 0)
+    };
+    let err = execute(&mut book_with_stale_ask(), &order, limits).unwrap_err();
+    assert_eq!(
+        err,
+        MatchError::InvalidOrder(InvalidOrderReason::ExpiryOnlyForLimit)
+    );
+}
+
+#[test]
+fn reduce_only_limit_cancels_what_a_normal_limit_would_rest() {
+    let limits = EngineLimits {
+        max_trades: 100,
+        max_preview_scans: 1_000,
+        ..Default::default()
+    };
+    let asks = || {
+        let mut book = MockBook::new();
+        book.push_maker(maker(1, Side::Sell, 100, 3, 1));
+        book.push_maker(maker(2, Side::Sell, 101, 2, 2));
+        book.push_maker(maker(3, Side::Sell, 105, 4, 3));
+        book
+    };
+    let normal = taker(10, Side::Buy, OrderKind::Limit, 101, 8, 9, 0);
+    let reduce_only = IncomingOrder {
+        reduce_only: true,
+        ..normal.clone()
+    };
+
+    let mut normal_book = asks();
+    let normal_rep = execute(&mut normal_book, &normal, limits).unwrap();
+    let mut reduce_book = asks();
+    let reduce_rep = execute(&mut reduce_book, &reduce_only, limits).unwrap();
+
+    // the crossed part executes identically
+    let fills = |rep: &ExecutionReport| -> Vec<_> {
+        rep.trades
+            .iter()
+            .map(|tr| (tr.maker_order_id, tr.price, tr.amount_base))
+            .collect()
+    };
+    assert_eq!(fills(&normal_rep), fills(&reduce_rep));
+    assert_eq!(
+        fills(&reduce_rep),
+        vec![(1, u(100), u(3)), (2, u(101), u(2))]
+    );
+    assert!(matches!(
+        normal_rep.completion,
+        Completion::Placed { remaining_base, filled_base, .. }
+            if remaining_base == u(3) && filled_base == u(5)
+    ));
+    assert_eq!(
+        reduce_rep.completion,
+        Completion::Cancelled {
+            remaining_base: u(3)
+        }
+    );
+
+    // only the normal limit leaves a bid behind
+    assert_eq!(normal_book.best_price(Side::Buy), Some(u(101)));
+    assert_eq!(reduce_book.best_price(Side::Buy), None);
+    assert_eq!(
+        preview_limit_rest(&asks(), &reduce_only, limits).unwrap(),
+        (u(5), U256::zero())
+    );
+
+    // with nothing to cross it is a no-op rather than a new bid
+    let mut book = MockBook::new();
+    let rep = execute(&mut book, &reduce_only, limits).unwrap();
+    assert!(rep.trades.is_empty());
+    assert_eq!(
+        rep.completion,
+        Completion::Cancelled {
+            remaining_base: u(8)
+        }
+    );
+    assert_eq!(book.best_price(Side::Buy), None);
+}
+
+#[test]
+fn spent_totals_match_the_trades_across_levels() {
+    let limits = EngineLimits {
+        max_trades: 100,
+        max_preview_scans: 1_000,
+        ..Default::default()
+    };
+    let sums = |rep: &ExecutionReport| {
+        rep.trades
+            .iter()
+            .fold((U256::zero(), U256::zero()), |acc, tr| {
+                (acc.0 + tr.amount_quote, acc.1 + tr.amount_base)
+            })
+    };
+
+    // limit buy walks three ask levels and rests the rest
+    let mut book = MockBook::new();
+    book.push_maker(maker(1, Side::Sell, 100, 3, 1));
+    book.push_maker(maker(2, Side::Sell, 101, 2, 2));
+    book.push_maker(maker(3, Side::Sell, 105, 4, 3));
+    let order = taker(10, Side::Buy, OrderKind::Limit, 105, 12, 9, 0);
+    let rep = execute(&mut book, &order, limits).unwrap();
+    assert_eq!(rep.trades.len(), 3);
+    assert_eq!((rep.spent_quote, rep.spent_base), sums(&rep));
+    assert!(matches!(
+        rep.completion,
+        Completion::Placed { filled_base, filled_quote, .. }
+            if filled_base == rep.spent_base && filled_quote == rep.spent_quote
+    ));
+
+    // market sell walks two bid levels
+    let mut book = MockBook::new();
+    book.push_maker(maker(1, Side::Buy, 100, 2, 1));
+    book.push_maker(maker(2, Side::Buy, 99, 2, 2));
+    let order = taker(11, Side::Sell, OrderKind::Market, 0, 3, 9, 0);
+    let rep = execute(&mut book, &order, limits).unwrap();
+    assert_eq!(rep.trades.len(), 2);
+    assert_eq!((rep.spent_quote, rep.spent_base), sums(&rep));
+    assert_eq!(rep.spent_base, u(3));
+
+    // a rejected FOK reports nothing spent
+    let order = taker(12, Side::Buy, OrderKind::FillOrKill, 105, 50, 9, 0);
+    let rep = execute(&mut book, &order, limits).unwrap();
+    assert_eq!(rep.completion, Completion::Rejected);
+    assert_eq!(
+        (rep.spent_quote, rep.spent_base),
+        (U256::zero(), U256::zero())
+    );
+}
@@ -29,6 +29,22 @@ pub enum OrderKind {
     ImmediateOrCancel,
 }
 
+/// Self-trade prevention: what `execute` does when the taker meets a maker with the same owner.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub enum StpMode {
+    /// Self-match like any other maker.
+    #[default]
+    None,
+    /// Remove the own maker and keep matching.
+    CancelResting,
+    /// Stop matching and cancel the taker remainder.
+    CancelTaker,
+    /// Shrink both by the smaller size without trading.
+    DecrementBoth,
+}
+
 /// Incoming (taker) order.
 /// For Market orders, `limit_price` is ignored.
 #[derive(Debug, Clone, Encode, Decode, TypeInfo)]
@@ -41,8 +57,17 @@ pub struct IncomingOrder {
     pub limit_price: U256,
     pub amount_base: U256,
     pub owner: ActorId,
-    // budget for Market BUY (else 0)
+    // budget for Market BUY (else 0); with amount_base == 0 the whole budget is spent
     pub max_quote: U256,
+    /// Market/IOC only: stop before any maker priced worse than this and cancel the rest.
+    pub worst_price: Option<U256>,
+    pub stp: StpMode,
+    /// Market SELL only: least quote the whole order must realize, else it is rejected (0 = off).
+    pub min_quote: U256,
+    /// Limit only: time after which the resting remainder stops matching (0 = good till cancelled).
+    pub expires_at: u64,
+    /// Limit only: cancel whatever is left after crossing instead of resting it, like IOC.
+    pub reduce_only: bool,
 }
 
 /// Minimal view of a resting (maker) order stored in the book.
@@ -56,6 +81,14 @@ pub struct MakerView {
     /// For maker BUY orders: remaining reserved quote in escrow (to refund on cancel).
     /// For maker SELL orders: must be 0.
     pub reserved_quote: U256,
+    /// Time after which the order no longer matches (0 = good till cancelled).
+    pub expires_at: u64,
+}
+
+impl MakerView {
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at != 0 && self.expires_at < now
+    }
 }
 
 /// Remainder that should be inserted as a resting order (Limit only).
@@ -67,6 +100,51 @@ pub struct RestingOrder {
     pub price: U256,
     pub remaining_base: U256,
     pub remaining_quote: U256,
+    pub expires_at: u64,
+}
+
+/// Market-if-touched order waiting for its trigger price.
+///
+/// A buy fires once the last trade price rises to `trigger_price` or above,
+/// a sell once it falls to `trigger_price` or below.
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct TriggerOrder {
+    pub id: OrderId,
+    pub owner: ActorId,
+    pub side: Side,
+    pub trigger_price: U256,
+    pub amount_base: U256,
+    // budget for BUY (else 0)
+    pub max_quote: U256,
+}
+
+impl TriggerOrder {
+    pub fn is_touched(&self, last_price: U256) -> bool {
+        match self.side {
+            Side::Buy => last_price >= self.trigger_price,
+            Side::Sell => last_price <= self.trigger_price,
+        }
+    }
+
+    /// Market order the trigger converts into, keeping the trigger's id.
+    pub fn to_market(&self) -> IncomingOrder {
+        IncomingOrder {
+            id: self.id,
+            side: self.side,
+            kind: OrderKind::Market,
+            limit_price: U256::zero(),
+            amount_base: self.amount_base,
+            owner: self.owner,
+            max_quote: self.max_quote,
+            worst_price: None,
+            stp: StpMode::None,
+            min_quote: U256::zero(),
+            expires_at: 0,
+            reduce_only: false,
+        }
+    }
 }
 
 /// Trade (fill) produced by matching.
@@ -89,6 +167,13 @@ pub struct Trade {
 pub struct EngineLimits {
     pub max_trades: u32,
     pub max_preview_scans: u32,
+    /// Fills after which a taker stops matching and the remainder is cancelled (0 = off).
+    pub sweep_max_fills: u32,
+    /// Base a taker may trade before the remainder is cancelled (0 = off).
+    pub sweep_max_base: U256,
+    /// Current time, checked against each maker's `expires_at`; expired makers are dropped
+    /// instead of filled.
+    pub now: u64,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
@@ -102,10 +187,12 @@ pub enum Completion {
     Cancelled {
         remaining_base: U256,
     },
-    /// Limit remainder inserted as resting
+    /// Limit remainder inserted as resting; `filled_*` is what traded before it rested
     Placed {
         remaining_base: U256,
         remaining_quote: U256,
+        filled_base: U256,
+        filled_quote: U256,
     },
 }
 
@@ -114,7 +201,54 @@ pub enum Completion {
 #[scale_info(crate = sails_rs::scale_info)]
 pub struct ExecutionReport {
     pub trades: Vec<Trade>,
+    /// Sum of `amount_quote` over `trades`.
+    pub spent_quote: U256,
+    /// Sum of `amount_base` over `trades`.
+    pub spent_base: U256,
     pub completion: Completion,
+    /// Own makers cancelled or shrunk by self-trade prevention.
+    pub stp_released: Vec<StpRelease>,
+    /// Makers dropped because their `expires_at` had passed.
+    pub expired: Vec<ExpiredMaker>,
+}
+
+/// Funds freed from one of the taker's own makers by self-trade prevention.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct StpRelease {
+    pub maker_order_id: OrderId,
+    /// Whether the maker left the book.
+    pub removed: bool,
+    pub released_base: U256,
+    pub released_quote: U256,
+}
+
+/// Resting order removed unfilled during matching because it had expired.
+/// Its escrow goes back to `owner`.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct ExpiredMaker {
+    pub maker_order_id: OrderId,
+    pub owner: ActorId,
+    pub released_base: U256,
+    pub released_quote: U256,
+}
+
+impl From<MakerView> for ExpiredMaker {
+    fn from(m: MakerView) -> Self {
+        Self {
+            maker_order_id: m.id,
+            owner: m.owner,
+            released_base: if m.side == Side::Sell {
+                m.remaining_base
+            } else {
+                U256::zero()
+            },
+            released_quote: m.reserved_quote,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -127,6 +261,8 @@ pub enum MatchError {
     MarketBuyBudgetCheckInconsistent,
     MarketBuyLiquidityCheckInconsistent,
     MarketBuyMaxQuoteExceeded,
+    MarketSellMinQuoteNotMet,
+    MarketSellMinQuoteCheckInconsistent,
 
     BrokenBook(BookInvariant),
 
@@ -157,4 +293,8 @@ pub enum InvalidOrderReason {
     MaxQuoteOnlyForMarketBuy,
     PreviewOnlyForMarketBuyBudget,
     MarketBuyMaxQuoteExceeded,
+    WorstPriceOnlyForMarketOrIoc,
+    MinQuoteOnlyForMarketSell,
+    PreviewOnlyForMarketSellMinQuote,
+    ExpiryOnlyForLimit,
 }
@@ -1,15 +1,128 @@
 #![no_std]
-use clob_common::TokenId;
+use clob_common::{actor_to_eth, parse_version, DepositError, Price, Quantity, TokenId, Version};
 #[cfg(feature = "debug")]
 use clob_common::{eth_to_actor, SHOWCASE_PREFUNDED_ETH_ADDRESSES};
-use matching_engine::{Book, IncomingOrder, MatchError, OrderId, OrderKind, Side};
-use sails_rs::{cell::RefCell, gstd::msg, prelude::*};
+use matching_engine::{
+    Book, ExecutionReport, IncomingOrder, MakerView, MatchError, OrderId, OrderKind, Side, StpMode,
+    TriggerOrder, MAX_PRICE_EXPONENT,
+};
+use sails_rs::{cell::RefCell, gstd::exec, gstd::msg, prelude::*};
 
-use crate::state::{kind_from_io, side_from_io, Asset, OrderKindIO, SideIO};
+use crate::state::{quote_bounds, Asset, OrderCreated, OrderType, OrderView};
 use vault_client::vault::io as vault_io;
 mod orderbook;
 mod state;
 
+// --- Events ---
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode, TypeInfo)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub enum RejectReason {
+    /// A fill-or-kill order could not be filled in full, so nothing traded.
+    FillOrKillUnfillable,
+}
+
+/// Every variant carries `seq`, a program-wide counter that grows by one per event,
+/// so consumers can detect gaps.
+#[sails_rs::event]
+#[derive(Clone, Debug, PartialEq, Encode, TypeInfo)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub enum Events {
+    /// Quote a fully filled buy locked but did not spend, returned to the taker's balance.
+    BudgetRefunded {
+        seq: u64,
+        user: [u8; 20],
+        token: TokenId,
+        amount: u128,
+    },
+    /// An order completed without trading and left nothing on the book.
+    OrderRejected {
+        seq: u64,
+        reason: RejectReason,
+        side: Side,
+        requested_base: u128,
+    },
+    /// Best bid price or the quantity resting at it changed; zeros when no bids remain.
+    BestBidChanged {
+        seq: u64,
+        price: u128,
+        quantity: u128,
+    },
+    /// Best ask price or the quantity resting at it changed; zeros when no asks remain.
+    BestAskChanged {
+        seq: u64,
+        price: u128,
+        quantity: u128,
+    },
+    /// Free balance written off by `register_emergency_exit` while the market was paused,
+    /// to be settled off-chain; nothing was sent to the vault.
+    EmergencyExit {
+        seq: u64,
+        user: [u8; 20],
+        token: TokenId,
+        amount: u128,
+    },
+    /// A resting order was pulled by `cancel_all`, `cancel_orders`, `cancel_at_price` or a
+    /// cancelling `pause`, or dropped as expired by `purge_expired`; its reservation went back
+    /// to the owner.
+    OrderCanceled {
+        seq: u64,
+        order_id: OrderId,
+        side: Side,
+        price: u128,
+        remaining_base: u128,
+    },
+    /// A resting order's price or quantity was amended by its owner.
+    OrderModified {
+        seq: u64,
+        order_id: OrderId,
+        old_price: u128,
+        new_price: u128,
+        old_quantity: u128,
+        new_quantity: u128,
+    },
+    /// Accrued protocol fees were moved to the admin's quote balance.
+    FeesClaimed {
+        seq: u64,
+        token: TokenId,
+        amount: u128,
+    },
+    /// The pending admin accepted and replaced `previous_admin`.
+    AdminChanged {
+        seq: u64,
+        previous_admin: [u8; 20],
+        new_admin: [u8; 20],
+    },
+    /// The admin zeroed `user`'s free `token` balance with `admin_zero_balance` while the
+    /// market was paused; `amount` is what was written off.
+    BalanceZeroed {
+        seq: u64,
+        user: [u8; 20],
+        token: TokenId,
+        amount: u128,
+    },
+}
+
+const VERSION: Version = parse_version(
+    env!("CARGO_PKG_VERSION_MAJOR"),
+    env!("CARGO_PKG_VERSION_MINOR"),
+    env!("CARGO_PKG_VERSION_PATCH"),
+);
+
+const MAX_AUDIT_PAGE: u32 = 100;
+/// Most orders `open_orders_of` returns; anything past it is left out.
+const MAX_OPEN_ORDERS: usize = 100;
+/// Most price levels `depth` returns per side.
+const MAX_DEPTH_LEVELS: u32 = 100;
+/// Most resting orders one `purge_expired` call looks at.
+const MAX_PURGE_SCAN: u32 = 100;
+/// Most resting orders one `pause(true)` call cancels when `cancel_on_pause` is set.
+const MAX_PAUSE_CANCELS: u32 = 100;
+// Bounds the trigger cascade a single order can set off.
+const MAX_TRIGGER_ROUNDS: usize = 4;
+const MAX_TRIGGERS_PER_ROUND: usize = 8;
 #[cfg(feature = "debug")]
 const DEMO_MAX_TOTAL_ORDERS: u32 = 2_000;
 #[cfg(feature = "debug")]
@@ -57,7 +170,15 @@ pub struct Orderbook<'a> {
     state: &'a RefCell<state::State>,
 }
 
-type TradeHistoryEntry = (u64, u64, u64, ActorId, ActorId, u128, u128, u128);
+type TradeHistoryEntry = (u64, OrderId, OrderId, ActorId, ActorId, u128, u128, u128);
+/// `(seq, maker_order_id, taker_order_id, price, amount_base, amount_quote)` of one fill.
+type RecentTrade = (u64, OrderId, OrderId, Price, Quantity, Quantity);
+/// `(min, max)` resting price of one side, `None` when the side is empty.
+type PriceRange = Option<(Price, Price)>;
+/// `(price, total remaining_base)` of each level on one side, best first.
+type DepthSide = Vec<(Price, Quantity)>;
+/// `(side, kind, limit_price, amount_base, max_quote)`, as taken by `submit_order`.
+type OrderInputIO = (Side, OrderType, u128, u128, u128);
 
 impl<'a> Orderbook<'a> {
     pub fn new(state: &'a RefCell<state::State>) -> Self {
@@ -74,6 +195,88 @@ impl<'a> Orderbook<'a> {
         self.state.borrow()
     }
 
+    /// Moves `amount` of `asset` from the book balance back to the owner's vault balance.
+    /// The book balance is restored if the vault rejects the deposit.
+    async fn withdraw_to_vault(&mut self, owner: ActorId, asset: Asset, amount: u128) {
+        let vault_id = {
+            let mut st = self.get_mut();
+            st.withdraw(owner, asset, U256::from(amount));
+            match asset {
+                Asset::Base => st.base_vault_id,
+                Asset::Quote => st.quote_vault_id,
+            }
+        };
+        let payload = vault_io::VaultDeposit::encode_params_with_prefix("Vault", owner, amount);
+        let result = msg::send_bytes_for_reply(vault_id, payload, 0)
+            .expect("SendFailed")
+            .await;
+
+        if result.is_err() {
+            let mut st = self.get_mut();
+            st.deposit(owner, asset, U256::from(amount));
+        }
+    }
+
+    /// Cancels a resting order or pending trigger owned by `caller`.
+    /// Returns the asset and amount unlocked back to the caller.
+    fn cancel_for_owner(
+        st: &mut state::State,
+        caller: ActorId,
+        order_id: OrderId,
+    ) -> (Asset, U256) {
+        st.throttle_cancel(caller, exec::block_height());
+        Orderbook::cancel_owned(st, caller, order_id)
+    }
+
+    /// Same as `cancel_for_owner` without the cancel cooldown, for callers that throttle once
+    /// per batch.
+    fn cancel_owned(st: &mut state::State, caller: ActorId, order_id: OrderId) -> (Asset, U256) {
+        if let Some(trigger) = st.triggers.get(&order_id) {
+            if trigger.owner != caller {
+                panic!("Not order owner");
+            }
+            let trigger = st.take_trigger(order_id).expect("Order not found");
+            return state::State::trigger_funds(&trigger);
+        }
+
+        let Some(view) = st.book.peek_order(order_id) else {
+            panic!("Order not found");
+        };
+        if view.owner != caller {
+            panic!("Not order owner");
+        }
+        st.ensure_order_aged(caller, order_id, exec::block_height());
+
+        let maker = st.book.cancel(view.id).expect("Order not found");
+        st.order_created.remove(&order_id);
+
+        // Unlock remaining locked funds back to caller.
+        let freed = match maker.side {
+            Side::Sell => (Asset::Base, maker.remaining_base),
+            Side::Buy => (Asset::Quote, maker.reserved_quote),
+        };
+        st.unlock(caller, freed.0, freed.1);
+        freed
+    }
+
+    /// Pulls `targets` off the book and unlocks each reservation back to its owner.
+    /// Returns the makers actually removed.
+    fn cancel_makers(st: &mut state::State, targets: Vec<MakerView>) -> Vec<MakerView> {
+        let mut cancelled = Vec::with_capacity(targets.len());
+        for target in targets {
+            let Some(maker) = st.book.cancel(target.id) else {
+                continue;
+            };
+            st.order_created.remove(&maker.id);
+            match maker.side {
+                Side::Sell => st.unlock(maker.owner, Asset::Base, maker.remaining_base),
+                Side::Buy => st.unlock(maker.owner, Asset::Quote, maker.reserved_quote),
+            }
+            cancelled.push(maker);
+        }
+        cancelled
+    }
+
     fn submit_order_for_owner(
         st: &mut state::State,
         owner: ActorId,
@@ -83,7 +286,9 @@ impl<'a> Orderbook<'a> {
         amount_base: u128,
         max_quote: u128,
     ) -> Result<OrderId, MatchError> {
+        st.ensure_order_size(U256::from(amount_base));
         let order_id = st.alloc_order_id();
+        let (max_quote, min_quote) = quote_bounds(side, kind, max_quote);
         let incoming = IncomingOrder {
             id: order_id,
             owner,
@@ -91,17 +296,67 @@ impl<'a> Orderbook<'a> {
             kind,
             limit_price: U256::from(limit_price),
             amount_base: U256::from(amount_base),
-            max_quote: U256::from(max_quote),
+            max_quote,
+            worst_price: None,
+            stp: st.stp_mode(owner),
+            min_quote,
+            expires_at: 0,
+            reduce_only: false,
         };
 
-        let (locked_base, locked_quote) = st.lock_taker_funds(&incoming);
-        let limits = st.limits;
-        let report = matching_engine::execute(&mut st.book, &incoming, limits)?;
-        st.settle_execution(&incoming, &report, locked_base, locked_quote);
-        st.append_executed_trades(&report.trades);
+        Orderbook::execute_for_owner(st, &incoming)?;
+        st.ensure_resting_capacity(&incoming);
         Ok(order_id)
     }
 
+    fn execute_for_owner(
+        st: &mut state::State,
+        incoming: &IncomingOrder,
+    ) -> Result<(), MatchError> {
+        st.ensure_not_paused();
+        let (locked_base, locked_quote) = st.lock_taker_funds(incoming);
+        let limits = st.engine_limits(exec::block_timestamp());
+        let report = matching_engine::execute(&mut st.book, incoming, limits)?;
+        st.settle_execution(incoming, &report, locked_base, locked_quote);
+        let now = OrderCreated {
+            block: exec::block_height(),
+            timestamp: exec::block_timestamp(),
+        };
+        st.record_resting(incoming, &report, now);
+        st.append_executed_trades(&report.trades);
+        Ok(())
+    }
+
+    /// Converts touched market-if-touched orders into market orders.
+    /// A buy whose budget or liquidity no longer suffices stays parked.
+    fn fire_triggers(st: &mut state::State) -> Result<(), MatchError> {
+        for _ in 0..MAX_TRIGGER_ROUNDS {
+            let touched = st.touched_triggers(MAX_TRIGGERS_PER_ROUND);
+            if touched.is_empty() {
+                return Ok(());
+            }
+            for id in touched {
+                let Some(trigger) = st.take_trigger(id) else {
+                    continue;
+                };
+                let incoming = trigger.to_market();
+                if incoming.side == Side::Buy
+                    && matching_engine::preview_market_buy_budget_strict(
+                        &st.book,
+                        &incoming,
+                        st.engine_limits(exec::block_timestamp()),
+                    )
+                    .is_err()
+                {
+                    st.place_trigger(trigger);
+                    continue;
+                }
+                Orderbook::execute_for_owner(st, &incoming)?;
+            }
+        }
+        Ok(())
+    }
+
     fn trade_to_io(trade: &state::ExecutedTrade) -> TradeHistoryEntry {
         (
             trade.seq,
@@ -280,90 +535,356 @@ impl<'a> Orderbook<'a> {
     }
 }
 
-#[sails_rs::service]
+#[sails_rs::service(events = Events)]
 impl<'a> Orderbook<'a> {
-    #[export]
-    pub fn deposit(&mut self, account: ActorId, token: TokenId, amount: u128) -> bool {
+    /// Next value of the program-wide event sequence.
+    fn alloc_event_seq(&self) -> u64 {
         let mut st = self.get_mut();
-        let caller = sails_rs::gstd::msg::source();
-        if token == st.base_token_id {
-            if caller != st.base_vault_id {
-                panic!("Not allowed to deposit")
+        let seq = st.next_event_seq;
+        st.next_event_seq = seq.saturating_add(1);
+        seq
+    }
+
+    /// Emits `event` both as an eth event and as a plain sails event.
+    fn publish(&self, event: Events) {
+        self.emit_eth_event(event.clone()).expect("EmitEventFailed");
+        let mut emitter = self.emitter();
+        emitter.emit_event(event).expect("EmitEventFailed");
+    }
+
+    /// Emits `BestBidChanged`/`BestAskChanged` for sides whose top moved since the last call.
+    fn emit_top_of_book_changes(&self) {
+        let (bid, ask) = {
+            let mut st = self.get_mut();
+            let zero = (U256::zero(), U256::zero());
+            let bid = st.book.top_of_book(Side::Buy).unwrap_or(zero);
+            let ask = st.book.top_of_book(Side::Sell).unwrap_or(zero);
+            let bid = (bid != st.published_best_bid).then_some(bid);
+            let ask = (ask != st.published_best_ask).then_some(ask);
+            if let Some(bid) = bid {
+                st.published_best_bid = bid;
             }
-            st.deposit(account, Asset::Base, U256::from(amount));
-        } else if token == st.quote_token_id {
-            if caller != st.quote_vault_id {
-                panic!("Not allowed to deposit")
+            if let Some(ask) = ask {
+                st.published_best_ask = ask;
             }
-            st.deposit(account, Asset::Quote, U256::from(amount));
-        } else {
-            panic!("Invalid token");
+            (bid, ask)
+        };
+
+        if let Some((price, quantity)) = bid {
+            let seq = self.alloc_event_seq();
+            self.publish(Events::BestBidChanged {
+                seq,
+                price: price.low_u128(),
+                quantity: quantity.low_u128(),
+            });
+        }
+        if let Some((price, quantity)) = ask {
+            let seq = self.alloc_event_seq();
+            self.publish(Events::BestAskChanged {
+                seq,
+                price: price.low_u128(),
+                quantity: quantity.low_u128(),
+            });
         }
-        true
     }
 
-    #[export]
-    pub async fn withdraw_base(&mut self, amount: u128) {
-        let caller = msg::source();
-        let base_vault_id = {
+    fn emit_budget_refunds(&self) {
+        let (token, refunds) = {
             let mut st = self.get_mut();
-            st.withdraw(caller, Asset::Base, U256::from(amount));
-            st.base_vault_id
+            (st.quote_token_id, core::mem::take(&mut st.budget_refunds))
         };
-        let payload = vault_io::VaultDeposit::encode_params_with_prefix("Vault", caller, amount);
-        let result = msg::send_bytes_for_reply(base_vault_id, payload, 0)
-            .expect("SendFailed")
-            .await;
+        for (user, amount) in refunds {
+            let seq = self.alloc_event_seq();
+            self.publish(Events::BudgetRefunded {
+                seq,
+                user: actor_to_eth(user),
+                token,
+                amount: amount.low_u128(),
+            });
+        }
+    }
 
-        if result.is_err() {
-            let mut st = self.get_mut();
-            st.deposit(caller, Asset::Base, U256::from(amount));
+    fn emit_cancellations(&self, cancelled: &[MakerView]) {
+        for maker in cancelled {
+            let seq = self.alloc_event_seq();
+            self.publish(Events::OrderCanceled {
+                seq,
+                order_id: maker.id,
+                side: maker.side,
+                price: maker.price.low_u128(),
+                remaining_base: maker.remaining_base.low_u128(),
+            });
         }
     }
 
-    #[export]
-    pub async fn withdraw_quote(&mut self, amount: u128) {
-        let caller = msg::source();
-        let quote_vault_id = {
+    fn emit_rejections(&self) {
+        let rejected = core::mem::take(&mut self.get_mut().rejected_orders);
+        for (side, requested_base) in rejected {
+            let seq = self.alloc_event_seq();
+            self.publish(Events::OrderRejected {
+                seq,
+                reason: RejectReason::FillOrKillUnfillable,
+                side,
+                requested_base: requested_base.low_u128(),
+            });
+        }
+    }
+
+    /// Credits `amount` of `token` to `account`; only that token's vault may call it.
+    fn credit_from_vault(
+        &self,
+        account: ActorId,
+        token: TokenId,
+        amount: u128,
+    ) -> Result<(), DepositError> {
+        let mut st = self.get_mut();
+        let asset = st.vault_asset(sails_rs::gstd::msg::source(), token)?;
+        st.deposit(account, asset, U256::from(amount));
+        Ok(())
+    }
+
+    /// Places an order for `owner`, fires any triggers it touches and emits the resulting events.
+    fn place_order(
+        &self,
+        owner: ActorId,
+        side: Side,
+        kind: OrderType,
+        limit_price: u128,
+        amount_base: u128,
+        max_quote: u128,
+    ) -> Result<OrderId, MatchError> {
+        let order_id = {
             let mut st = self.get_mut();
-            st.withdraw(caller, Asset::Quote, U256::from(amount));
-            st.quote_vault_id
+            let (kind, limit_price) = match kind.engine_kind() {
+                Some(kind) => (kind, limit_price),
+                None => (OrderKind::Limit, st.peg_price(side).low_u128()),
+            };
+            let order_id = Orderbook::submit_order_for_owner(
+                &mut st,
+                owner,
+                side,
+                kind,
+                limit_price,
+                amount_base,
+                max_quote,
+            )?;
+            Orderbook::fire_triggers(&mut st)?;
+            order_id
         };
-        let payload = vault_io::VaultDeposit::encode_params_with_prefix("Vault", caller, amount);
-        let result = msg::send_bytes_for_reply(quote_vault_id, payload, 0)
-            .expect("SendFailed")
-            .await;
+        self.emit_budget_refunds();
+        self.emit_rejections();
+        self.emit_top_of_book_changes();
+        Ok(order_id)
+    }
 
-        if result.is_err() {
+    /// Places a limit order for `owner` with the given expiry and reduce-only flag, and emits
+    /// the resulting events.
+    fn place_limit(
+        &self,
+        owner: ActorId,
+        side: Side,
+        limit_price: u128,
+        amount_base: u128,
+        expires_at: u64,
+        reduce_only: bool,
+    ) -> Result<OrderId, MatchError> {
+        let order_id = {
+            let mut st = self.get_mut();
+            st.ensure_order_size(U256::from(amount_base));
+            let order_id = st.alloc_order_id();
+            let incoming = IncomingOrder {
+                id: order_id,
+                owner,
+                side,
+                kind: OrderKind::Limit,
+                limit_price: U256::from(limit_price),
+                amount_base: U256::from(amount_base),
+                max_quote: U256::zero(),
+                worst_price: None,
+                stp: st.stp_mode(owner),
+                min_quote: U256::zero(),
+                expires_at,
+                reduce_only,
+            };
+            Orderbook::execute_for_owner(&mut st, &incoming)?;
+            st.ensure_resting_capacity(&incoming);
+            Orderbook::fire_triggers(&mut st)?;
+            order_id
+        };
+        self.emit_budget_refunds();
+        self.emit_rejections();
+        self.emit_top_of_book_changes();
+        Ok(order_id)
+    }
+
+    /// Vault-only: credits `amount` of `token` to `account`. Any other caller gets
+    /// `DepositError::UnauthorizedCaller` and nothing is credited.
+    #[export]
+    pub fn deposit(
+        &mut self,
+        account: ActorId,
+        token: TokenId,
+        amount: u128,
+    ) -> Result<bool, DepositError> {
+        self.credit_from_vault(account, token, amount)?;
+        Ok(true)
+    }
+
+    /// Vault-only: credits `amount` of `token` to `account` and places an order for it in
+    /// the same message. If the order is invalid or unaffordable, the deposit is undone too.
+    #[allow(clippy::too_many_arguments)]
+    #[export(unwrap_result)]
+    pub fn deposit_and_submit(
+        &mut self,
+        account: ActorId,
+        token: TokenId,
+        amount: u128,
+        side: Side,
+        kind: OrderType,
+        limit_price: u128,
+        amount_base: u128,
+        max_quote: u128,
+    ) -> Result<OrderId, MatchError> {
+        self.credit_from_vault(account, token, amount)
+            .unwrap_or_else(|err| panic!("{err:?}"));
+        self.place_order(account, side, kind, limit_price, amount_base, max_quote)
+    }
+
+    /// While the market is paused, removes `amount` of the caller's free `token` balance
+    /// without calling the vault and emits `EmergencyExit`. `token` must be the market's base
+    /// or quote token.
+    #[export]
+    pub fn register_emergency_exit(&mut self, token: TokenId, amount: u128) {
+        let caller = msg::source();
+        {
             let mut st = self.get_mut();
-            st.deposit(caller, Asset::Quote, U256::from(amount));
+            if !st.paused {
+                panic!("NotPaused");
+            }
+            let asset = st.token_asset(token);
+            let balance = st.balances.get(&caller).cloned().unwrap_or_default();
+            let free = match asset {
+                Asset::Base => balance.base,
+                Asset::Quote => balance.quote,
+            };
+            if U256::from(amount) > free {
+                panic!("InsufficientBalance");
+            }
+            st.withdraw(caller, asset, U256::from(amount));
         }
+
+        let seq = self.alloc_event_seq();
+        self.publish(Events::EmergencyExit {
+            seq,
+            user: actor_to_eth(caller),
+            token,
+            amount,
+        });
+    }
+
+    #[export]
+    pub async fn withdraw_base(&mut self, amount: u128) {
+        self.withdraw_to_vault(msg::source(), Asset::Base, amount)
+            .await;
+    }
+
+    #[export]
+    pub async fn withdraw_quote(&mut self, amount: u128) {
+        self.withdraw_to_vault(msg::source(), Asset::Quote, amount)
+            .await;
     }
 
     /// Submits an order and immediately matches against the book.
     /// Limit remainder is placed as resting order inside the book.
+    /// `PegToBest` ignores `limit_price` and posts one tick inside the best on its side.
+    /// A market buy with `amount_base` 0 spends `max_quote` instead; unspendable dust is refunded.
+    /// For a market sell `max_quote` is the least quote it must realize, else it is rejected.
     #[export(unwrap_result)]
     pub fn submit_order(
         &mut self,
-        side: SideIO,
-        kind: OrderKindIO,
+        side: Side,
+        kind: OrderType,
         limit_price: u128,
         amount_base: u128,
         max_quote: u128,
     ) -> Result<OrderId, MatchError> {
         let caller = sails_rs::gstd::msg::source();
-        let mut st = self.get_mut();
-        Orderbook::submit_order_for_owner(
-            &mut st,
-            caller,
-            side_from_io(side),
-            kind_from_io(kind),
+        self.place_order(caller, side, kind, limit_price, amount_base, max_quote)
+    }
+
+    /// Places a good-till-time limit order. Once the block timestamp passes `expires_at` the
+    /// order stops matching; it is dropped when a taker reaches it or `purge_expired` runs,
+    /// and its reservation goes back to the owner.
+    #[export(unwrap_result)]
+    pub fn submit_limit_until(
+        &mut self,
+        side: Side,
+        limit_price: u128,
+        amount_base: u128,
+        expires_at: u64,
+    ) -> Result<OrderId, MatchError> {
+        if expires_at <= exec::block_timestamp() {
+            panic!("InvalidExpiry");
+        }
+        self.place_limit(
+            msg::source(),
+            side,
             limit_price,
             amount_base,
-            max_quote,
+            expires_at,
+            false,
         )
     }
 
+    /// Places a limit order that only takes liquidity: it matches whatever crosses
+    /// `limit_price` and the unfilled remainder is cancelled and refunded instead of resting.
+    #[export(unwrap_result)]
+    pub fn submit_reduce_only(
+        &mut self,
+        side: Side,
+        limit_price: u128,
+        amount_base: u128,
+    ) -> Result<OrderId, MatchError> {
+        self.place_limit(msg::source(), side, limit_price, amount_base, 0, true)
+    }
+
+    /// Parks a market-if-touched order. Once the last trade price reaches `trigger_price`
+    /// (at or above for buys, at or below for sells) it executes as a market order.
+    /// Buys lock `max_quote` as their budget; sells lock `amount_base`.
+    #[export]
+    pub fn submit_market_if_touched(
+        &mut self,
+        side: Side,
+        trigger_price: u128,
+        amount_base: u128,
+        max_quote: u128,
+    ) -> OrderId {
+        if trigger_price == 0 || amount_base == 0 {
+            panic!("InvalidTriggerOrder");
+        }
+        if side == Side::Buy && max_quote == 0 {
+            panic!("InvalidTriggerOrder");
+        }
+
+        let mut st = self.get_mut();
+        st.ensure_not_paused();
+        st.ensure_order_size(U256::from(amount_base));
+        let id = st.alloc_order_id();
+        st.place_trigger(TriggerOrder {
+            id,
+            owner: msg::source(),
+            side,
+            trigger_price: U256::from(trigger_price),
+            amount_base: U256::from(amount_base),
+            max_quote: if side == Side::Buy {
+                U256::from(max_quote)
+            } else {
+                U256::zero()
+            },
+        });
+        id
+    }
+
     #[export]
     pub fn populate_demo_orders(
         &mut self,
@@ -464,9 +985,10 @@ impl<'a> Orderbook<'a> {
                     let owner = Orderbook::seeded_actor(seed, Side::Buy, level, i);
                     let amount_base =
                         Orderbook::seeded_amount(&mut rng_state, min_amount_base, max_amount_base);
-                    let quote_to_lock = matching_engine::calc_quote_ceil(
+                    let quote_to_lock = matching_engine::calc_quote_ceil_scaled(
                         U256::from(amount_base),
                         U256::from(bid_price),
+                        self.get().book.price_exponent(),
                     )
                     .expect("PopulateMathError");
 
@@ -497,98 +1019,942 @@ impl<'a> Orderbook<'a> {
     }
 
     #[export]
-    pub fn cancel_order(&mut self, order_id: u64) {
+    pub fn cancel_order(&mut self, order_id: OrderId) {
         let caller = msg::source();
-        let mut st = self.get_mut();
+        {
+            let mut st = self.get_mut();
+            Orderbook::cancel_for_owner(&mut st, caller, order_id);
+        }
+        self.emit_top_of_book_changes();
+    }
 
-        let Some(view) = st.book.peek_order(order_id) else {
-            panic!("Order not found");
+    /// Cancels up to `max_to_cancel` of the caller's resting orders, oldest first, skipping
+    /// any still too young to cancel. Returns how many were removed; a result equal to
+    /// `max_to_cancel` means more may remain, so call again to continue.
+    #[export]
+    pub fn cancel_all(&mut self, max_to_cancel: u32) -> u32 {
+        let caller = msg::source();
+        let cancelled = {
+            let mut st = self.get_mut();
+            let now = exec::block_height();
+            st.throttle_cancel(caller, now);
+            let targets: Vec<MakerView> = st
+                .book
+                .orders_of(caller)
+                .into_iter()
+                .filter(|maker| st.order_aged(caller, maker.id, now))
+                .take(max_to_cancel as usize)
+                .collect();
+            Orderbook::cancel_makers(&mut st, targets)
         };
-        if view.owner != caller {
-            panic!("Not order owner");
-        }
 
-        let maker = st.book.cancel(order_id).expect("Order not found");
+        self.emit_cancellations(&cancelled);
+        self.emit_top_of_book_changes();
+        cancelled.len() as u32
+    }
 
-        // Unlock remaining locked funds back to caller.
-        match maker.side {
-            Side::Sell => {
-                st.unlock(caller, Asset::Base, maker.remaining_base);
+    /// Drops resting orders whose `expires_at` has passed and returns their reservations to
+    /// the owners. Looks at up to `max_to_scan` (at most 100) orders by ascending id, carrying
+    /// on from where the previous call stopped. Anyone may call it. Returns how many were dropped.
+    #[export]
+    pub fn purge_expired(&mut self, max_to_scan: u32) -> u32 {
+        let purged = {
+            let mut st = self.get_mut();
+            let now = exec::block_timestamp();
+            let limit = max_to_scan.min(MAX_PURGE_SCAN);
+            let page = st.book.orders_from(st.purge_cursor, limit);
+            st.purge_cursor = match page.last() {
+                Some(last) if page.len() == limit as usize => last.id + 1,
+                _ => 0,
+            };
+
+            let expired = page
+                .into_iter()
+                .filter(|maker| maker.is_expired(now))
+                .collect();
+            Orderbook::cancel_makers(&mut st, expired)
+        };
+
+        self.emit_cancellations(&purged);
+        self.emit_top_of_book_changes();
+        purged.len() as u32
+    }
+
+    /// Cancels each of the caller's resting orders and pending triggers in `order_ids` and
+    /// returns `(id, cancelled)` in input order. Ids that are missing, foreign, repeated or
+    /// still too young are skipped rather than failing the batch. Counts as a single cancel
+    /// for the cooldown.
+    #[export]
+    pub fn cancel_orders(&mut self, order_ids: Vec<OrderId>) -> Vec<(OrderId, bool)> {
+        let caller = msg::source();
+        let (results, cancelled) = {
+            let mut st = self.get_mut();
+            let now = exec::block_height();
+            if !order_ids.is_empty() {
+                st.throttle_cancel(caller, now);
+            }
+            let mut results = Vec::with_capacity(order_ids.len());
+            let mut targets: Vec<MakerView> = Vec::new();
+            for order_id in order_ids {
+                if st
+                    .triggers
+                    .get(&order_id)
+                    .is_some_and(|t| t.owner == caller)
+                {
+                    st.take_trigger(order_id);
+                    results.push((order_id, true));
+                    continue;
+                }
+                let target = st.book.peek_order(order_id).filter(|view| {
+                    view.owner == caller
+                        && st.order_aged(caller, order_id, now)
+                        && !targets.iter().any(|t| t.id == order_id)
+                });
+                results.push((order_id, target.is_some()));
+                targets.extend(target);
             }
-            Side::Buy => {
-                st.unlock(caller, Asset::Quote, maker.reserved_quote);
+            (results, Orderbook::cancel_makers(&mut st, targets))
+        };
+
+        self.emit_cancellations(&cancelled);
+        self.emit_top_of_book_changes();
+        results
+    }
+
+    /// Cancels every order in `cancels`, then places `places` as the caller, returning the
+    /// new ids in input order. All or nothing: a foreign or missing id, or a placement the
+    /// caller cannot fund, reverts the whole batch. Counts as a single cancel for the cooldown.
+    #[export(unwrap_result)]
+    pub fn replace_orders(
+        &mut self,
+        cancels: Vec<OrderId>,
+        places: Vec<OrderInputIO>,
+    ) -> Result<Vec<OrderId>, MatchError> {
+        let caller = msg::source();
+        if !cancels.is_empty() {
+            let mut st = self.get_mut();
+            st.throttle_cancel(caller, exec::block_height());
+            for order_id in cancels {
+                Orderbook::cancel_owned(&mut st, caller, order_id);
             }
         }
+
+        let mut order_ids = Vec::with_capacity(places.len());
+        for (side, kind, limit_price, amount_base, max_quote) in places {
+            order_ids.push(self.place_order(
+                caller,
+                side,
+                kind,
+                limit_price,
+                amount_base,
+                max_quote,
+            )?);
+        }
+        self.emit_top_of_book_changes();
+        Ok(order_ids)
     }
 
+    /// Cancels an order and sends the funds it released to the caller's vault.
+    /// Returns the withdrawn amount (base for sells, quote for buys).
     #[export]
-    pub fn best_bid_price(&self) -> u128 {
-        self.get()
-            .book
-            .best_price(Side::Buy)
-            .map(|x| x.low_u128())
-            .unwrap_or(0)
+    pub async fn cancel_and_withdraw(&mut self, order_id: OrderId) -> u128 {
+        let caller = msg::source();
+        let (asset, freed) = {
+            let mut st = self.get_mut();
+            Orderbook::cancel_for_owner(&mut st, caller, order_id)
+        };
+        self.emit_top_of_book_changes();
+        let amount = freed.low_u128();
+        if amount > 0 {
+            self.withdraw_to_vault(caller, asset, amount).await;
+        }
+        amount
     }
 
+    /// Self-trade prevention for the caller's own orders.
     #[export]
-    pub fn best_ask_price(&self) -> u128 {
-        self.get()
-            .book
-            .best_price(Side::Sell)
-            .map(|x| x.low_u128())
-            .unwrap_or(0)
+    pub fn set_stp_mode(&mut self, mode: StpMode) {
+        let caller = msg::source();
+        let mut st = self.get_mut();
+        if mode == StpMode::None {
+            st.stp_modes.remove(&caller);
+        } else {
+            st.stp_modes.insert(caller, mode);
+        }
     }
 
+    /// Halts or resumes order entry. While paused every new order is refused; cancels and
+    /// withdrawals keep working. Admin only.
+    ///
+    /// With `cancel_on_pause` set, pausing also cancels up to 100 resting orders, oldest id
+    /// first, and refunds their reservations to the owners. Returns how many were cancelled;
+    /// a result of 100 means more may remain, so call `pause(true)` again to continue.
     #[export]
-    pub fn balance_of(&self, who: ActorId) -> (u128, u128) {
-        let st = self.get();
-        let b = st.balances.get(&who).cloned().unwrap_or_default();
-        (b.base.low_u128(), b.quote.low_u128())
+    pub fn pause(&mut self, paused: bool) -> u32 {
+        let cancelled = {
+            let mut st = self.get_mut();
+            if st.admin != Some(msg::source()) {
+                panic!("Unauthorized: Not Admin");
+            }
+            st.paused = paused;
+            if !(paused && st.cancel_on_pause) {
+                return 0;
+            }
+            let targets = st.book.orders_from(0, MAX_PAUSE_CANCELS);
+            Orderbook::cancel_makers(&mut st, targets)
+        };
+
+        self.emit_cancellations(&cancelled);
+        self.emit_top_of_book_changes();
+        cancelled.len() as u32
     }
 
+    /// Whether `pause(true)` also cancels resting orders and refunds them. Admin only.
     #[export]
-    pub fn order_by_id(&self, order_id: u64) -> (bool, u64, ActorId, u16, u128, u128, u128) {
-        let st = self.get();
-
-        // Tuple-only ABI: return (found, fields...). If not found -> found=false and zeros.
-        let Some(o) = st.book.peek_order(order_id) else {
-            return (false, 0, ActorId::zero(), 0, 0, 0, 0);
-        };
+    pub fn set_cancel_on_pause(&mut self, enabled: bool) {
+        let mut st = self.get_mut();
+        if st.admin != Some(msg::source()) {
+            panic!("Unauthorized: Not Admin");
+        }
+        st.cancel_on_pause = enabled;
+    }
 
-        let side_io: u16 = match o.side {
-            Side::Buy => 0,
-            Side::Sell => 1,
+    /// Writes off `user`'s whole free `token` balance and emits `BalanceZeroed` with the
+    /// amount removed. Reserved funds are left alone. Admin only, and only while paused.
+    #[export]
+    pub fn admin_zero_balance(&mut self, user: ActorId, token: TokenId) {
+        let amount = {
+            let mut st = self.get_mut();
+            if st.admin != Some(msg::source()) {
+                panic!("Unauthorized: Not Admin");
+            }
+            if !st.paused {
+                panic!("NotPaused");
+            }
+            let asset = st.token_asset(token);
+            let balance = st.balances.get(&user).cloned().unwrap_or_default();
+            let free = match asset {
+                Asset::Base => balance.base,
+                Asset::Quote => balance.quote,
+            };
+            st.withdraw(user, asset, free);
+            free.low_u128()
         };
 
-        (
-            true,
-            o.id,
-            o.owner,
-            side_io,
-            o.price.low_u128(),
-            o.remaining_base.low_u128(),
-            o.reserved_quote.low_u128(),
-        )
+        let seq = self.alloc_event_seq();
+        self.publish(Events::BalanceZeroed {
+            seq,
+            user: actor_to_eth(user),
+            token,
+            amount,
+        });
     }
 
+    /// First step of an admin handover: records `new_admin`, who then has to call
+    /// `accept_admin`. A new proposal replaces one that wasn't accepted yet. Admin only.
     #[export]
-    pub fn orders(&self, offset: u32, count: u32) -> Vec<(u64, ActorId, u16, u128, u128, u128)> {
-        let state = self.get();
+    pub fn propose_admin(&mut self, new_admin: ActorId) {
+        let mut st = self.get_mut();
+        if st.admin != Some(msg::source()) {
+            panic!("Unauthorized: Not Admin");
+        }
+        st.pending_admin = Some(new_admin);
+    }
+
+    /// Completes the handover started by `propose_admin`. Only the proposed admin may call it.
+    #[export]
+    pub fn accept_admin(&mut self) {
+        let caller = msg::source();
+        let previous = {
+            let mut st = self.get_mut();
+            if st.pending_admin != Some(caller) {
+                panic!("Unauthorized: Not Pending Admin");
+            }
+            st.pending_admin = None;
+            st.admin.replace(caller).unwrap_or_default()
+        };
+
+        let seq = self.alloc_event_seq();
+        self.publish(Events::AdminChanged {
+            seq,
+            previous_admin: actor_to_eth(previous),
+            new_admin: actor_to_eth(caller),
+        });
+    }
+
+    /// Fee withheld from the quote side of every fill, in basis points (at most 10000).
+    /// Admin only; zero, the initial value, disables fees.
+    #[export]
+    pub fn set_fee_rate_bps(&mut self, bps: u128) {
+        let mut st = self.get_mut();
+        if st.admin != Some(msg::source()) {
+            panic!("Unauthorized: Not Admin");
+        }
+        if bps > 10_000 {
+            panic!("InvalidRate");
+        }
+        st.fee_rate_bps = bps;
+    }
+
+    /// Moves every accrued protocol fee to the admin's quote balance, from where it can be
+    /// withdrawn like any other funds. Returns the amount claimed. Admin only.
+    #[export]
+    pub fn claim_protocol_fees(&mut self) -> u128 {
+        let caller = msg::source();
+        let (token, amount) = {
+            let mut st = self.get_mut();
+            if st.admin != Some(caller) {
+                panic!("Unauthorized: Not Admin");
+            }
+            let amount = core::mem::take(&mut st.protocol_fee_quote);
+            st.deposit(caller, Asset::Quote, amount);
+            (st.quote_token_id, amount.low_u128())
+        };
+        if amount == 0 {
+            return 0;
+        }
+
+        let seq = self.alloc_event_seq();
+        self.publish(Events::FeesClaimed { seq, token, amount });
+        amount
+    }
+
+    /// Minimum blocks between two cancels by the same account. Admin only; zero disables it.
+    #[export]
+    pub fn set_cancel_cooldown(&mut self, blocks: u32) {
+        let mut st = self.get_mut();
+        if st.admin != Some(msg::source()) {
+            panic!("Unauthorized: Not Admin");
+        }
+        st.cancel_cooldown_blocks = blocks;
+    }
+
+    /// Caps how many orders the book may hold in total. A placement that would only rest past
+    /// the cap is rejected; one that partly executed keeps its fills and has the rest cancelled
+    /// and refunded. Admin only; zero removes the cap.
+    #[export]
+    pub fn set_max_resting_orders(&mut self, max_resting_orders: u32) {
+        let mut st = self.get_mut();
+        if st.admin != Some(msg::source()) {
+            panic!("Unauthorized: Not Admin");
+        }
+        st.max_resting_orders = max_resting_orders;
+    }
+
+    /// Blocks an order must rest before its owner may cancel it. Admin only; zero disables
+    /// it. The admin's own cancels are never held back.
+    #[export]
+    pub fn set_min_order_lifetime(&mut self, blocks: u32) {
+        let mut st = self.get_mut();
+        if st.admin != Some(msg::source()) {
+            panic!("Unauthorized: Not Admin");
+        }
+        st.min_order_lifetime_blocks = blocks;
+    }
+
+    /// Caps `amount_base` of any single order. Admin only; zero removes the cap.
+    #[export]
+    pub fn set_max_order_base(&mut self, max_order_base: u128) {
+        let mut st = self.get_mut();
+        if st.admin != Some(msg::source()) {
+            panic!("Unauthorized: Not Admin");
+        }
+        st.max_order_base = U256::from(max_order_base);
+    }
+
+    /// Caps how much a single taker may sweep: matching stops after `max_fills` fills or
+    /// `max_base` traded and the rest of the order is cancelled and refunded. Admin only;
+    /// zero disables either cap.
+    #[export]
+    pub fn set_sweep_limits(&mut self, max_fills: u32, max_base: u128) {
+        let mut st = self.get_mut();
+        if st.admin != Some(msg::source()) {
+            panic!("Unauthorized: Not Admin");
+        }
+        st.limits.sweep_max_fills = max_fills;
+        st.limits.sweep_max_base = U256::from(max_base);
+    }
+
+    /// Sets the decimal exponent prices are scaled by. Admin only, and only while the book is empty.
+    #[export]
+    pub fn set_price_exponent(&mut self, price_exponent: u32) {
+        let mut st = self.get_mut();
+        if st.admin != Some(msg::source()) {
+            panic!("Unauthorized: Not Admin");
+        }
+        if price_exponent > MAX_PRICE_EXPONENT {
+            panic!("InvalidPriceExponent");
+        }
+        if !st.book.is_empty() {
+            panic!("MarketNotEmpty");
+        }
+        st.book.set_price_exponent(price_exponent);
+    }
+
+    /// Cancels the caller's orders resting at `price` on `side`, skipping any still too young
+    /// to cancel. Returns how many orders were removed.
+    #[export]
+    pub fn cancel_at_price(&mut self, side: Side, price: u128) -> u32 {
+        let caller = msg::source();
+        let cancelled = {
+            let mut st = self.get_mut();
+            let now = exec::block_height();
+            st.throttle_cancel(caller, now);
+            let targets = st
+                .book
+                .owner_orders_at_price(side, U256::from(price), caller)
+                .into_iter()
+                .filter(|maker| st.order_aged(caller, maker.id, now))
+                .collect();
+            Orderbook::cancel_makers(&mut st, targets)
+        };
+
+        self.emit_cancellations(&cancelled);
+        self.emit_top_of_book_changes();
+        cancelled.len() as u32
+    }
+
+    /// Shrinks a resting order at its current price without losing time priority.
+    /// Only the freed part of the reservation is returned to the caller's balance.
+    /// Subject to the same minimum lifetime and cooldown as a cancel.
+    #[export]
+    pub fn reduce_order(&mut self, order_id: OrderId, new_amount_base: u128) -> u128 {
+        let caller = msg::source();
+        let refund = {
+            let mut st = self.get_mut();
+
+            let Some(view) = st.book.peek_order(order_id) else {
+                panic!("Order not found");
+            };
+            if view.owner != caller {
+                panic!("Not order owner");
+            }
+            let now = exec::block_height();
+            st.ensure_order_aged(caller, order_id, now);
+            st.throttle_cancel(caller, now);
+
+            st.reduce_resting(&view, U256::from(new_amount_base))
+        };
+        self.emit_top_of_book_changes();
+        refund.low_u128()
+    }
+
+    /// Amends a resting order. Shrinking it at the same price edits it in place and keeps
+    /// its queue position; any price change or size increase cancels it and re-enters it
+    /// under the same id at the back of the queue, matching first if the new price crosses.
+    /// The order keeps its expiry; one that has already expired can't be amended.
+    #[export(unwrap_result)]
+    pub fn amend_order(
+        &mut self,
+        order_id: OrderId,
+        new_price: u128,
+        new_quantity: u128,
+    ) -> Result<(), MatchError> {
+        let caller = msg::source();
+        let (old_price, old_quantity) = {
+            let mut st = self.get_mut();
+
+            let Some(view) = st.book.peek_order(order_id) else {
+                panic!("Order not found");
+            };
+            if view.owner != caller {
+                panic!("Not order owner");
+            }
+            if new_quantity == 0 {
+                panic!("InvalidAmendAmount");
+            }
+            if view.is_expired(exec::block_timestamp()) {
+                panic!("OrderExpired");
+            }
+            st.ensure_order_aged(caller, order_id, exec::block_height());
+
+            let new_price_fp = U256::from(new_price);
+            let new_quantity_fp = U256::from(new_quantity);
+            if new_price_fp == view.price && new_quantity_fp <= view.remaining_base {
+                if new_quantity_fp < view.remaining_base {
+                    st.reduce_resting(&view, new_quantity_fp);
+                }
+            } else {
+                st.ensure_order_size(new_quantity_fp);
+                let maker = st.book.cancel(view.id).expect("Order not found");
+                st.order_created.remove(&order_id);
+                match maker.side {
+                    Side::Sell => st.unlock(caller, Asset::Base, maker.remaining_base),
+                    Side::Buy => st.unlock(caller, Asset::Quote, maker.reserved_quote),
+                }
+
+                let incoming = IncomingOrder {
+                    id: order_id,
+                    owner: caller,
+                    side: view.side,
+                    kind: OrderKind::Limit,
+                    limit_price: new_price_fp,
+                    amount_base: new_quantity_fp,
+                    max_quote: U256::zero(),
+                    worst_price: None,
+                    stp: st.stp_mode(caller),
+                    min_quote: U256::zero(),
+                    expires_at: view.expires_at,
+                    reduce_only: false,
+                };
+                Orderbook::execute_for_owner(&mut st, &incoming)?;
+                st.ensure_resting_capacity(&incoming);
+                Orderbook::fire_triggers(&mut st)?;
+            }
+            (view.price.low_u128(), view.remaining_base.low_u128())
+        };
+
+        let seq = self.alloc_event_seq();
+        self.publish(Events::OrderModified {
+            seq,
+            order_id,
+            old_price,
+            new_price,
+            old_quantity,
+            new_quantity,
+        });
+        self.emit_budget_refunds();
+        self.emit_rejections();
+        self.emit_top_of_book_changes();
+        Ok(())
+    }
+
+    /// Up to `levels` (at most 100) price levels per side as `(bids, asks)`, each with the
+    /// total base resting there. Bids descend and asks ascend from the best price.
+    #[export]
+    pub fn depth(&self, levels: u32) -> (DepthSide, DepthSide) {
+        let st = self.get();
+        let levels = levels.min(MAX_DEPTH_LEVELS) as usize;
+        let side_depth = |side| {
+            st.book
+                .depth(side, levels)
+                .into_iter()
+                .map(|(price, quantity)| (price.low_u128(), quantity.low_u128()))
+                .collect()
+        };
+        (side_depth(Side::Buy), side_depth(Side::Sell))
+    }
+
+    /// `(min, max)` resting price per side as `(bids, asks)`; `None` for an empty side.
+    #[export]
+    pub fn price_range(&self) -> (PriceRange, PriceRange) {
+        let st = self.get();
+        let range = |side| {
+            st.book
+                .price_range(side)
+                .map(|(min, max)| (min.low_u128(), max.low_u128()))
+        };
+        (range(Side::Buy), range(Side::Sell))
+    }
+
+    /// Maker arena slots as `(occupied, free, capacity)`. Freed slots are reused before
+    /// the arena grows past `capacity`.
+    #[export]
+    pub fn arena_footprint(&self) -> (u32, u32, u32) {
+        self.get().book.arena_footprint()
+    }
+
+    /// Level and order counts per side: `(bid_levels, bid_orders, ask_levels, ask_orders)`.
+    #[export]
+    pub fn book_stats(&self) -> (u32, u32, u32, u32) {
+        self.get().book.stats()
+    }
+
+    /// Debug-only: runs `orders` in sequence against a copy of the book as the caller
+    /// and returns each execution report. Balances and the real book are left untouched.
+    /// `PegToBest` is not supported here.
+    #[export(unwrap_result)]
+    pub fn simulate(&self, orders: Vec<OrderInputIO>) -> Result<Vec<ExecutionReport>, MatchError> {
+        #[cfg(not(feature = "debug"))]
+        {
+            let _ = orders;
+            panic!("DebugFeatureDisabled");
+        }
+
+        #[cfg(feature = "debug")]
+        {
+            let st = self.get();
+            let caller = msg::source();
+            let mut book = st.book.clone();
+            let mut next_id = st.next_order_id;
+            let limits = st.engine_limits(exec::block_timestamp());
+            let mut reports = Vec::with_capacity(orders.len());
+            for (side, kind, limit_price, amount_base, max_quote) in orders {
+                let Some(kind) = kind.engine_kind() else {
+                    panic!("Invalid kind");
+                };
+                let (max_quote, min_quote) = quote_bounds(side, kind, max_quote);
+                let incoming = IncomingOrder {
+                    id: next_id,
+                    owner: caller,
+                    side,
+                    kind,
+                    limit_price: U256::from(limit_price),
+                    amount_base: U256::from(amount_base),
+                    max_quote,
+                    worst_price: None,
+                    stp: st.stp_mode(caller),
+                    min_quote,
+                    expires_at: 0,
+                    reduce_only: false,
+                };
+                next_id = next_id.saturating_add(1);
+                reports.push(matching_engine::execute(&mut book, &incoming, limits)?);
+            }
+            Ok(reports)
+        }
+    }
+
+    #[export]
+    pub fn best_bid_price(&self) -> u128 {
+        self.get()
+            .book
+            .best_price(Side::Buy)
+            .map(|x| x.low_u128())
+            .unwrap_or(0)
+    }
+
+    #[export]
+    pub fn best_ask_price(&self) -> u128 {
+        self.get()
+            .book
+            .best_price(Side::Sell)
+            .map(|x| x.low_u128())
+            .unwrap_or(0)
+    }
+
+    /// How a prospective limit order would split: `(filled_base, rest_base, rest_price)`.
+    /// The remainder always rests at `limit_price`; `rest_base` is zero when sweep limits
+    /// would cancel it instead.
+    #[export(unwrap_result)]
+    pub fn preview_limit_rest(
+        &self,
+        side: Side,
+        limit_price: u128,
+        amount_base: u128,
+    ) -> Result<(u128, u128, Price), MatchError> {
+        let st = self.get();
+        let probe = IncomingOrder {
+            id: 0,
+            owner: msg::source(),
+            side,
+            kind: OrderKind::Limit,
+            limit_price: U256::from(limit_price),
+            amount_base: U256::from(amount_base),
+            max_quote: U256::zero(),
+            worst_price: None,
+            stp: StpMode::None,
+            min_quote: U256::zero(),
+            expires_at: 0,
+            reduce_only: false,
+        };
+        let (filled, rest) = matching_engine::preview_limit_rest(
+            &st.book,
+            &probe,
+            st.engine_limits(exec::block_timestamp()),
+        )?;
+        Ok((filled.low_u128(), rest.low_u128(), limit_price))
+    }
+
+    /// Base amount of a prospective limit order that would cross the caller's own resting orders.
+    #[export(unwrap_result)]
+    pub fn would_self_trade(
+        &self,
+        side: Side,
+        limit_price: u128,
+        amount_base: u128,
+    ) -> Result<(bool, u128), MatchError> {
+        let st = self.get();
+        let probe = IncomingOrder {
+            id: 0,
+            owner: msg::source(),
+            side,
+            kind: OrderKind::Limit,
+            limit_price: U256::from(limit_price),
+            amount_base: U256::from(amount_base),
+            max_quote: U256::zero(),
+            worst_price: None,
+            stp: StpMode::None,
+            min_quote: U256::zero(),
+            expires_at: 0,
+            reduce_only: false,
+        };
+        let blocked = matching_engine::preview_self_trade(
+            &st.book,
+            &probe,
+            st.limits.max_preview_scans,
+            exec::block_timestamp(),
+        )?;
+        Ok((!blocked.is_zero(), blocked.low_u128()))
+    }
+
+    /// Quote `user` has saved as a taker by filling better than its limit price.
+    /// Improvement is tracked in quote only, so the base token always reports zero.
+    #[export]
+    pub fn get_price_improvement(&self, user: ActorId, token: TokenId) -> u128 {
+        let st = self.get();
+        if token != st.quote_token_id {
+            return 0;
+        }
+        st.price_improvement
+            .get(&user)
+            .map(|v| v.low_u128())
+            .unwrap_or(0)
+    }
+
+    #[export]
+    pub fn is_paused(&self) -> bool {
+        self.get().paused
+    }
+
+    #[export]
+    pub fn cancel_on_pause(&self) -> bool {
+        self.get().cancel_on_pause
+    }
+
+    #[export]
+    pub fn fee_rate_bps(&self) -> u128 {
+        self.get().fee_rate_bps
+    }
+
+    /// Quote collected in fees and not yet claimed.
+    #[export]
+    pub fn protocol_fee_quote(&self) -> u128 {
+        self.get().protocol_fee_quote.low_u128()
+    }
+
+    #[export]
+    pub fn cancel_cooldown(&self) -> u32 {
+        self.get().cancel_cooldown_blocks
+    }
+
+    #[export]
+    pub fn max_resting_orders(&self) -> u32 {
+        self.get().max_resting_orders
+    }
+
+    #[export]
+    pub fn min_order_lifetime(&self) -> u32 {
+        self.get().min_order_lifetime_blocks
+    }
+
+    #[export]
+    pub fn max_order_base(&self) -> u128 {
+        self.get().max_order_base.low_u128()
+    }
+
+    /// `(max_fills, max_base)` per taker order; zero means unlimited.
+    #[export]
+    pub fn sweep_limits(&self) -> (u32, u128) {
+        let st = self.get();
+        (
+            st.limits.sweep_max_fills,
+            st.limits.sweep_max_base.low_u128(),
+        )
+    }
+
+    #[export]
+    pub fn stp_mode(&self, user: ActorId) -> StpMode {
+        self.get().stp_mode(user)
+    }
+
+    #[export]
+    pub fn price_exponent(&self) -> u32 {
+        self.get().book.price_exponent()
+    }
+
+    #[export]
+    pub fn version(&self) -> Version {
+        VERSION
+    }
+
+    /// True once the program has been created with an admin and while it isn't paused.
+    #[export]
+    pub fn health(&self) -> bool {
+        let st = self.get();
+        st.admin.is_some() && !st.paused
+    }
+
+    /// True if a resting order at `price` on `side` would be strictly better than
+    /// that side's current best (or the side is empty).
+    #[export]
+    pub fn improves_book(&self, side: Side, price: u128) -> bool {
+        let price = U256::from(price);
+        match self.get().book.best_price(side) {
+            None => true,
+            Some(best) => match side {
+                Side::Buy => price > best,
+                Side::Sell => price < best,
+            },
+        }
+    }
+
+    #[export]
+    pub fn balance_of(&self, who: ActorId) -> (u128, u128) {
+        let st = self.get();
+        let b = st.balances.get(&who).cloned().unwrap_or_default();
+        (b.base.low_u128(), b.quote.low_u128())
+    }
+
+    /// Signed base exposure of `user`: available base, plus base still to be bought by
+    /// resting buys, minus base still offered by resting sells. Saturates at the `i128` range.
+    #[export]
+    pub fn net_base_exposure(&self, user: ActorId) -> i128 {
+        let st = self.get();
+        let as_i128 = |x: U256| i128::try_from(x.low_u128()).unwrap_or(i128::MAX);
+        let available = st.balances.get(&user).map(|b| b.base).unwrap_or_default();
+        st.book
+            .orders_of(user)
+            .iter()
+            .fold(as_i128(available), |exposure, maker| match maker.side {
+                Side::Buy => exposure.saturating_add(as_i128(maker.remaining_base)),
+                Side::Sell => exposure.saturating_sub(as_i128(maker.remaining_base)),
+            })
+    }
+
+    /// `(id, side, price, remaining_base, reserved_quote)` of each order `user` has resting,
+    /// bids best-first then asks best-first, FIFO within a level. Truncated after
+    /// `MAX_OPEN_ORDERS` (100) entries.
+    #[export]
+    pub fn open_orders_of(&self, user: ActorId) -> Vec<(OrderId, Side, Price, Quantity, Quantity)> {
+        self.get()
+            .book
+            .open_orders_of(user, MAX_OPEN_ORDERS)
+            .into_iter()
+            .map(|maker| {
+                (
+                    maker.id,
+                    maker.side,
+                    maker.price.low_u128(),
+                    maker.remaining_base.low_u128(),
+                    maker.reserved_quote.low_u128(),
+                )
+            })
+            .collect()
+    }
+
+    /// Quote volume `user` has traded as `(maker, taker)`.
+    #[export]
+    pub fn volume_split(&self, user: ActorId) -> (u128, u128) {
+        let st = self.get();
+        let maker = st.maker_volume.get(&user).copied().unwrap_or_default();
+        let taker = st.taker_volume.get(&user).copied().unwrap_or_default();
+        (maker.low_u128(), taker.low_u128())
+    }
+
+    /// Amount of `token` the caller can withdraw right now. Funds reserved by resting
+    /// orders or pending triggers are excluded; unknown tokens report zero.
+    #[export]
+    pub fn withdrawable(&self, token: TokenId) -> u128 {
+        let st = self.get();
+        let b = st.balances.get(&msg::source()).cloned().unwrap_or_default();
+        if token == st.base_token_id {
+            b.base.low_u128()
+        } else if token == st.quote_token_id {
+            b.quote.low_u128()
+        } else {
+            0
+        }
+    }
+
+    /// Token and amount an order with these parameters would lock on submission, so a
+    /// user knows what to deposit first. Nothing is reserved.
+    #[export]
+    pub fn required_funds(
+        &self,
+        side: Side,
+        kind: OrderType,
+        limit_price: u128,
+        amount_base: u128,
+        max_quote: u128,
+    ) -> (TokenId, u128) {
+        let st = self.get();
+        let (kind, limit_price) = match kind.engine_kind() {
+            Some(kind) => (kind, U256::from(limit_price)),
+            None => (OrderKind::Limit, st.peg_price(side)),
+        };
+        let probe = IncomingOrder {
+            id: 0,
+            owner: msg::source(),
+            side,
+            kind,
+            limit_price,
+            amount_base: U256::from(amount_base),
+            max_quote: U256::from(max_quote),
+            worst_price: None,
+            stp: StpMode::None,
+            min_quote: U256::zero(),
+            expires_at: 0,
+            reduce_only: false,
+        };
+        let (asset, amount) = st.taker_funds(&probe);
+        let token = match asset {
+            Asset::Base => st.base_token_id,
+            Asset::Quote => st.quote_token_id,
+        };
+        (token, amount.low_u128())
+    }
+
+    #[export]
+    pub fn order_by_id(&self, order_id: OrderId) -> Option<OrderView> {
+        let st = self.get();
+        st.book.peek_order(order_id).map(|o| st.order_view(o))
+    }
+
+    /// `(found, created_at, expires_at, now)` for a resting order, all block timestamps: when
+    /// it started resting, its expiry (0 = good till cancelled) and the current time.
+    /// `found` is false, with zeroed fields, once the order is gone.
+    #[export]
+    pub fn order_lifetime(&self, order_id: OrderId) -> (bool, u64, u64, u64) {
+        let st = self.get();
+        let now = exec::block_timestamp();
+        match st.book.peek_order(order_id) {
+            Some(maker) => {
+                let created_at = st
+                    .order_created
+                    .get(&order_id)
+                    .map(|created| created.timestamp)
+                    .unwrap_or_default();
+                (true, created_at, maker.expires_at, now)
+            }
+            None => (false, 0, 0, now),
+        }
+    }
+
+    /// Pages through every resting order by ascending id, starting at `cursor`.
+    /// Returns the page and the cursor for the next call, or 0 once the book is exhausted.
+    #[export]
+    pub fn all_orders(&self, cursor: OrderId, limit: u32) -> (Vec<OrderView>, OrderId) {
+        let limit = limit.min(MAX_AUDIT_PAGE);
+        let st = self.get();
+        let page = st.book.orders_from(cursor, limit);
+        let next_cursor = match page.last() {
+            Some(last) if page.len() == limit as usize => last.id + 1,
+            _ => 0,
+        };
+        (
+            page.into_iter().map(|o| st.order_view(o)).collect(),
+            next_cursor,
+        )
+    }
+
+    #[export]
+    pub fn orders(
+        &self,
+        offset: u32,
+        count: u32,
+    ) -> Vec<(OrderId, ActorId, Side, u128, u128, u128)> {
+        let state = self.get();
 
         state
             .book
             .orders(offset, count)
             .into_iter()
             .map(|order| {
-                let side_io: u16 = match order.side {
-                    Side::Buy => 0,
-                    Side::Sell => 1,
-                };
-
                 (
                     order.id,
                     order.owner,
-                    side_io,
+                    order.side,
                     order.price.low_u128(),
                     order.remaining_base.low_u128(),
                     order.reserved_quote.low_u128(),
@@ -602,7 +1968,7 @@ impl<'a> Orderbook<'a> {
         &self,
         offset: u32,
         count: u32,
-    ) -> Vec<(u64, ActorId, u16, u128, u128, u128)> {
+    ) -> Vec<(OrderId, ActorId, Side, u128, u128, u128)> {
         let state = self.get();
 
         state
@@ -610,15 +1976,10 @@ impl<'a> Orderbook<'a> {
             .orders_reverse(offset, count)
             .into_iter()
             .map(|order| {
-                let side_io: u16 = match order.side {
-                    Side::Buy => 0,
-                    Side::Sell => 1,
-                };
-
                 (
                     order.id,
                     order.owner,
-                    side_io,
+                    order.side,
                     order.price.low_u128(),
                     order.remaining_base.low_u128(),
                     order.reserved_quote.low_u128(),
@@ -627,6 +1988,13 @@ impl<'a> Orderbook<'a> {
             .collect()
     }
 
+    /// Fills executed since deployment. Unlike `trades_count`, this is not bounded by
+    /// the retained trade history.
+    #[export]
+    pub fn total_trades(&self) -> u64 {
+        self.get().total_trades
+    }
+
     #[export]
     pub fn trades_count(&self) -> u64 {
         self.get().executed_trades.len() as u64
@@ -654,6 +2022,28 @@ impl<'a> Orderbook<'a> {
             .map(Orderbook::trade_to_io)
             .collect()
     }
+
+    /// Up to `limit` retained fills with `seq >= start_seq`, oldest first. Trimmed history
+    /// is not returned; a first seq above `start_seq` means the caller missed fills.
+    #[export]
+    pub fn recent_trades(&self, start_seq: u64, limit: u32) -> Vec<RecentTrade> {
+        let st = self.get();
+        let start = st.executed_trades.partition_point(|t| t.seq < start_seq);
+        st.executed_trades
+            .range(start..)
+            .take(limit as usize)
+            .map(|t| {
+                (
+                    t.seq,
+                    t.maker_order_id,
+                    t.taker_order_id,
+                    t.price,
+                    t.amount_base,
+                    t.amount_quote,
+                )
+            })
+            .collect()
+    }
 }
 
 #[derive(Default)]
@@ -671,6 +2061,9 @@ impl OrderBookProgram {
         max_trades: u32,
         max_preview_scans: u32,
     ) -> Self {
+        if base_token_id == quote_token_id {
+            panic!("InvalidTokenPair");
+        }
         #[cfg(feature = "debug")]
         let mut state = state::State::new(
             msg::source(),
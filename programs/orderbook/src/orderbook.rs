@@ -1,20 +1,22 @@
 use sails_rs::{
     collections::BTreeMap,
     ops::Bound::{Excluded, Unbounded},
-    Vec, U256,
+    ActorId, Vec, U256,
 };
 
 use intrusive_arena::{Arena, Index, List, Node};
 
-use matching_engine::{Book, MakerView, OrderId, RestingOrder, Side};
+use matching_engine::{Book, MakerView, OrderId, RestingOrder, Side, DEFAULT_PRICE_EXPONENT};
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 struct PriceLevel {
     // FIFO via intrusive list
     fifo: List,
+    // sum of remaining_base over the FIFO, so top of book needs no walk
+    total_base: U256,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone)]
 pub struct OrderBook {
     arena: Arena<Node<MakerView>>,
     // maker Side::Buy
@@ -23,6 +25,25 @@ pub struct OrderBook {
     asks: BTreeMap<U256, PriceLevel>,
     // for cancel
     by_id: BTreeMap<OrderId, Index>,
+    // prices are scaled by 10^price_exponent
+    price_exponent: u32,
+    // resting order counts, kept in step with the level FIFOs
+    bid_orders: u32,
+    ask_orders: u32,
+}
+
+impl Default for OrderBook {
+    fn default() -> Self {
+        Self {
+            arena: Arena::default(),
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            by_id: BTreeMap::new(),
+            price_exponent: DEFAULT_PRICE_EXPONENT,
+            bid_orders: 0,
+            ask_orders: 0,
+        }
+    }
 }
 
 impl OrderBook {
@@ -30,6 +51,69 @@ impl OrderBook {
         Self::default()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.by_id.is_empty()
+    }
+
+    /// `(bid_levels, bid_orders, ask_levels, ask_orders)` without walking the book.
+    pub fn stats(&self) -> (u32, u32, u32, u32) {
+        (
+            self.bids.len() as u32,
+            self.bid_orders,
+            self.asks.len() as u32,
+            self.ask_orders,
+        )
+    }
+
+    /// Maker arena `(occupied, free, capacity)` in slots.
+    pub fn arena_footprint(&self) -> (u32, u32, u32) {
+        (
+            self.arena.len() as u32,
+            self.arena.free_slots() as u32,
+            self.arena.capacity() as u32,
+        )
+    }
+
+    /// Lowest and highest resting price on `side`.
+    pub fn price_range(&self, side: Side) -> Option<(U256, U256)> {
+        let map = self.side_map(side);
+        let (min, _) = map.first_key_value()?;
+        let (max, _) = map.last_key_value()?;
+        Some((*min, *max))
+    }
+
+    /// Best price on `side` and the total base resting there.
+    pub fn top_of_book(&self, side: Side) -> Option<(U256, U256)> {
+        let price = self.best_price(side)?;
+        Some((price, self.level_quantity(side, price)))
+    }
+
+    /// Up to `levels` price levels on `side` from the best price outwards, each with the
+    /// total base resting there.
+    pub fn depth(&self, side: Side, levels: usize) -> Vec<(U256, U256)> {
+        let mut out = Vec::new();
+        let mut price = self.best_price(side);
+        while let Some(p) = price {
+            if out.len() >= levels {
+                break;
+            }
+            out.push((p, self.level_quantity(side, p)));
+            price = self.next_price(side, p);
+        }
+        out
+    }
+
+    fn level_quantity(&self, side: Side, price: U256) -> U256 {
+        self.side_map(side)
+            .get(&price)
+            .map(|level| level.total_base)
+            .unwrap_or_default()
+    }
+
+    pub fn set_price_exponent(&mut self, price_exponent: u32) {
+        self.price_exponent = price_exponent;
+    }
+
     fn side_map(&self, side: Side) -> &BTreeMap<U256, PriceLevel> {
         match side {
             Side::Buy => &self.bids,
@@ -41,14 +125,26 @@ impl OrderBook {
         let side = maker.side;
         let price = maker.price;
 
-        let (map, arena, by_id) = match side {
-            Side::Buy => (&mut self.bids, &mut self.arena, &mut self.by_id),
-            Side::Sell => (&mut self.asks, &mut self.arena, &mut self.by_id),
+        let (map, arena, by_id, count) = match side {
+            Side::Buy => (
+                &mut self.bids,
+                &mut self.arena,
+                &mut self.by_id,
+                &mut self.bid_orders,
+            ),
+            Side::Sell => (
+                &mut self.asks,
+                &mut self.arena,
+                &mut self.by_id,
+                &mut self.ask_orders,
+            ),
         };
 
         let level = map.entry(price).or_insert_with(PriceLevel::default);
         let idx = level.fifo.push_back(arena, maker);
+        level.total_base = level.total_base.saturating_add(maker.remaining_base);
         by_id.insert(maker.id, idx);
+        *count += 1;
         idx
     }
 
@@ -59,6 +155,22 @@ impl OrderBook {
         Some(maker)
     }
 
+    /// Orders of `owner` resting at `price` on `side`, FIFO.
+    pub fn owner_orders_at_price(&self, side: Side, price: U256, owner: ActorId) -> Vec<MakerView> {
+        let mut found = Vec::new();
+        let mut cursor = self.level_head(side, price);
+        while let Some(h) = cursor {
+            let Some(node) = self.arena.get(h) else {
+                break;
+            };
+            if node.value.owner == owner {
+                found.push(node.value);
+            }
+            cursor = node.next;
+        }
+        found
+    }
+
     fn remove_by_handle(&mut self, h: Index) {
         let maker = match self.arena.get(h) {
             Some(n) => n.value,
@@ -73,7 +185,10 @@ impl OrderBook {
                 let Some(level) = self.bids.get_mut(&price) else {
                     return;
                 };
-                let _ = level.fifo.remove(&mut self.arena, h);
+                if level.fifo.remove(&mut self.arena, h).is_some() {
+                    self.bid_orders -= 1;
+                    level.total_base = level.total_base.saturating_sub(maker.remaining_base);
+                }
                 if level.fifo.head.is_none() {
                     self.bids.remove(&price);
                 }
@@ -82,7 +197,10 @@ impl OrderBook {
                 let Some(level) = self.asks.get_mut(&price) else {
                     return;
                 };
-                let _ = level.fifo.remove(&mut self.arena, h);
+                if level.fifo.remove(&mut self.arena, h).is_some() {
+                    self.ask_orders -= 1;
+                    level.total_base = level.total_base.saturating_sub(maker.remaining_base);
+                }
                 if level.fifo.head.is_none() {
                     self.asks.remove(&price);
                 }
@@ -90,6 +208,44 @@ impl OrderBook {
         }
     }
 
+    /// Sets a resting maker's remaining base and moves its level total by the difference.
+    fn set_remaining(&mut self, h: Index, new_remaining_base: U256) {
+        let Some(node) = self.arena.get_mut(h) else {
+            return;
+        };
+        let old = core::mem::replace(&mut node.value.remaining_base, new_remaining_base);
+        let (side, price) = (node.value.side, node.value.price);
+        let level = match side {
+            Side::Buy => self.bids.get_mut(&price),
+            Side::Sell => self.asks.get_mut(&price),
+        };
+        if let Some(level) = level {
+            level.total_base = level
+                .total_base
+                .saturating_sub(old)
+                .saturating_add(new_remaining_base);
+        }
+    }
+
+    /// Shrinks a resting order in place, keeping its FIFO position.
+    /// Returns `false` if the order is unknown.
+    pub fn reduce(
+        &mut self,
+        order_id: OrderId,
+        new_remaining_base: U256,
+        new_reserved_quote: U256,
+    ) -> bool {
+        let Some(&idx) = self.by_id.get(&order_id) else {
+            return false;
+        };
+        let Some(node) = self.arena.get_mut(idx) else {
+            return false;
+        };
+        node.value.reserved_quote = new_reserved_quote;
+        self.set_remaining(idx, new_remaining_base);
+        true
+    }
+
     pub fn peek_order(&self, order_id: OrderId) -> Option<MakerView> {
         let idx = *self.by_id.get(&order_id)?;
         let node = self.arena.get(idx)?;
@@ -105,6 +261,46 @@ impl OrderBook {
         self.collect(offset, count, self.by_id.values().rev().copied())
     }
 
+    /// Every resting order of `owner`, ascending by id.
+    pub fn orders_of(&self, owner: ActorId) -> Vec<MakerView> {
+        self.by_id
+            .values()
+            .filter_map(|idx| self.arena.get(*idx).map(|node| node.value))
+            .filter(|maker| maker.owner == owner)
+            .collect()
+    }
+
+    /// Up to `max` resting orders of `owner` in matching order: bids from the best price down,
+    /// then asks from the best price up, FIFO within each level.
+    pub fn open_orders_of(&self, owner: ActorId, max: usize) -> Vec<MakerView> {
+        let mut found = Vec::new();
+        if max == 0 {
+            return found;
+        }
+        for side in [Side::Buy, Side::Sell] {
+            let mut price = self.best_price(side);
+            while let Some(p) = price {
+                let mut cursor = self.level_head(side, p);
+                while let Some(h) = cursor {
+                    if let Some(maker) = self.get_maker(h).filter(|m| m.owner == owner) {
+                        found.push(maker);
+                        if found.len() == max {
+                            return found;
+                        }
+                    }
+                    cursor = self.next_in_level(h);
+                }
+                price = self.next_price(side, p);
+            }
+        }
+        found
+    }
+
+    /// Resting orders with id >= `cursor`, ascending by id.
+    pub fn orders_from(&self, cursor: OrderId, count: u32) -> Vec<MakerView> {
+        self.collect(0, count, self.by_id.range(cursor..).map(|(_, idx)| *idx))
+    }
+
     fn collect(
         &self,
         offset: u32,
@@ -160,9 +356,7 @@ impl Book for OrderBook {
     }
 
     fn set_maker_remaining(&mut self, h: Self::Handle, new_remaining_base: U256) {
-        if let Some(node) = self.arena.get_mut(h) {
-            node.value.remaining_base = new_remaining_base;
-        }
+        self.set_remaining(h, new_remaining_base);
     }
 
     fn remove_maker(&mut self, h: Self::Handle) {
@@ -180,6 +374,10 @@ impl Book for OrderBook {
         }
     }
 
+    fn price_exponent(&self) -> u32 {
+        self.price_exponent
+    }
+
     fn insert_resting(&mut self, o: RestingOrder) {
         self.push_maker(MakerView {
             id: o.id,
@@ -188,6 +386,7 @@ impl Book for OrderBook {
             price: o.price,
             remaining_base: o.remaining_base,
             reserved_quote: o.remaining_quote,
+            expires_at: o.expires_at,
         });
     }
 }
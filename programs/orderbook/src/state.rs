@@ -1,21 +1,58 @@
-use clob_common::TokenId;
-use sails_rs::{collections::HashMap, prelude::*, U256};
+use clob_common::{DepositError, TokenId};
+use sails_rs::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    prelude::*,
+    U256,
+};
 
 use matching_engine::{
-    Completion, EngineLimits, ExecutionReport, IncomingOrder, OrderId, OrderKind, Side, Trade,
+    Book, Completion, EngineLimits, ExecutionReport, IncomingOrder, MakerView, OrderId, OrderKind,
+    Side, StpMode, Trade, TriggerOrder,
 };
 
 use crate::orderbook::OrderBook;
 
-/// TEMPORARY ABI workaround.
+/// Legacy u16 encodings of `Side` and `OrderKind`.
 ///
-/// We currently cannot expose `Side` and `OrderKind` (and/or other rich types like `U256`) directly
-/// through exported contract methods due to interface/codec constraints.
+/// Every entry point, view and event takes `Side` and `OrderType` now. These numbers and their
+/// `*_from_io` helpers are deprecated and stay for one more release.
 pub type SideIO = u16;
 pub type OrderKindIO = u16;
+/// Legacy code of `OrderType::PegToBest`.
+pub const KIND_PEG_TO_BEST: OrderKindIO = 4;
+
+/// Order type taken by the order entry points: the engine kinds plus `PegToBest`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub enum OrderType {
+    Limit,
+    Market,
+    FillOrKill,
+    ImmediateOrCancel,
+    /// Limit order priced one tick inside the best price on its own side, never crossing;
+    /// its `limit_price` is ignored.
+    PegToBest,
+}
+
+impl OrderType {
+    /// Kind the engine matches with; `None` for `PegToBest`, which is priced first.
+    pub fn engine_kind(self) -> Option<OrderKind> {
+        match self {
+            OrderType::Limit => Some(OrderKind::Limit),
+            OrderType::Market => Some(OrderKind::Market),
+            OrderType::FillOrKill => Some(OrderKind::FillOrKill),
+            OrderType::ImmediateOrCancel => Some(OrderKind::ImmediateOrCancel),
+            OrderType::PegToBest => None,
+        }
+    }
+}
+
 const MAX_EXECUTED_TRADE_HISTORY: usize = 512;
 const MAX_RECORDED_TRADES_PER_EXECUTION: usize = 32;
 
+#[deprecated(note = "entry points take `Side`")]
+#[allow(dead_code)]
 pub fn side_from_io(x: SideIO) -> Side {
     match x {
         0 => Side::Buy,
@@ -24,6 +61,8 @@ pub fn side_from_io(x: SideIO) -> Side {
     }
 }
 
+#[deprecated(note = "entry points take `OrderType`")]
+#[allow(dead_code)]
 pub fn kind_from_io(x: OrderKindIO) -> OrderKind {
     match x {
         0 => OrderKind::Limit,
@@ -34,6 +73,38 @@ pub fn kind_from_io(x: OrderKindIO) -> OrderKind {
     }
 }
 
+/// Legacy `OrderKindIO` as an `OrderType`, `KIND_PEG_TO_BEST` included.
+#[deprecated(note = "entry points take `OrderType`")]
+#[allow(dead_code, deprecated)]
+pub fn order_type_from_io(x: OrderKindIO) -> OrderType {
+    if x == KIND_PEG_TO_BEST {
+        return OrderType::PegToBest;
+    }
+    match kind_from_io(x) {
+        OrderKind::Limit => OrderType::Limit,
+        OrderKind::Market => OrderType::Market,
+        OrderKind::FillOrKill => OrderType::FillOrKill,
+        OrderKind::ImmediateOrCancel => OrderType::ImmediateOrCancel,
+    }
+}
+
+/// Splits the `max_quote` argument of an order entry point into the engine's
+/// `(max_quote, min_quote)`: market sells carry their slippage floor in it.
+pub fn quote_bounds(side: Side, kind: OrderKind, quote: u128) -> (U256, U256) {
+    if side == Side::Sell && kind == OrderKind::Market {
+        (U256::zero(), U256::from(quote))
+    } else {
+        (U256::from(quote), U256::zero())
+    }
+}
+
+/// When an order started resting: the block height and that block's timestamp.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OrderCreated {
+    pub block: u32,
+    pub timestamp: u64,
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct AccountBalances {
     pub base: U256,
@@ -52,15 +123,76 @@ pub struct ExecutedTrade {
     pub amount_quote: u128,
 }
 
+/// Resting order as returned by `order_by_id`.
+///
+/// New per-order metadata (flags, timestamps) is appended here rather than widening a tuple.
+#[derive(Clone, Debug, Encode, Decode, TypeInfo)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct OrderView {
+    pub id: OrderId,
+    pub owner: ActorId,
+    pub side: Side,
+    pub price: u128,
+    pub remaining_base: u128,
+    pub reserved_quote: u128,
+    /// Block the order started resting at; amending its price restarts it.
+    pub created_at: u32,
+    /// Block timestamp after which the order stops matching (0 = good till cancelled).
+    pub expires_at: u64,
+}
+
 #[derive(Default, Debug)]
 pub struct State {
     pub admin: Option<ActorId>,
+    /// Proposed by `propose_admin`; becomes admin once it calls `accept_admin`.
+    pub pending_admin: Option<ActorId>,
+    /// Set by the admin during an incident: new orders are refused, cancels still work.
+    pub paused: bool,
+    /// Whether `pause(true)` also pulls resting orders off the book and refunds them.
+    pub cancel_on_pause: bool,
     pub next_order_id: OrderId,
     pub next_trade_seq: u64,
+    pub next_event_seq: u64,
+    /// Every fill ever executed, including ones too numerous to keep in `executed_trades`.
+    pub total_trades: u64,
     pub limits: EngineLimits,
+    /// Largest `amount_base` a single order may carry; zero disables the cap.
+    pub max_order_base: U256,
+    /// Orders the whole book may hold; 0 = unbounded.
+    pub max_resting_orders: u32,
+    /// Blocks an account must wait between cancels; zero disables the throttle.
+    pub cancel_cooldown_blocks: u32,
+    pub last_cancel_block: HashMap<ActorId, u32>,
+    pub min_order_lifetime_blocks: u32,
+    /// When each order placed through the matching path started resting.
+    pub order_created: HashMap<OrderId, OrderCreated>,
+    /// Order id `purge_expired` resumes scanning from; wraps to 0 at the end of the book.
+    pub purge_cursor: OrderId,
     pub book: OrderBook,
     pub balances: HashMap<ActorId, AccountBalances>,
-    pub executed_trades: Vec<ExecutedTrade>,
+    /// Self-trade prevention each account's takers run with; absent means `StpMode::None`.
+    pub stp_modes: HashMap<ActorId, StpMode>,
+    pub executed_trades: VecDeque<ExecutedTrade>,
+    /// Market-if-touched orders waiting for `last_trade_price` to reach their trigger.
+    pub triggers: BTreeMap<OrderId, TriggerOrder>,
+    pub last_trade_price: U256,
+    /// Cumulative quote saved by takers filling better than their limit price.
+    pub price_improvement: HashMap<ActorId, U256>,
+    /// Unused budget handed back to fully filled buy takers; drained into events by the service.
+    pub budget_refunds: Vec<(ActorId, U256)>,
+    /// `(side, amount_base)` of fill-or-kill orders rejected unfilled; drained into events.
+    pub rejected_orders: Vec<(Side, U256)>,
+    /// Top of book `(price, quantity)` per side as last announced in events; zero when empty.
+    pub published_best_bid: (U256, U256),
+    pub published_best_ask: (U256, U256),
+    /// Cumulative quote traded per user while resting in the book.
+    pub maker_volume: HashMap<ActorId, U256>,
+    /// Cumulative quote traded per user as the incoming order.
+    pub taker_volume: HashMap<ActorId, U256>,
+    /// Quote withheld from whichever side receives quote in each fill, in basis points.
+    pub fee_rate_bps: u128,
+    /// Quote collected through `fee_rate_bps` and not yet claimed.
     pub protocol_fee_quote: U256,
     pub base_token_id: TokenId,
     pub quote_token_id: TokenId,
@@ -86,15 +218,42 @@ impl State {
     ) -> Self {
         Self {
             admin: Some(admin),
+            pending_admin: None,
+            paused: false,
+            cancel_on_pause: false,
             next_order_id: 1,
             next_trade_seq: 1,
+            next_event_seq: 1,
+            total_trades: 0,
             limits: EngineLimits {
                 max_trades,
                 max_preview_scans,
+                sweep_max_fills: 0,
+                sweep_max_base: U256::zero(),
+                // set per execution by `engine_limits`
+                now: 0,
             },
+            max_order_base: U256::zero(),
+            max_resting_orders: 0,
+            cancel_cooldown_blocks: 0,
+            last_cancel_block: HashMap::new(),
+            min_order_lifetime_blocks: 0,
+            order_created: HashMap::new(),
+            purge_cursor: 0,
             book: OrderBook::new(),
             balances: HashMap::with_capacity(100_000),
-            executed_trades: Vec::new(),
+            stp_modes: HashMap::new(),
+            executed_trades: VecDeque::new(),
+            triggers: BTreeMap::new(),
+            last_trade_price: U256::zero(),
+            price_improvement: HashMap::new(),
+            budget_refunds: Vec::new(),
+            rejected_orders: Vec::new(),
+            published_best_bid: (U256::zero(), U256::zero()),
+            published_best_ask: (U256::zero(), U256::zero()),
+            maker_volume: HashMap::new(),
+            taker_volume: HashMap::new(),
+            fee_rate_bps: 0,
             protocol_fee_quote: U256::zero(),
             base_token_id,
             quote_token_id,
@@ -103,6 +262,150 @@ impl State {
         }
     }
 
+    pub fn ensure_not_paused(&self) {
+        if self.paused {
+            panic!("Paused");
+        }
+    }
+
+    pub fn ensure_order_size(&self, amount_base: U256) {
+        if !self.max_order_base.is_zero() && amount_base > self.max_order_base {
+            panic!("OrderTooLarge");
+        }
+    }
+
+    /// Keeps `order` off the book once it holds more than `max_resting_orders`. An order
+    /// that would only rest is rejected; one that already executed in part keeps its fills
+    /// and has its resting remainder cancelled and refunded, like an IOC. Orders that fully
+    /// execute never reach the book and pass.
+    pub fn ensure_resting_capacity(&mut self, order: &IncomingOrder) {
+        if self.max_resting_orders == 0 {
+            return;
+        }
+        let Some(view) = self.book.peek_order(order.id) else {
+            return;
+        };
+        let (_, bid_orders, _, ask_orders) = self.book.stats();
+        if bid_orders.saturating_add(ask_orders) <= self.max_resting_orders {
+            return;
+        }
+        if view.remaining_base == order.amount_base {
+            panic!("TooManyRestingOrders");
+        }
+
+        let maker = self.book.cancel(view.id).expect("Order not found");
+        self.order_created.remove(&maker.id);
+        match maker.side {
+            Side::Sell => self.unlock(maker.owner, Asset::Base, maker.remaining_base),
+            Side::Buy => self.unlock(maker.owner, Asset::Quote, maker.reserved_quote),
+        }
+    }
+
+    /// Price for a peg-to-best order: one tick (the smallest price step) better than the
+    /// best on `side`, or level with it when that would cross the other side.
+    pub fn peg_price(&self, side: Side) -> U256 {
+        let Some(best) = self.book.best_price(side) else {
+            panic!("NoBestToPeg");
+        };
+        let opposite = self.book.best_price(side.opposite());
+        let (improved, crosses) = match side {
+            Side::Buy => {
+                let p = best.saturating_add(U256::one());
+                (p, opposite.is_some_and(|ask| p >= ask))
+            }
+            Side::Sell => {
+                let p = best.saturating_sub(U256::one());
+                (p, p.is_zero() || opposite.is_some_and(|bid| p <= bid))
+            }
+        };
+        if crosses {
+            best
+        } else {
+            improved
+        }
+    }
+
+    /// Records a cancel by `who` at block `now`, rejecting it if the previous one
+    /// was less than `cancel_cooldown_blocks` ago.
+    pub fn throttle_cancel(&mut self, who: ActorId, now: u32) {
+        if self.cancel_cooldown_blocks == 0 {
+            return;
+        }
+        if let Some(last) = self.last_cancel_block.get(&who) {
+            if now < last.saturating_add(self.cancel_cooldown_blocks) {
+                panic!("CancelCooldown");
+            }
+        }
+        self.last_cancel_block.insert(who, now);
+    }
+
+    /// Rejects cancelling `order_id` before it has rested `min_order_lifetime_blocks`.
+    /// The admin is exempt so it can still clear the book in an emergency.
+    pub fn ensure_order_aged(&self, who: ActorId, order_id: OrderId, now: u32) {
+        if !self.order_aged(who, order_id, now) {
+            panic!("OrderTooYoung");
+        }
+    }
+
+    /// Whether `who` may cancel `order_id` at block `now` under the minimum order lifetime.
+    pub fn order_aged(&self, who: ActorId, order_id: OrderId, now: u32) -> bool {
+        if self.min_order_lifetime_blocks == 0 || self.admin == Some(who) {
+            return true;
+        }
+        self.order_created.get(&order_id).is_none_or(|created| {
+            now >= created.block.saturating_add(self.min_order_lifetime_blocks)
+        })
+    }
+
+    pub fn stp_mode(&self, who: ActorId) -> StpMode {
+        self.stp_modes.get(&who).copied().unwrap_or_default()
+    }
+
+    /// Tracks when a taker's remainder started resting and forgets makers it filled.
+    pub fn record_resting(
+        &mut self,
+        order: &IncomingOrder,
+        rep: &ExecutionReport,
+        now: OrderCreated,
+    ) {
+        for tr in &rep.trades {
+            if self.book.peek_order(tr.maker_order_id).is_none() {
+                self.order_created.remove(&tr.maker_order_id);
+            }
+        }
+        for released in rep.stp_released.iter().filter(|r| r.removed) {
+            self.order_created.remove(&released.maker_order_id);
+        }
+        for expired in &rep.expired {
+            self.order_created.remove(&expired.maker_order_id);
+        }
+        if matches!(rep.completion, Completion::Placed { .. }) {
+            self.order_created.insert(order.id, now);
+        }
+    }
+
+    /// Configured limits with the clock set to `now` for expiry checks.
+    pub fn engine_limits(&self, now: u64) -> EngineLimits {
+        EngineLimits { now, ..self.limits }
+    }
+
+    pub fn order_view(&self, o: MakerView) -> OrderView {
+        OrderView {
+            id: o.id,
+            owner: o.owner,
+            side: o.side,
+            price: o.price.low_u128(),
+            remaining_base: o.remaining_base.low_u128(),
+            reserved_quote: o.reserved_quote.low_u128(),
+            created_at: self
+                .order_created
+                .get(&o.id)
+                .map(|created| created.block)
+                .unwrap_or_default(),
+            expires_at: o.expires_at,
+        }
+    }
+
     pub fn alloc_order_id(&mut self) -> OrderId {
         let id = self.next_order_id;
         self.next_order_id = self.next_order_id.saturating_add(1);
@@ -114,6 +417,17 @@ impl State {
     }
 
     pub fn append_executed_trades(&mut self, trades: &[Trade]) {
+        if let Some(last) = trades.last() {
+            self.last_trade_price = last.price;
+        }
+        self.total_trades = self.total_trades.saturating_add(trades.len() as u64);
+        for tr in trades {
+            let maker = self.maker_volume.entry(tr.maker).or_default();
+            *maker = maker.saturating_add(tr.amount_quote);
+            let taker = self.taker_volume.entry(tr.taker).or_default();
+            *taker = taker.saturating_add(tr.amount_quote);
+        }
+
         if trades.len() > MAX_RECORDED_TRADES_PER_EXECUTION {
             return;
         }
@@ -122,7 +436,7 @@ impl State {
             let seq = self.next_trade_seq;
             self.next_trade_seq = self.next_trade_seq.saturating_add(1);
 
-            self.executed_trades.push(ExecutedTrade {
+            self.executed_trades.push_back(ExecutedTrade {
                 seq,
                 maker_order_id: tr.maker_order_id,
                 taker_order_id: tr.taker_order_id,
@@ -134,7 +448,7 @@ impl State {
             });
 
             if self.executed_trades.len() > MAX_EXECUTED_TRADE_HISTORY {
-                let _ = self.executed_trades.remove(0);
+                let _ = self.executed_trades.pop_front();
             }
         }
     }
@@ -161,6 +475,30 @@ impl State {
         }
     }
 
+    /// Side of the market `token` is held on; any other token is rejected.
+    pub fn token_asset(&self, token: TokenId) -> Asset {
+        if token == self.base_token_id {
+            Asset::Base
+        } else if token == self.quote_token_id {
+            Asset::Quote
+        } else {
+            panic!("Invalid token");
+        }
+    }
+
+    /// Asset a deposit of `token` credits, provided `caller` is the vault bound to it.
+    pub fn vault_asset(&self, caller: ActorId, token: TokenId) -> Result<Asset, DepositError> {
+        let asset = self.token_asset(token);
+        let vault = match asset {
+            Asset::Base => self.base_vault_id,
+            Asset::Quote => self.quote_vault_id,
+        };
+        if caller != vault {
+            return Err(DepositError::UnauthorizedCaller);
+        }
+        Ok(asset)
+    }
+
     pub fn deposit(&mut self, who: ActorId, asset: Asset, amount: U256) {
         self.unlock(who, asset, amount);
     }
@@ -169,24 +507,146 @@ impl State {
         self.lock(who, asset, amount);
     }
 
-    pub fn lock_taker_funds(&mut self, order: &IncomingOrder) -> (U256, U256) {
-        match order.side {
-            Side::Sell => {
-                self.lock(order.owner, Asset::Base, order.amount_base);
-                (order.amount_base, U256::zero())
+    /// Same-price size reduction of a resting order.
+    ///
+    /// Adjusts the maker in place and unlocks only the freed part of its reservation,
+    /// so the order keeps its queue position. Returns the unlocked amount
+    /// (base for sells, quote for buys).
+    pub fn reduce_resting(&mut self, maker: &MakerView, new_remaining_base: U256) -> U256 {
+        if new_remaining_base.is_zero() || new_remaining_base >= maker.remaining_base {
+            panic!("InvalidReduceAmount");
+        }
+
+        let (asset, new_reserved_quote, refund) = match maker.side {
+            Side::Sell => (
+                Asset::Base,
+                U256::zero(),
+                maker.remaining_base - new_remaining_base,
+            ),
+            Side::Buy => {
+                let keep = matching_engine::calc_quote_ceil_scaled(
+                    new_remaining_base,
+                    maker.price,
+                    self.book.price_exponent(),
+                )
+                .expect("Math error");
+                let refund = maker
+                    .reserved_quote
+                    .checked_sub(keep)
+                    .expect("reserve underflow");
+                (Asset::Quote, keep, refund)
             }
+        };
+
+        if !self
+            .book
+            .reduce(maker.id, new_remaining_base, new_reserved_quote)
+        {
+            panic!("Order not found");
+        }
+        self.unlock(maker.owner, asset, refund);
+        refund
+    }
+
+    /// Asset and amount a pending trigger keeps locked.
+    pub fn trigger_funds(trigger: &TriggerOrder) -> (Asset, U256) {
+        match trigger.side {
+            Side::Sell => (Asset::Base, trigger.amount_base),
+            Side::Buy => (Asset::Quote, trigger.max_quote),
+        }
+    }
+
+    /// Parks a market-if-touched order, locking what its market conversion will need.
+    pub fn place_trigger(&mut self, trigger: TriggerOrder) {
+        let (asset, amount) = Self::trigger_funds(&trigger);
+        self.lock(trigger.owner, asset, amount);
+        self.triggers.insert(trigger.id, trigger);
+    }
+
+    /// Removes a pending trigger and releases its locked funds.
+    pub fn take_trigger(&mut self, id: OrderId) -> Option<TriggerOrder> {
+        let trigger = self.triggers.remove(&id)?;
+        let (asset, amount) = Self::trigger_funds(&trigger);
+        self.unlock(trigger.owner, asset, amount);
+        Some(trigger)
+    }
+
+    /// Ids of pending triggers touched by the last trade price, oldest first.
+    pub fn touched_triggers(&self, max: usize) -> Vec<OrderId> {
+        if self.last_trade_price.is_zero() {
+            return Vec::new();
+        }
+        self.triggers
+            .values()
+            .filter(|t| t.is_touched(self.last_trade_price))
+            .take(max)
+            .map(|t| t.id)
+            .collect()
+    }
+
+    /// Asset and amount `order` locks when it is submitted: base for sells, `max_quote`
+    /// for market buys and the ceiled limit-price quote for every other buy.
+    pub fn taker_funds(&self, order: &IncomingOrder) -> (Asset, U256) {
+        match order.side {
+            Side::Sell => (Asset::Base, order.amount_base),
             Side::Buy => {
                 let lock_quote = match order.kind {
                     OrderKind::Market => order.max_quote,
-                    _ => matching_engine::calc_quote_ceil(order.amount_base, order.limit_price)
-                        .expect("Math error"),
+                    _ => matching_engine::calc_quote_ceil_scaled(
+                        order.amount_base,
+                        order.limit_price,
+                        self.book.price_exponent(),
+                    )
+                    .expect("Math error"),
                 };
-                self.lock(order.owner, Asset::Quote, lock_quote);
-                (U256::zero(), lock_quote)
+                (Asset::Quote, lock_quote)
             }
         }
     }
 
+    pub fn lock_taker_funds(&mut self, order: &IncomingOrder) -> (U256, U256) {
+        let (asset, amount) = self.taker_funds(order);
+        self.lock(order.owner, asset, amount);
+        match asset {
+            Asset::Base => (amount, U256::zero()),
+            Asset::Quote => (U256::zero(), amount),
+        }
+    }
+
+    /// Records how much quote the taker saved against its own limit price.
+    fn record_price_improvement(&mut self, order: &IncomingOrder, trades: &[Trade]) {
+        if order.kind == OrderKind::Market {
+            return;
+        }
+        let exponent = self.book.price_exponent();
+        let mut saved = U256::zero();
+        for tr in trades {
+            let at_limit = matching_engine::calc_quote_floor_scaled(
+                tr.amount_base,
+                order.limit_price,
+                exponent,
+            )
+            .expect("Math error");
+            let delta = match order.side {
+                Side::Buy => at_limit.saturating_sub(tr.amount_quote),
+                Side::Sell => tr.amount_quote.saturating_sub(at_limit),
+            };
+            saved = saved.saturating_add(delta);
+        }
+        if saved.is_zero() {
+            return;
+        }
+        let total = self.price_improvement.entry(order.owner).or_default();
+        *total = total.saturating_add(saved);
+    }
+
+    fn trade_fee(&self, amount_quote: U256) -> U256 {
+        amount_quote
+            .checked_mul(U256::from(self.fee_rate_bps))
+            .expect("fee mul overflow")
+            / U256::from(10_000u64)
+    }
+
     pub fn settle_execution(
         &mut self,
         order: &IncomingOrder,
@@ -196,33 +656,31 @@ impl State {
     ) {
         let taker_side = order.side;
         let maker_side = order.side.opposite();
+        self.record_price_improvement(order, &rep.trades);
 
-        let mut taker_spent_quote = U256::zero();
-        let mut taker_spent_base = U256::zero();
+        let (taker_spent_base, taker_spent_quote) = match taker_side {
+            Side::Buy => (U256::zero(), rep.spent_quote),
+            Side::Sell => (rep.spent_base, U256::zero()),
+        };
         // 1) Apply trades: credit balances
         for tr in &rep.trades {
-            match taker_side {
-                Side::Buy => {
-                    taker_spent_quote = taker_spent_quote
-                        .checked_add(tr.amount_quote)
-                        .expect("quote add overflow");
-                }
-                Side::Sell => {
-                    taker_spent_base = taker_spent_base
-                        .checked_add(tr.amount_base)
-                        .expect("quote add overflow");
-                }
-            }
+            // the side receiving quote pays the protocol fee out of it
+            let fee = self.trade_fee(tr.amount_quote);
+            self.protocol_fee_quote = self
+                .protocol_fee_quote
+                .checked_add(fee)
+                .expect("fee add overflow");
+            let net_quote = tr.amount_quote - fee;
 
             // credit taker receive
             match taker_side {
                 Side::Buy => self.unlock(tr.taker, Asset::Base, tr.amount_base),
-                Side::Sell => self.unlock(tr.taker, Asset::Quote, tr.amount_quote),
+                Side::Sell => self.unlock(tr.taker, Asset::Quote, net_quote),
             }
 
             // credit maker receive
             match maker_side {
-                Side::Sell => self.unlock(tr.maker, Asset::Quote, tr.amount_quote),
+                Side::Sell => self.unlock(tr.maker, Asset::Quote, net_quote),
                 Side::Buy => self.unlock(tr.maker, Asset::Base, tr.amount_base),
             }
         }
@@ -233,15 +691,20 @@ impl State {
                 // FOK fail => orderbook wasn't mutated => unlock
                 self.unlock(order.owner, Asset::Base, locked_base);
                 self.unlock(order.owner, Asset::Quote, locked_quote);
+                self.rejected_orders.push((order.side, order.amount_base));
             }
 
-            Completion::Cancelled { remaining_base } => match taker_side {
+            Completion::Cancelled { .. } => match taker_side {
                 Side::Sell => {
-                    // SELL: unlock remaining base
-                    self.unlock(order.owner, Asset::Base, remaining_base);
+                    // SELL: unlock whatever wasn't sold (STP may have shrunk the order)
+                    let refund = locked_base
+                        .checked_sub(taker_spent_base)
+                        .expect("refund underflow");
+                    self.unlock(order.owner, Asset::Base, refund);
                 }
                 Side::Buy => {
-                    // BUY: refund = locked_quote - spent_quote
+                    // BUY: refund = locked_quote - spent_quote; for IOC and reduce-only limits
+                    // that is the ceiled limit lock, so the fill dust comes back too
                     let refund = locked_quote
                         .checked_sub(taker_spent_quote)
                         .expect("refund underflow");
@@ -250,23 +713,39 @@ impl State {
             },
 
             Completion::Filled => {
+                // SELL: base only stays locked when DecrementBoth cut the order
+                if taker_side == Side::Sell {
+                    let extra = locked_base
+                        .checked_sub(taker_spent_base)
+                        .expect("extra underflow");
+                    self.unlock(order.owner, Asset::Base, extra);
+                }
                 // BUY: dust because ceil lock vs floor fills
                 if taker_side == Side::Buy {
                     let extra = locked_quote
                         .checked_sub(taker_spent_quote)
                         .expect("extra underflow");
                     self.unlock(order.owner, Asset::Quote, extra);
+                    if !extra.is_zero() {
+                        self.budget_refunds.push((order.owner, extra));
+                    }
                 }
             }
 
             Completion::Placed {
-                remaining_base: _,
+                remaining_base,
                 remaining_quote,
+                ..
             } => match taker_side {
                 Side::Sell => {
                     // Remaining base is now a resting SELL order => stays locked.
                     // Sold base is already deducted by the original lock.
-                    // => refund nothing
+                    // => refund only what DecrementBoth cut
+                    let used = taker_spent_base
+                        .checked_add(remaining_base)
+                        .expect("used overflow");
+                    let extra = locked_base.checked_sub(used).expect("extra underflow");
+                    self.unlock(order.owner, Asset::Base, extra);
                 }
                 Side::Buy => {
                     // Remaining quote stays reserved in the resting BUY order.
@@ -279,5 +758,72 @@ impl State {
                 }
             },
         }
+
+        // 3) Own makers cancelled or shrunk by self-trade prevention free their escrow
+        for released in &rep.stp_released {
+            self.unlock(order.owner, Asset::Base, released.released_base);
+            self.unlock(order.owner, Asset::Quote, released.released_quote);
+        }
+
+        // 4) Makers dropped as expired hand their escrow back to their owners
+        for expired in &rep.expired {
+            self.unlock(expired.owner, Asset::Base, expired.released_base);
+            self.unlock(expired.owner, Asset::Quote, expired.released_quote);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(n: u64) -> Trade {
+        Trade {
+            maker_order_id: n,
+            taker_order_id: n + 1,
+            maker: ActorId::from(1u64),
+            taker: ActorId::from(2u64),
+            price: U256::from(100u64),
+            amount_base: U256::from(1u64),
+            amount_quote: U256::from(100u64),
+        }
+    }
+
+    #[test]
+    fn trade_history_evicts_oldest_first() {
+        let mut st = State::new(
+            ActorId::from(9u64),
+            ActorId::from(10u64),
+            ActorId::from(11u64),
+            [1u8; 20],
+            [2u8; 20],
+            100,
+            100,
+        );
+
+        let extra = 40;
+        let total = (MAX_EXECUTED_TRADE_HISTORY + extra) as u64;
+        let mut n = 0;
+        while n < total {
+            let batch: Vec<Trade> = (n..(n + 8).min(total)).map(trade).collect();
+            st.append_executed_trades(&batch);
+            n += batch.len() as u64;
+        }
+
+        assert_eq!(st.total_trades, total);
+        assert_eq!(st.executed_trades.len(), MAX_EXECUTED_TRADE_HISTORY);
+        // Seqs start at 1, so the first `extra` fills are the ones dropped.
+        assert_eq!(st.executed_trades.front().unwrap().seq, extra as u64 + 1);
+        assert_eq!(
+            st.executed_trades.front().unwrap().maker_order_id,
+            extra as u64
+        );
+        assert_eq!(st.executed_trades.back().unwrap().seq, total);
+        assert_eq!(st.executed_trades.back().unwrap().maker_order_id, total - 1);
+        assert!(st
+            .executed_trades
+            .iter()
+            .zip(st.executed_trades.iter().skip(1))
+            .all(|(a, b)| b.seq == a.seq + 1));
     }
 }
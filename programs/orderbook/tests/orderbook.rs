@@ -1,9 +1,10 @@
-use clob_common::TokenId;
+use clob_common::{actor_to_eth, TokenId};
 use orderbook_client::{
-    orderbook::*, Orderbook as OrderbookClient, OrderbookCtors, OrderbookProgram,
+    orderbook::*, OrderType, Orderbook as OrderbookClient, OrderbookCtors, OrderbookProgram, Side,
+    StpMode,
 };
 
-use sails_rs::{client::*, gtest::*};
+use sails_rs::{client::*, futures::StreamExt, gtest::*};
 use sails_rs::{prelude::*, ActorId};
 pub(crate) const ORDERBOOK_WASM: &str = "../../target/wasm32-gear/release/orderbook.opt.wasm";
 
@@ -85,6 +86,16 @@ async fn setup_orderbook(
     max_trades: u32,
     max_preview_scans: u32,
 ) -> Actor<OrderbookProgram, sails_rs::client::GtestEnv> {
+    setup_orderbook_with_env(max_trades, max_preview_scans)
+        .await
+        .1
+}
+
+/// Like `setup_orderbook`, but also returns the env for listening to events.
+async fn setup_orderbook_with_env(
+    max_trades: u32,
+    max_preview_scans: u32,
+) -> (GtestEnv, Actor<OrderbookProgram, GtestEnv>) {
     let system = System::new();
     system.init_logger();
     system.mint_to(ADMIN_ID, 100_000_000_000_000_000);
@@ -98,7 +109,8 @@ async fn setup_orderbook(
     // Deploy OrderBook passing the vault_id
     let program_code_id = env.system().submit_code_file(ORDERBOOK_WASM);
 
-    env.deploy::<orderbook_client::OrderbookProgram>(program_code_id, b"salt".to_vec())
+    let program = env
+        .deploy::<orderbook_client::OrderbookProgram>(program_code_id, b"salt".to_vec())
         .create(
             vault(),
             vault(),
@@ -108,7 +120,8 @@ async fn setup_orderbook(
             max_preview_scans,
         )
         .await
-        .unwrap()
+        .unwrap();
+    (env, program)
 }
 
 async fn assert_balance(
@@ -138,7 +151,11 @@ async fn market_buy_strict_partial_fill_refunds_unused_budget() {
         .unwrap();
     let ask_id = c
         .submit_order(
-            /*side=*/ 1, /*kind=*/ 0, price, ask_amount, /*max_quote=*/ 0,
+            Side::Sell,
+            OrderType::Limit,
+            price,
+            ask_amount,
+            /*max_quote=*/ 0,
         )
         .with_actor_id(seller())
         .await
@@ -154,7 +171,10 @@ async fn market_buy_strict_partial_fill_refunds_unused_budget() {
     let budget = spent + usdt_micro(100); // extra budget that must be refunded
 
     c.submit_order(
-        /*side=*/ 0, /*kind=*/ 1, /*limit_price=*/ 0, buy_amount,
+        Side::Buy,
+        OrderType::Market,
+        /*limit_price=*/ 0,
+        buy_amount,
         /*max_quote=*/ budget,
     )
     .with_actor_id(buyer())
@@ -170,16 +190,17 @@ async fn market_buy_strict_partial_fill_refunds_unused_budget() {
     assert_eq!(c.best_ask_price().await.unwrap(), price);
 
     // Order #1 is the original ask; it must have remaining_base = 0.1 ETH
-    let (found, id, owner, side_io, p, remaining_base, reserved_quote) =
-        c.order_by_id(ask_id).await.unwrap();
-
-    assert!(found);
-    assert_eq!(id, ask_id);
-    assert_eq!(owner, seller());
-    assert_eq!(side_io, 1); // SELL
-    assert_eq!(p, price);
-    assert_eq!(remaining_base, ask_amount - buy_amount);
-    assert_eq!(reserved_quote, 0);
+    let order = c
+        .order_by_id(ask_id)
+        .await
+        .unwrap()
+        .expect("order must rest");
+    assert_eq!(order.id, ask_id);
+    assert_eq!(order.owner, seller());
+    assert_eq!(order.side, Side::Sell);
+    assert_eq!(order.price, price);
+    assert_eq!(order.remaining_base, ask_amount - buy_amount);
+    assert_eq!(order.reserved_quote, 0);
 }
 
 #[tokio::test]
@@ -197,7 +218,7 @@ async fn market_buy_strict_budget_exceeded_reverts_without_state_change() {
         .await
         .unwrap();
     let ask_id = c
-        .submit_order(1, 0, price, ask_amount, 0)
+        .submit_order(Side::Sell, OrderType::Limit, price, ask_amount, 0)
         .with_actor_id(seller())
         .await
         .unwrap();
@@ -213,7 +234,13 @@ async fn market_buy_strict_budget_exceeded_reverts_without_state_change() {
     let too_small_budget = spent - 1;
 
     let res = c
-        .submit_order(0, 1, 0, buy_amount, too_small_budget)
+        .submit_order(
+            Side::Buy,
+            OrderType::Market,
+            0,
+            buy_amount,
+            too_small_budget,
+        )
         .with_actor_id(buyer())
         .await;
 
@@ -227,10 +254,12 @@ async fn market_buy_strict_budget_exceeded_reverts_without_state_change() {
     assert_eq!(c.best_ask_price().await.unwrap(), price);
 
     // Ask order must still be intact
-    let (found, _id, _owner, _side_io, _p, remaining_base, _rq) =
-        c.order_by_id(ask_id).await.unwrap();
-    assert!(found);
-    assert_eq!(remaining_base, ask_amount);
+    let order = c
+        .order_by_id(ask_id)
+        .await
+        .unwrap()
+        .expect("order must rest");
+    assert_eq!(order.remaining_base, ask_amount);
 }
 
 #[tokio::test]
@@ -248,7 +277,7 @@ async fn market_sell_matches_bid_and_decrements_reserved_quote() {
         .await
         .unwrap();
     let bid_id = c
-        .submit_order(0, 0, price, bid_amount, 0)
+        .submit_order(Side::Buy, OrderType::Limit, price, bid_amount, 0)
         .with_actor_id(buyer())
         .await
         .unwrap();
@@ -264,8 +293,8 @@ async fn market_sell_matches_bid_and_decrements_reserved_quote() {
     let got = quote_floor_atoms(sell_amount, price);
 
     c.submit_order(
-        /*side=*/ 1,
-        /*kind=*/ 1,
+        Side::Sell,
+        OrderType::Market,
         /*limit_price=*/ 0,
         sell_amount,
         /*max_quote=*/ 0,
@@ -281,12 +310,14 @@ async fn market_sell_matches_bid_and_decrements_reserved_quote() {
     assert_balance(&program, buyer(), sell_amount, usdt_micro(10_000) - locked).await;
 
     // Bid order #1 must be partially filled: remaining_base decreased, reserved_quote decreased by `got`
-    let (found, _id, _owner, side_io, _p, remaining_base, reserved_quote) =
-        c.order_by_id(bid_id).await.unwrap();
-    assert!(found);
-    assert_eq!(side_io, 0); // BUY
-    assert_eq!(remaining_base, bid_amount - sell_amount);
-    assert_eq!(reserved_quote, locked - got);
+    let order = c
+        .order_by_id(bid_id)
+        .await
+        .unwrap()
+        .expect("order must rest");
+    assert_eq!(order.side, Side::Buy);
+    assert_eq!(order.remaining_base, bid_amount - sell_amount);
+    assert_eq!(order.reserved_quote, locked - got);
 
     // Best bid stays because order still has remaining_base > 0
     assert_eq!(c.best_bid_price().await.unwrap(), price);
@@ -306,7 +337,7 @@ async fn ioc_buy_partial_fill_refunds_remainder_and_does_not_place_resting() {
         .with_actor_id(vault())
         .await
         .unwrap();
-    c.submit_order(1, 0, price, ask_amount, 0)
+    c.submit_order(Side::Sell, OrderType::Limit, price, ask_amount, 0)
         .with_actor_id(seller())
         .await
         .unwrap();
@@ -320,7 +351,10 @@ async fn ioc_buy_partial_fill_refunds_remainder_and_does_not_place_resting() {
     let spent = quote_floor_atoms(ask_amount, price);
 
     c.submit_order(
-        /*side=*/ 0, /*kind=*/ 3, /*limit_price=*/ price, buy_amount,
+        Side::Buy,
+        OrderType::ImmediateOrCancel,
+        /*limit_price=*/ price,
+        buy_amount,
         /*max_quote=*/ 0,
     )
     .with_actor_id(buyer())
@@ -340,7 +374,7 @@ async fn ioc_buy_partial_fill_refunds_remainder_and_does_not_place_resting() {
     assert_eq!(c.best_bid_price().await.unwrap(), 0);
 
     // Ask order #1 should be removed
-    let (found, ..) = c.order_by_id(1).await.unwrap();
+    let found = c.order_by_id(1).await.unwrap().is_some();
     assert!(!found);
 }
 #[tokio::test]
@@ -359,7 +393,11 @@ async fn limit_buy_places_and_reserves_quote_ceil() {
     // Place limit bid 0.5 ETH @ 1900
     let bid_id = c
         .submit_order(
-            /*side=*/ 0, /*kind=*/ 0, price, bid_amount, /*max_quote=*/ 0,
+            Side::Buy,
+            OrderType::Limit,
+            price,
+            bid_amount,
+            /*max_quote=*/ 0,
         )
         .with_actor_id(buyer())
         .await
@@ -373,15 +411,17 @@ async fn limit_buy_places_and_reserves_quote_ceil() {
     assert_eq!(c.best_bid_price().await.unwrap(), price);
 
     // Order #1 must exist with reserved_quote = `reserved`
-    let (found, id, owner, side_io, p, remaining_base, reserved_quote) =
-        c.order_by_id(bid_id).await.unwrap();
-    assert!(found);
-    assert_eq!(id, bid_id);
-    assert_eq!(owner, buyer());
-    assert_eq!(side_io, 0); // BUY
-    assert_eq!(p, price);
-    assert_eq!(remaining_base, bid_amount);
-    assert_eq!(reserved_quote, reserved);
+    let order = c
+        .order_by_id(bid_id)
+        .await
+        .unwrap()
+        .expect("order must rest");
+    assert_eq!(order.id, bid_id);
+    assert_eq!(order.owner, buyer());
+    assert_eq!(order.side, Side::Buy);
+    assert_eq!(order.price, price);
+    assert_eq!(order.remaining_base, bid_amount);
+    assert_eq!(order.reserved_quote, reserved);
 }
 
 #[tokio::test]
@@ -398,7 +438,7 @@ async fn fok_buy_rejects_without_mutating_book_or_balances() {
         .with_actor_id(vault())
         .await
         .unwrap();
-    c.submit_order(1, 0, price, ask_amount, 0)
+    c.submit_order(Side::Sell, OrderType::Limit, price, ask_amount, 0)
         .with_actor_id(seller())
         .await
         .unwrap();
@@ -410,7 +450,10 @@ async fn fok_buy_rejects_without_mutating_book_or_balances() {
         .unwrap();
 
     c.submit_order(
-        /*side=*/ 0, /*kind=*/ 2, /*limit_price=*/ price, buy_amount,
+        Side::Buy,
+        OrderType::FillOrKill,
+        /*limit_price=*/ price,
+        buy_amount,
         /*max_quote=*/ 0,
     )
     .with_actor_id(buyer())
@@ -424,9 +467,8 @@ async fn fok_buy_rejects_without_mutating_book_or_balances() {
     assert_balance(&program, seller(), eth_wei(1) - ask_amount, 0).await;
     assert_eq!(c.best_ask_price().await.unwrap(), price);
 
-    let (found, _id, _owner, _side, _p, remaining_base, _rq) = c.order_by_id(1).await.unwrap();
-    assert!(found);
-    assert_eq!(remaining_base, ask_amount);
+    let order = c.order_by_id(1).await.unwrap().expect("order must rest");
+    assert_eq!(order.remaining_base, ask_amount);
 }
 
 #[tokio::test]
@@ -447,8 +489,8 @@ async fn limit_sell_places_and_locks_base() {
 
     let ask_id = service_client
         .submit_order(
-            /*side=*/ 1,
-            /*kind=*/ 0,
+            Side::Sell,
+            OrderType::Limit,
             expected_price_fp,
             expected_remaining_base,
             /*max_quote=*/ 0,
@@ -464,15 +506,17 @@ async fn limit_sell_places_and_locks_base() {
         expected_price_fp
     );
 
-    let (found, id, owner, side_io, price, remaining_base, reserved_quote) =
-        service_client.order_by_id(ask_id).await.unwrap();
-    assert!(found);
-    assert_eq!(id, ask_id);
-    assert_eq!(owner, seller());
-    assert_eq!(side_io, 1); // SELL
-    assert_eq!(price, expected_price_fp);
-    assert_eq!(remaining_base, expected_remaining_base);
-    assert_eq!(reserved_quote, 0);
+    let order = service_client
+        .order_by_id(ask_id)
+        .await
+        .unwrap()
+        .expect("order must rest");
+    assert_eq!(order.id, ask_id);
+    assert_eq!(order.owner, seller());
+    assert_eq!(order.side, Side::Sell);
+    assert_eq!(order.price, expected_price_fp);
+    assert_eq!(order.remaining_base, expected_remaining_base);
+    assert_eq!(order.reserved_quote, 0);
 }
 
 #[tokio::test]
@@ -494,7 +538,7 @@ async fn market_buy_strict_fills_across_two_price_levels_best_to_worse() {
         .await
         .unwrap();
     let ask1_id = c
-        .submit_order(1, 0, price_1990, ask1, 0)
+        .submit_order(Side::Sell, OrderType::Limit, price_1990, ask1, 0)
         .with_actor_id(seller())
         .await
         .unwrap();
@@ -505,7 +549,7 @@ async fn market_buy_strict_fills_across_two_price_levels_best_to_worse() {
         .await
         .unwrap();
     let ask2_id = c
-        .submit_order(1, 0, price_2000, ask2, 0)
+        .submit_order(Side::Sell, OrderType::Limit, price_2000, ask2, 0)
         .with_actor_id(seller2())
         .await
         .unwrap();
@@ -522,7 +566,7 @@ async fn market_buy_strict_fills_across_two_price_levels_best_to_worse() {
 
     let budget = spent_total + usdt_micro(50); // unused budget must be refunded
 
-    c.submit_order(0, 1, 0, buy, budget)
+    c.submit_order(Side::Buy, OrderType::Market, 0, buy, budget)
         .with_actor_id(buyer())
         .await
         .unwrap();
@@ -535,18 +579,20 @@ async fn market_buy_strict_fills_across_two_price_levels_best_to_worse() {
     assert_balance(&program, seller2(), eth_wei(1) - ask2, spent2).await;
 
     // Ask1 fully consumed -> removed
-    let (found1, ..) = c.order_by_id(ask1_id).await.unwrap();
+    let found1 = c.order_by_id(ask1_id).await.unwrap().is_some();
     assert!(!found1);
 
     // Ask2 partially consumed -> remains with 0.05 ETH
-    let (found2, _id, owner, side_io, p, remaining_base, rq) =
-        c.order_by_id(ask2_id).await.unwrap();
-    assert!(found2);
-    assert_eq!(owner, seller2());
-    assert_eq!(side_io, 1); // SELL
-    assert_eq!(p, price_2000);
-    assert_eq!(remaining_base, ask2 - fill2);
-    assert_eq!(rq, 0);
+    let order = c
+        .order_by_id(ask2_id)
+        .await
+        .unwrap()
+        .expect("order must rest");
+    assert_eq!(order.owner, seller2());
+    assert_eq!(order.side, Side::Sell);
+    assert_eq!(order.price, price_2000);
+    assert_eq!(order.remaining_base, ask2 - fill2);
+    assert_eq!(order.reserved_quote, 0);
 
     // Best ask should now be the 2000 level
     assert_eq!(c.best_ask_price().await.unwrap(), price_2000);
@@ -570,7 +616,7 @@ async fn market_buy_strict_fifo_within_same_price_level() {
         .await
         .unwrap();
     let ask_a_id = c
-        .submit_order(1, 0, price, ask_a, 0)
+        .submit_order(Side::Sell, OrderType::Limit, price, ask_a, 0)
         .with_actor_id(seller())
         .await
         .unwrap();
@@ -581,7 +627,7 @@ async fn market_buy_strict_fifo_within_same_price_level() {
         .await
         .unwrap();
     let ask_b_id = c
-        .submit_order(1, 0, price, ask_b, 0)
+        .submit_order(Side::Sell, OrderType::Limit, price, ask_b, 0)
         .with_actor_id(seller2())
         .await
         .unwrap();
@@ -597,7 +643,7 @@ async fn market_buy_strict_fifo_within_same_price_level() {
     let spent_total = spent_a + spent_b;
     let budget = spent_total + usdt_micro(25);
 
-    c.submit_order(0, 1, 0, buy, budget)
+    c.submit_order(Side::Buy, OrderType::Market, 0, buy, budget)
         .with_actor_id(buyer())
         .await
         .unwrap();
@@ -606,17 +652,19 @@ async fn market_buy_strict_fifo_within_same_price_level() {
     assert_balance(&program, buyer(), buy, usdt_micro(10_000) - spent_total).await;
 
     // FIFO: first ask is gone, second ask remains with 0.15 ETH
-    let (found_a, ..) = c.order_by_id(ask_a_id).await.unwrap();
+    let found_a = c.order_by_id(ask_a_id).await.unwrap().is_some();
     assert!(!found_a);
 
-    let (found_b, _id, owner, side_io, p, remaining_base, rq) =
-        c.order_by_id(ask_b_id).await.unwrap();
-    assert!(found_b);
-    assert_eq!(owner, seller2());
-    assert_eq!(side_io, 1);
-    assert_eq!(p, price);
-    assert_eq!(remaining_base, ask_b - fill_b);
-    assert_eq!(rq, 0);
+    let order = c
+        .order_by_id(ask_b_id)
+        .await
+        .unwrap()
+        .expect("order must rest");
+    assert_eq!(order.owner, seller2());
+    assert_eq!(order.side, Side::Sell);
+    assert_eq!(order.price, price);
+    assert_eq!(order.remaining_base, ask_b - fill_b);
+    assert_eq!(order.reserved_quote, 0);
 
     // Sellers get credited quote
     assert_balance(&program, seller(), eth_wei(1) - ask_a, spent_a).await;
@@ -646,7 +694,7 @@ async fn market_sell_consumes_multiple_bids_best_to_worse_and_updates_reserved_q
         .await
         .unwrap();
     let bid1_id = c
-        .submit_order(0, 0, price_1900, bid1, 0)
+        .submit_order(Side::Buy, OrderType::Limit, price_1900, bid1, 0)
         .with_actor_id(buyer())
         .await
         .unwrap();
@@ -658,7 +706,7 @@ async fn market_sell_consumes_multiple_bids_best_to_worse_and_updates_reserved_q
         .await
         .unwrap();
     let bid2_id = c
-        .submit_order(0, 0, price_1890, bid2, 0)
+        .submit_order(Side::Buy, OrderType::Limit, price_1890, bid2, 0)
         .with_actor_id(buyer2())
         .await
         .unwrap();
@@ -674,7 +722,7 @@ async fn market_sell_consumes_multiple_bids_best_to_worse_and_updates_reserved_q
     let got2 = quote_floor_atoms(fill2, price_1890);
     let got_total = got1 + got2;
 
-    c.submit_order(1, 1, 0, sell, 0)
+    c.submit_order(Side::Sell, OrderType::Market, 0, sell, 0)
         .with_actor_id(seller())
         .await
         .unwrap();
@@ -689,18 +737,20 @@ async fn market_sell_consumes_multiple_bids_best_to_worse_and_updates_reserved_q
     assert_balance(&program, buyer2(), fill2, usdt_micro(10_000) - locked2).await;
 
     // Bid1 fully consumed -> removed
-    let (found1, ..) = c.order_by_id(bid1_id).await.unwrap();
+    let found1 = c.order_by_id(bid1_id).await.unwrap().is_some();
     assert!(!found1);
 
     // Bid2 remains with remaining_base and reserved_quote reduced by got2
-    let (found2, _id, owner, side_io, p, remaining_base, reserved_quote) =
-        c.order_by_id(bid2_id).await.unwrap();
-    assert!(found2);
-    assert_eq!(owner, buyer2());
-    assert_eq!(side_io, 0); // BUY
-    assert_eq!(p, price_1890);
-    assert_eq!(remaining_base, rem2);
-    assert_eq!(reserved_quote, locked2 - got2);
+    let order = c
+        .order_by_id(bid2_id)
+        .await
+        .unwrap()
+        .expect("order must rest");
+    assert_eq!(order.owner, buyer2());
+    assert_eq!(order.side, Side::Buy);
+    assert_eq!(order.price, price_1890);
+    assert_eq!(order.remaining_base, rem2);
+    assert_eq!(order.reserved_quote, locked2 - got2);
 
     // Best bid should now be 1890 (since 1900 was fully consumed)
     assert_eq!(c.best_bid_price().await.unwrap(), price_1890);
@@ -732,8 +782,8 @@ async fn limit_buy_partial_fill_across_two_asks_then_places_remainder_bid() {
         .unwrap();
     let ask1_id = c
         .submit_order(
-            /*side=*/ 1,
-            /*kind=*/ 0,
+            Side::Sell,
+            OrderType::Limit,
             ask_price_1950,
             ask1_amount,
             /*max_quote=*/ 0,
@@ -749,8 +799,8 @@ async fn limit_buy_partial_fill_across_two_asks_then_places_remainder_bid() {
         .unwrap();
     let ask2_id = c
         .submit_order(
-            /*side=*/ 1,
-            /*kind=*/ 0,
+            Side::Sell,
+            OrderType::Limit,
             ask_price_1990,
             ask2_amount,
             /*max_quote=*/ 0,
@@ -775,8 +825,8 @@ async fn limit_buy_partial_fill_across_two_asks_then_places_remainder_bid() {
     // Submit the limit order (should partially fill + place remainder)
     let bid_id = c
         .submit_order(
-            /*side=*/ 0,
-            /*kind=*/ 0,
+            Side::Buy,
+            OrderType::Limit,
             limit_price_2000,
             buy_amount,
             /*max_quote=*/ 0,
@@ -810,22 +860,25 @@ async fn limit_buy_partial_fill_across_two_asks_then_places_remainder_bid() {
     assert_eq!(c.best_bid_price().await.unwrap(), limit_price_2000);
 
     // ask1 removed
-    let (found1, ..) = c.order_by_id(ask1_id).await.unwrap();
+    let found1 = c.order_by_id(ask1_id).await.unwrap().is_some();
     assert!(!found1);
 
     // ask2 removed
-    let (found2, ..) = c.order_by_id(ask2_id).await.unwrap();
+    let found2 = c.order_by_id(ask2_id).await.unwrap().is_some();
     assert!(!found2);
 
     // bid exists with remaining_base = 0.6 ETH and reserved_quote = remaining_quote
-    let (found, id, owner, side_io, p, rem_base, reserved_q) = c.order_by_id(bid_id).await.unwrap();
-    assert!(found);
-    assert_eq!(id, bid_id);
-    assert_eq!(owner, buyer());
-    assert_eq!(side_io, 0); // BUY
-    assert_eq!(p, limit_price_2000);
-    assert_eq!(rem_base, remaining_base);
-    assert_eq!(reserved_q, locked_total - spent_total);
+    let order = c
+        .order_by_id(bid_id)
+        .await
+        .unwrap()
+        .expect("order must rest");
+    assert_eq!(order.id, bid_id);
+    assert_eq!(order.owner, buyer());
+    assert_eq!(order.side, Side::Buy);
+    assert_eq!(order.price, limit_price_2000);
+    assert_eq!(order.remaining_base, remaining_base);
+    assert_eq!(order.reserved_quote, locked_total - spent_total);
 }
 
 #[tokio::test]
@@ -851,9 +904,10 @@ async fn stress_1000_makers_one_taker_market_buy_strict_consumes_all() {
     for i in 0..1000u64 {
         let ask_id = c
             .submit_order(
-                /*side=*/ 1, // SELL
-                /*kind=*/ 0, // LIMIT
-                /*limit_price=*/ price, /*amount_base=*/ chunk_base,
+                Side::Sell,
+                OrderType::Limit,
+                /*limit_price=*/ price,
+                /*amount_base=*/ chunk_base,
                 /*max_quote=*/ 0,
             )
             .with_actor_id(seller())
@@ -885,9 +939,11 @@ async fn stress_1000_makers_one_taker_market_buy_strict_consumes_all() {
     assert!(budget <= initial_quote);
 
     c.submit_order(
-        /*side=*/ 0, // BUY
-        /*kind=*/ 1, // MARKET
-        /*limit_price=*/ 0, /*amount_base=*/ total_base, /*max_quote=*/ budget,
+        Side::Buy,
+        OrderType::Market,
+        /*limit_price=*/ 0,
+        /*amount_base=*/ total_base,
+        /*max_quote=*/ budget,
     )
     .with_actor_id(buyer())
     .await
@@ -915,7 +971,7 @@ async fn stress_1000_makers_one_taker_market_buy_strict_consumes_all() {
     assert_eq!(c.best_ask_price().await.unwrap(), 0);
 
     // The first ask must be removed.
-    let (found, ..) = c.order_by_id(first_ask_id).await.unwrap();
+    let found = c.order_by_id(first_ask_id).await.unwrap().is_some();
     assert!(!found);
 }
 
@@ -956,9 +1012,10 @@ async fn one_big_market_buy_matches_n_small_asks() {
     for i in 0..n {
         let ask_id = c
             .submit_order(
-                /*side=*/ 1, // SELL
-                /*kind=*/ 0, // LIMIT
-                /*limit_price=*/ price, /*amount_base=*/ chunk_base,
+                Side::Sell,
+                OrderType::Limit,
+                /*limit_price=*/ price,
+                /*amount_base=*/ chunk_base,
                 /*max_quote=*/ 0,
             )
             .with_actor_id(seller())
@@ -992,9 +1049,11 @@ async fn one_big_market_buy_matches_n_small_asks() {
         "Increase initial_quote or lower N_MATCHES"
     );
     c.submit_order(
-        /*side=*/ 0, // BUY
-        /*kind=*/ 1, // MARKET
-        /*limit_price=*/ 0, /*amount_base=*/ total_base, /*max_quote=*/ budget,
+        Side::Buy,
+        OrderType::Market,
+        /*limit_price=*/ 0,
+        /*amount_base=*/ total_base,
+        /*max_quote=*/ budget,
     )
     .with_actor_id(buyer())
     .await
@@ -1021,10 +1080,10 @@ async fn one_big_market_buy_matches_n_small_asks() {
     assert_eq!(c.best_ask_price().await.unwrap(), 0);
 
     // Spot check that first and last orders are removed.
-    let (found_first, ..) = c.order_by_id(first_id).await.unwrap();
+    let found_first = c.order_by_id(first_id).await.unwrap().is_some();
     assert!(!found_first);
 
-    let (found_last, ..) = c.order_by_id(last_id).await.unwrap();
+    let found_last = c.order_by_id(last_id).await.unwrap().is_some();
     assert!(!found_last);
 }
 
@@ -1043,7 +1102,7 @@ async fn cancel_limit_buy_unlocks_reserved_quote_and_removes_order() {
         .unwrap();
 
     let order_id = c
-        .submit_order(0, 0, price, amount, 0)
+        .submit_order(Side::Buy, OrderType::Limit, price, amount, 0)
         .with_actor_id(buyer())
         .await
         .unwrap();
@@ -1058,7 +1117,7 @@ async fn cancel_limit_buy_unlocks_reserved_quote_and_removes_order() {
 
     assert_balance(&program, buyer(), 0, initial_quote).await;
     assert_eq!(c.best_bid_price().await.unwrap(), 0);
-    let (found, ..) = c.order_by_id(order_id).await.unwrap();
+    let found = c.order_by_id(order_id).await.unwrap().is_some();
     assert!(!found);
 }
 
@@ -1077,7 +1136,7 @@ async fn cancel_limit_sell_unlocks_locked_base_and_removes_order() {
         .unwrap();
 
     let order_id = c
-        .submit_order(1, 0, price, amount, 0)
+        .submit_order(Side::Sell, OrderType::Limit, price, amount, 0)
         .with_actor_id(seller())
         .await
         .unwrap();
@@ -1091,7 +1150,7 @@ async fn cancel_limit_sell_unlocks_locked_base_and_removes_order() {
 
     assert_balance(&program, seller(), initial_base, 0).await;
     assert_eq!(c.best_ask_price().await.unwrap(), 0);
-    let (found, ..) = c.order_by_id(order_id).await.unwrap();
+    let found = c.order_by_id(order_id).await.unwrap().is_some();
     assert!(!found);
 }
 
@@ -1110,7 +1169,7 @@ async fn limit_buy_rejects_when_quote_balance_insufficient() {
         .unwrap();
 
     let res = c
-        .submit_order(0, 0, price, amount, 0)
+        .submit_order(Side::Buy, OrderType::Limit, price, amount, 0)
         .with_actor_id(buyer())
         .await;
     assert!(res.is_err(), "Expected insufficient quote balance");
@@ -1152,7 +1211,7 @@ async fn populate_demo_orders_rejects_when_market_not_empty() {
         .with_actor_id(vault())
         .await
         .unwrap();
-    c.submit_order(1, 0, price, eth_frac(1, 10), 0)
+    c.submit_order(Side::Sell, OrderType::Limit, price, eth_frac(1, 10), 0)
         .with_actor_id(seller())
         .await
         .unwrap();
@@ -1257,8 +1316,8 @@ async fn populate_demo_orders_creates_expected_top_of_book() {
     assert_eq!(c.best_bid_price().await.unwrap(), expected_best_bid);
     assert_eq!(c.best_ask_price().await.unwrap(), expected_best_ask);
 
-    let (found_first, ..) = c.order_by_id(first_id).await.unwrap();
-    let (found_last, ..) = c.order_by_id(last_id).await.unwrap();
+    let found_first = c.order_by_id(first_id).await.unwrap().is_some();
+    let found_last = c.order_by_id(last_id).await.unwrap().is_some();
     assert!(found_first);
     assert!(found_last);
 }
@@ -1292,8 +1351,8 @@ async fn populate_demo_orders_seeded_depth_executes_real_market_order() {
         .unwrap();
 
     c.submit_order(
-        /*side=*/ 0, // BUY
-        /*kind=*/ 1, // MARKET
+        Side::Buy,
+        OrderType::Market,
         /*limit_price=*/ 0, // ignored
         /*amount_base=*/ eth_frac(21, 100),
         /*max_quote=*/ initial_quote,
@@ -1312,7 +1371,7 @@ async fn populate_demo_orders_seeded_depth_executes_real_market_order() {
     assert!(buyer_base > 0);
     assert!(buyer_quote < initial_quote);
 
-    let (found_first, ..) = c.order_by_id(first_order_id).await.unwrap();
+    let found_first = c.order_by_id(first_order_id).await.unwrap().is_some();
     assert!(
         !found_first,
         "Expected first seeded ask to be fully consumed"
@@ -1334,7 +1393,7 @@ async fn executed_trades_history_supports_count_ordering_and_pagination() {
         .await
         .unwrap();
     let ask_id = c
-        .submit_order(1, 0, price, ask_amount, 0)
+        .submit_order(Side::Sell, OrderType::Limit, price, ask_amount, 0)
         .with_actor_id(seller())
         .await
         .unwrap();
@@ -1344,7 +1403,13 @@ async fn executed_trades_history_supports_count_ordering_and_pagination() {
         .await
         .unwrap();
     let first_taker_id = c
-        .submit_order(0, 1, 0, first_buy, usdt_micro(10_000))
+        .submit_order(
+            Side::Buy,
+            OrderType::Market,
+            0,
+            first_buy,
+            usdt_micro(10_000),
+        )
         .with_actor_id(buyer())
         .await
         .unwrap();
@@ -1354,7 +1419,13 @@ async fn executed_trades_history_supports_count_ordering_and_pagination() {
         .await
         .unwrap();
     let second_taker_id = c
-        .submit_order(0, 1, 0, second_buy, usdt_micro(10_000))
+        .submit_order(
+            Side::Buy,
+            OrderType::Market,
+            0,
+            second_buy,
+            usdt_micro(10_000),
+        )
         .with_actor_id(buyer2())
         .await
         .unwrap();
@@ -1404,6 +1475,109 @@ async fn executed_trades_history_supports_count_ordering_and_pagination() {
     assert_eq!(paged[0], trades[1]);
 }
 
+#[tokio::test]
+async fn recent_trades_resumes_from_a_seq_in_fill_order() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let amount = eth_frac(1, 10);
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    let mut asks = Vec::new();
+    for usdt in [2_000, 2_001, 2_002, 2_003] {
+        let id = c
+            .submit_order(
+                Side::Sell,
+                OrderType::Limit,
+                price_fp_usdt_per_eth(usdt),
+                amount,
+                0,
+            )
+            .with_actor_id(seller())
+            .await
+            .unwrap();
+        asks.push((id, price_fp_usdt_per_eth(usdt)));
+    }
+
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    let taker = c
+        .submit_order(
+            Side::Buy,
+            OrderType::Market,
+            0,
+            eth_frac(4, 10),
+            usdt_micro(10_000),
+        )
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+
+    // One fill per level, cheapest first, so seq n took asks[n - 1].
+    let expected = |seq: u64| {
+        let (maker, price) = asks[seq as usize - 1];
+        (
+            seq,
+            maker,
+            taker,
+            price,
+            amount,
+            quote_floor_atoms(amount, price),
+        )
+    };
+
+    let slice = c.recent_trades(2, 2).await.unwrap();
+    assert_eq!(slice, vec![expected(2), expected(3)]);
+
+    let rest = c.recent_trades(3, 10).await.unwrap();
+    assert_eq!(rest, vec![expected(3), expected(4)]);
+
+    assert!(c.recent_trades(5, 10).await.unwrap().is_empty());
+    assert!(c.recent_trades(1, 0).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn total_trades_counts_every_fill() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let price = price_fp_usdt_per_eth(2_000);
+
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.submit_order(Side::Sell, OrderType::Limit, price, eth_frac(3, 5), 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+
+    assert_eq!(c.total_trades().await.unwrap(), 0);
+
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    for _ in 0..3 {
+        c.submit_order(
+            Side::Buy,
+            OrderType::Market,
+            0,
+            eth_frac(1, 5),
+            usdt_micro(1_000),
+        )
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+    }
+
+    assert_eq!(c.total_trades().await.unwrap(), 3);
+}
+
 #[tokio::test]
 async fn executed_trades_history_ignores_non_executed_orders() {
     let program = setup_orderbook(1000, 1000).await;
@@ -1417,7 +1591,7 @@ async fn executed_trades_history_ignores_non_executed_orders() {
         .await
         .unwrap();
 
-    c.submit_order(0, 0, price, buy_amount, 0)
+    c.submit_order(Side::Buy, OrderType::Limit, price, buy_amount, 0)
         .with_actor_id(buyer())
         .await
         .unwrap();
@@ -1426,3 +1600,3002 @@ async fn executed_trades_history_ignores_non_executed_orders() {
     assert!(c.trades(0, 10).await.unwrap().is_empty());
     assert!(c.trades_reverse(0, 10).await.unwrap().is_empty());
 }
+
+#[tokio::test]
+async fn reduce_order_same_price_keeps_priority_and_refunds_delta() {
+    let (env, program) = setup_orderbook_with_env(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let price = price_fp_usdt_per_eth(2_000);
+    let ask_amount = eth_frac(1, 2); // 0.5 ETH
+    let reduced_amount = eth_frac(1, 5); // 0.2 ETH
+
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.deposit(seller2(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+
+    let first_ask = c
+        .submit_order(Side::Sell, OrderType::Limit, price, ask_amount, 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    let second_ask = c
+        .submit_order(Side::Sell, OrderType::Limit, price, ask_amount, 0)
+        .with_actor_id(seller2())
+        .await
+        .unwrap();
+
+    // Only the owner may reduce.
+    let res = c
+        .reduce_order(first_ask, reduced_amount)
+        .with_actor_id(seller2())
+        .await;
+    assert!(res.is_err(), "Expected non-owner reduce to fail");
+
+    let mut listener = env.listen(Some).await.unwrap();
+    let refunded = c
+        .reduce_order(first_ask, reduced_amount)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    assert_eq!(refunded, ask_amount - reduced_amount);
+    assert_balance(&program, seller(), eth_wei(1) - reduced_amount, 0).await;
+
+    // Shrinking the second ask the long way, by cancel and replace, costs two calls and
+    // publishes two top-of-book updates where the reduce published one.
+    c.cancel_order(second_ask)
+        .with_actor_id(seller2())
+        .await
+        .unwrap();
+    let second_ask = c
+        .submit_order(Side::Sell, OrderType::Limit, price, reduced_amount, 0)
+        .with_actor_id(seller2())
+        .await
+        .unwrap();
+
+    let mut published = Vec::new();
+    for _ in 0..6 {
+        let (_, payload) = listener.next().await.unwrap();
+        if let Ok((service, event, _seq, level_price, quantity)) =
+            <(String, String, u64, u128, u128)>::decode(&mut payload.as_slice())
+        {
+            if service == "Orderbook" {
+                assert_eq!(event, "BestAskChanged");
+                assert_eq!(level_price, price);
+                published.push(quantity);
+            }
+        }
+    }
+    // One update for the reduce, then two for the cancel and the replacement.
+    assert_eq!(
+        published,
+        vec![
+            reduced_amount + ask_amount,
+            reduced_amount,
+            reduced_amount * 2,
+        ]
+    );
+
+    let order = c
+        .order_by_id(first_ask)
+        .await
+        .unwrap()
+        .expect("order must rest");
+    assert_eq!(order.price, price);
+    assert_eq!(order.remaining_base, reduced_amount);
+
+    // The reduced order is still first in the FIFO queue.
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.submit_order(
+        Side::Buy,
+        OrderType::Market,
+        0,
+        reduced_amount,
+        usdt_micro(10_000),
+    )
+    .with_actor_id(buyer())
+    .await
+    .unwrap();
+
+    let trades = c.trades(0, 10).await.unwrap();
+    assert_eq!(trades.len(), 1);
+    assert_eq!(trades[0].1, first_ask);
+
+    let found = c.order_by_id(first_ask).await.unwrap().is_some();
+    assert!(!found);
+    let order = c
+        .order_by_id(second_ask)
+        .await
+        .unwrap()
+        .expect("order must rest");
+    assert_eq!(order.remaining_base, reduced_amount);
+}
+
+/// Cached level totals reported by `depth` must equal a fresh sum over the resting orders.
+async fn assert_depth_matches_orders(program: &Actor<OrderbookProgram, GtestEnv>) {
+    let (bids, asks) = program.orderbook().depth(100).await.unwrap();
+    let orders = program.orderbook().orders(0, 100).await.unwrap();
+    for (side, levels) in [(Side::Buy, bids), (Side::Sell, asks)] {
+        let mut expected: Vec<(u128, u128)> = Vec::new();
+        for (_, _, order_side, price, remaining_base, _) in &orders {
+            if *order_side != side {
+                continue;
+            }
+            match expected.iter_mut().find(|(p, _)| p == price) {
+                Some((_, total)) => *total += remaining_base,
+                None => expected.push((*price, *remaining_base)),
+            }
+        }
+        expected.sort_by_key(|(price, _)| *price);
+        if side == 0 {
+            expected.reverse();
+        }
+        assert_eq!(levels, expected, "side {side}");
+    }
+}
+
+#[tokio::test]
+async fn level_totals_stay_in_step_through_fills_reduces_and_cancels() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let price = price_fp_usdt_per_eth(2_000);
+    let next_price = price_fp_usdt_per_eth(2_100);
+    for user in [seller(), seller2()] {
+        c.deposit(user, BASE_TOKEN_ID, eth_wei(1))
+            .with_actor_id(vault())
+            .await
+            .unwrap();
+    }
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+
+    let first = c
+        .submit_order(Side::Sell, OrderType::Limit, price, eth_frac(1, 10), 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    let second = c
+        .submit_order(Side::Sell, OrderType::Limit, price, eth_frac(1, 10), 0)
+        .with_actor_id(seller2())
+        .await
+        .unwrap();
+    c.submit_order(Side::Sell, OrderType::Limit, next_price, eth_frac(1, 10), 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    c.submit_order(
+        Side::Buy,
+        OrderType::Limit,
+        price_fp_usdt_per_eth(1_900),
+        eth_frac(1, 10),
+        0,
+    )
+    .with_actor_id(buyer())
+    .await
+    .unwrap();
+    assert_depth_matches_orders(&program).await;
+
+    // Partial fill of the head maker.
+    c.submit_order(
+        Side::Buy,
+        OrderType::Market,
+        0,
+        eth_frac(1, 20),
+        usdt_micro(1_000),
+    )
+    .with_actor_id(buyer())
+    .await
+    .unwrap();
+    assert_depth_matches_orders(&program).await;
+
+    // Fill that removes the head and eats into the next maker.
+    c.submit_order(
+        Side::Buy,
+        OrderType::Market,
+        0,
+        eth_frac(1, 10),
+        usdt_micro(1_000),
+    )
+    .with_actor_id(buyer())
+    .await
+    .unwrap();
+    assert!(c.order_by_id(first).await.unwrap().is_none());
+    assert_depth_matches_orders(&program).await;
+
+    c.reduce_order(second, eth_frac(1, 50))
+        .with_actor_id(seller2())
+        .await
+        .unwrap();
+    assert_depth_matches_orders(&program).await;
+
+    c.cancel_order(second)
+        .with_actor_id(seller2())
+        .await
+        .unwrap();
+    assert_depth_matches_orders(&program).await;
+    let (_, asks) = c.depth(100).await.unwrap();
+    assert_eq!(asks, vec![(next_price, eth_frac(1, 10))]);
+}
+
+#[tokio::test]
+async fn depth_sums_each_level_and_orders_sides_from_best() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    for user in [seller(), seller2()] {
+        c.deposit(user, BASE_TOKEN_ID, eth_wei(1))
+            .with_actor_id(vault())
+            .await
+            .unwrap();
+    }
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+
+    // (user, side, usdt per eth, amount)
+    let placements = [
+        (seller(), Side::Sell, 2_000, eth_frac(1, 10)),
+        (seller2(), Side::Sell, 2_000, eth_frac(1, 10)),
+        (seller(), Side::Sell, 2_100, eth_frac(1, 20)),
+        (seller(), Side::Sell, 2_200, eth_frac(1, 100)),
+        (buyer(), Side::Buy, 1_900, eth_frac(1, 10)),
+        (buyer(), Side::Buy, 1_950, eth_frac(1, 50)),
+        (buyer(), Side::Buy, 1_950, eth_frac(1, 50)),
+    ];
+    for (user, side, usdt, amount) in placements {
+        c.submit_order(
+            side,
+            OrderType::Limit,
+            price_fp_usdt_per_eth(usdt),
+            amount,
+            0,
+        )
+        .with_actor_id(user)
+        .await
+        .unwrap();
+    }
+
+    let (bids, asks) = c.depth(2).await.unwrap();
+    assert_eq!(
+        bids,
+        vec![
+            (price_fp_usdt_per_eth(1_950), eth_frac(2, 50)),
+            (price_fp_usdt_per_eth(1_900), eth_frac(1, 10)),
+        ]
+    );
+    assert_eq!(
+        asks,
+        vec![
+            (price_fp_usdt_per_eth(2_000), eth_frac(2, 10)),
+            (price_fp_usdt_per_eth(2_100), eth_frac(1, 20)),
+        ]
+    );
+
+    let (bids, asks) = c.depth(10).await.unwrap();
+    assert_eq!(bids.len(), 2);
+    assert_eq!(asks.len(), 3);
+}
+
+#[tokio::test]
+async fn open_orders_of_lists_only_the_users_orders_in_book_order() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let amount = eth_frac(1, 100); // 0.01 ETH
+    for user in [buyer(), seller()] {
+        c.deposit(user, BASE_TOKEN_ID, eth_wei(1))
+            .with_actor_id(vault())
+            .await
+            .unwrap();
+        c.deposit(user, QUOTE_TOKEN_ID, usdt_micro(10_000))
+            .with_actor_id(vault())
+            .await
+            .unwrap();
+    }
+    let placements = [
+        (buyer(), Side::Buy, 1_900),
+        (seller(), Side::Buy, 1_950),
+        (buyer(), Side::Buy, 1_950),
+        (buyer(), Side::Buy, 1_950),
+        (buyer(), Side::Sell, 2_100),
+        (seller(), Side::Sell, 2_050),
+        (buyer(), Side::Sell, 2_050),
+    ];
+    let mut ids = Vec::new();
+    for (user, side, usdt) in placements {
+        let id = c
+            .submit_order(
+                side,
+                OrderType::Limit,
+                price_fp_usdt_per_eth(usdt),
+                amount,
+                0,
+            )
+            .with_actor_id(user)
+            .await
+            .unwrap();
+        ids.push(id);
+    }
+    let (low_bid, first_high_bid, second_high_bid) = (ids[0], ids[2], ids[3]);
+    let (far_ask, near_ask) = (ids[4], ids[6]);
+
+    let orders = c.open_orders_of(buyer()).await.unwrap();
+    let listed: Vec<_> = orders.iter().map(|o| o.0).collect();
+    assert_eq!(
+        listed,
+        vec![first_high_bid, second_high_bid, low_bid, near_ask, far_ask]
+    );
+
+    let high = price_fp_usdt_per_eth(1_950);
+    assert_eq!(
+        orders[0],
+        (
+            first_high_bid,
+            Side::Buy,
+            high,
+            amount,
+            quote_ceil_atoms(amount, high)
+        )
+    );
+    assert_eq!(
+        orders[3],
+        (
+            near_ask,
+            Side::Sell,
+            price_fp_usdt_per_eth(2_050),
+            amount,
+            0
+        )
+    );
+
+    let theirs = c.open_orders_of(seller()).await.unwrap();
+    assert_eq!(theirs.len(), 2);
+}
+
+#[tokio::test]
+async fn cancel_all_clears_only_the_callers_orders_in_pages() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let amount = eth_frac(1, 100); // 0.01 ETH
+    let quote_deposit = usdt_micro(10_000);
+    c.deposit(buyer(), QUOTE_TOKEN_ID, quote_deposit)
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+
+    for i in 0..5u128 {
+        c.submit_order(
+            Side::Buy,
+            OrderType::Limit,
+            price_fp_usdt_per_eth(1_900 + i),
+            amount,
+            0,
+        )
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+    }
+    for i in 0..3u128 {
+        c.submit_order(
+            Side::Sell,
+            OrderType::Limit,
+            price_fp_usdt_per_eth(2_100 + i),
+            amount,
+            0,
+        )
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    }
+
+    // Five bids cleared in pages of three; the last call finds nothing left.
+    assert_eq!(c.cancel_all(3).with_actor_id(buyer()).await.unwrap(), 3);
+    assert_eq!(c.cancel_all(3).with_actor_id(buyer()).await.unwrap(), 2);
+    assert_eq!(c.cancel_all(3).with_actor_id(buyer()).await.unwrap(), 0);
+
+    assert_balance(&program, buyer(), 0, quote_deposit).await;
+    assert_eq!(c.best_bid_price().await.unwrap(), 0);
+    // The other user's asks are left alone.
+    assert_eq!(
+        c.best_ask_price().await.unwrap(),
+        price_fp_usdt_per_eth(2_100)
+    );
+    assert_balance(&program, seller(), eth_wei(1) - 3 * amount, 0).await;
+}
+
+#[tokio::test]
+async fn cancel_orders_refunds_only_the_cancelled_bids() {
+    let (env, program) = setup_orderbook_with_env(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let bid_amount = eth_frac(1, 10); // 0.1 ETH
+    let quote_deposit = usdt_micro(10_000);
+    c.deposit(buyer(), QUOTE_TOKEN_ID, quote_deposit)
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+
+    let mut bids = Vec::new();
+    for usdt in [1_900, 1_950, 2_000] {
+        let id = c
+            .submit_order(
+                Side::Buy,
+                OrderType::Limit,
+                price_fp_usdt_per_eth(usdt),
+                bid_amount,
+                0,
+            )
+            .with_actor_id(buyer())
+            .await
+            .unwrap();
+        bids.push(id);
+    }
+    let foreign_ask = c
+        .submit_order(
+            Side::Sell,
+            OrderType::Limit,
+            price_fp_usdt_per_eth(2_100),
+            bid_amount,
+            0,
+        )
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    let trigger = c
+        .submit_market_if_touched(
+            Side::Buy,
+            price_fp_usdt_per_eth(2_200),
+            bid_amount,
+            usdt_micro(500),
+        )
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+    let missing = 9_999;
+
+    let mut listener = env.listen(Some).await.unwrap();
+    let results = c
+        .cancel_orders(vec![
+            bids[0],
+            bids[2],
+            missing,
+            foreign_ask,
+            trigger,
+            bids[0],
+        ])
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+    assert_eq!(
+        results,
+        vec![
+            (bids[0], true),
+            (bids[2], true),
+            (missing, false),
+            (foreign_ask, false),
+            (trigger, true),
+            (bids[0], false),
+        ]
+    );
+
+    // Each pulled bid is announced (eth and plain copies) before the top of book moves.
+    let mut canceled = Vec::new();
+    for _ in 0..4 {
+        let (_, payload) = listener.next().await.unwrap();
+        if let Ok((service, event, _seq, order_id, side, _price, _remaining)) =
+            <(String, String, u64, u64, Side, u128, u128)>::decode(&mut payload.as_slice())
+        {
+            if service == "Orderbook" {
+                assert_eq!(event, "OrderCanceled");
+                canceled.push((order_id, side));
+            }
+        }
+    }
+    assert_eq!(canceled, vec![(bids[0], Side::Buy), (bids[2], Side::Buy)]);
+
+    // Only the middle bid keeps its reservation; the foreign ask is untouched.
+    let still_reserved = quote_ceil_atoms(bid_amount, price_fp_usdt_per_eth(1_950));
+    assert_balance(&program, buyer(), 0, quote_deposit - still_reserved).await;
+    assert_eq!(
+        c.best_bid_price().await.unwrap(),
+        price_fp_usdt_per_eth(1_950)
+    );
+    assert_eq!(
+        c.best_ask_price().await.unwrap(),
+        price_fp_usdt_per_eth(2_100)
+    );
+}
+
+#[tokio::test]
+async fn amend_order_shrink_keeps_queue_position() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let price = price_fp_usdt_per_eth(2_000);
+    let ask_amount = eth_frac(1, 10); // 0.1 ETH
+    let amended_amount = eth_frac(1, 20); // 0.05 ETH
+
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.deposit(seller2(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    let first_ask = c
+        .submit_order(Side::Sell, OrderType::Limit, price, ask_amount, 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    c.submit_order(Side::Sell, OrderType::Limit, price, ask_amount, 0)
+        .with_actor_id(seller2())
+        .await
+        .unwrap();
+
+    // Only the owner may amend.
+    let res = c
+        .amend_order(first_ask, price, amended_amount)
+        .with_actor_id(seller2())
+        .await;
+    assert!(res.is_err(), "Expected non-owner amend to fail");
+
+    c.amend_order(first_ask, price, amended_amount)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    assert_balance(&program, seller(), eth_wei(1) - amended_amount, 0).await;
+
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.submit_order(
+        Side::Buy,
+        OrderType::Market,
+        0,
+        amended_amount,
+        usdt_micro(10_000),
+    )
+    .with_actor_id(buyer())
+    .await
+    .unwrap();
+
+    let trades = c.trades(0, 10).await.unwrap();
+    assert_eq!(trades.len(), 1);
+    assert_eq!(trades[0].1, first_ask);
+}
+
+#[tokio::test]
+async fn amend_order_price_change_loses_queue_position() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let price = price_fp_usdt_per_eth(2_000);
+    let away_price = price_fp_usdt_per_eth(2_100);
+    let ask_amount = eth_frac(1, 10); // 0.1 ETH
+
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.deposit(seller2(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    let first_ask = c
+        .submit_order(Side::Sell, OrderType::Limit, price, ask_amount, 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    let second_ask = c
+        .submit_order(Side::Sell, OrderType::Limit, price, ask_amount, 0)
+        .with_actor_id(seller2())
+        .await
+        .unwrap();
+
+    // Away and back again: same id, but now queued behind the second ask.
+    c.amend_order(first_ask, away_price, ask_amount)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    let order = c
+        .order_by_id(first_ask)
+        .await
+        .unwrap()
+        .expect("order must rest");
+    assert_eq!(order.price, away_price);
+    c.amend_order(first_ask, price, ask_amount)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    assert_balance(&program, seller(), eth_wei(1) - ask_amount, 0).await;
+
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.submit_order(
+        Side::Buy,
+        OrderType::Market,
+        0,
+        eth_frac(1, 20),
+        usdt_micro(10_000),
+    )
+    .with_actor_id(buyer())
+    .await
+    .unwrap();
+
+    let trades = c.trades(0, 10).await.unwrap();
+    assert_eq!(trades.len(), 1);
+    assert_eq!(trades[0].1, second_ask);
+}
+
+#[tokio::test]
+async fn order_by_id_returns_view_while_resting_and_none_after_cancel() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let price = price_fp_usdt_per_eth(2_000);
+    let bid_amount = eth_frac(1, 4); // 0.25 ETH
+    let reserved = quote_ceil_atoms(bid_amount, price);
+
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+
+    let bid_id = c
+        .submit_order(Side::Buy, OrderType::Limit, price, bid_amount, 0)
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+
+    let order = c
+        .order_by_id(bid_id)
+        .await
+        .unwrap()
+        .expect("order must rest");
+    assert_eq!(order.id, bid_id);
+    assert_eq!(order.owner, buyer());
+    assert_eq!(order.side, Side::Buy);
+    assert_eq!(order.price, price);
+    assert_eq!(order.remaining_base, bid_amount);
+    assert_eq!(order.reserved_quote, reserved);
+
+    c.cancel_order(bid_id).with_actor_id(buyer()).await.unwrap();
+
+    assert!(c.order_by_id(bid_id).await.unwrap().is_none());
+    assert!(c.order_by_id(bid_id + 1).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn version_matches_crate_and_health_is_ok() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let expected = (
+        env!("CARGO_PKG_VERSION_MAJOR").parse::<u16>().unwrap(),
+        env!("CARGO_PKG_VERSION_MINOR").parse::<u16>().unwrap(),
+        env!("CARGO_PKG_VERSION_PATCH").parse::<u16>().unwrap(),
+    );
+    assert_eq!(c.version().await.unwrap(), expected);
+    assert!(c.health().await.unwrap());
+
+    c.pause(true)
+        .with_actor_id(ActorId::from(ADMIN_ID))
+        .await
+        .unwrap();
+    assert!(!c.health().await.unwrap());
+    c.pause(false)
+        .with_actor_id(ActorId::from(ADMIN_ID))
+        .await
+        .unwrap();
+    assert!(c.health().await.unwrap());
+}
+
+#[tokio::test]
+async fn cancel_at_price_removes_only_callers_orders_at_level() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let price = price_fp_usdt_per_eth(2_000);
+    let other_price = price_fp_usdt_per_eth(2_100);
+    let ask_amount = eth_frac(1, 10); // 0.1 ETH
+
+    for who in [seller(), seller2()] {
+        c.deposit(who, BASE_TOKEN_ID, eth_wei(1))
+            .with_actor_id(vault())
+            .await
+            .unwrap();
+    }
+
+    let own_a = c
+        .submit_order(Side::Sell, OrderType::Limit, price, ask_amount, 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    let foreign = c
+        .submit_order(Side::Sell, OrderType::Limit, price, ask_amount, 0)
+        .with_actor_id(seller2())
+        .await
+        .unwrap();
+    let own_b = c
+        .submit_order(Side::Sell, OrderType::Limit, price, ask_amount, 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    let own_other_level = c
+        .submit_order(Side::Sell, OrderType::Limit, other_price, ask_amount, 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+
+    let cancelled = c
+        .cancel_at_price(Side::Sell, price)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    assert_eq!(cancelled, 2);
+
+    assert!(c.order_by_id(own_a).await.unwrap().is_none());
+    assert!(c.order_by_id(own_b).await.unwrap().is_none());
+    assert!(c.order_by_id(foreign).await.unwrap().is_some());
+    assert!(c.order_by_id(own_other_level).await.unwrap().is_some());
+
+    // Only the order at the other level stays locked for the caller.
+    assert_balance(&program, seller(), eth_wei(1) - ask_amount, 0).await;
+    assert_balance(&program, seller2(), eth_wei(1) - ask_amount, 0).await;
+    assert_eq!(c.best_ask_price().await.unwrap(), price);
+}
+
+#[tokio::test]
+async fn cancel_at_price_skips_orders_too_young_to_cancel() {
+    let (env, program) = setup_orderbook_with_env(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    c.set_min_order_lifetime(3)
+        .with_actor_id(ActorId::from(ADMIN_ID))
+        .await
+        .unwrap();
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+
+    let price = price_fp_usdt_per_eth(2_000);
+    let ask_amount = eth_frac(1, 10);
+    let old = c
+        .submit_order(Side::Sell, OrderType::Limit, price, ask_amount, 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    for _ in 0..3 {
+        env.system().run_next_block();
+    }
+    let young = c
+        .submit_order(Side::Sell, OrderType::Limit, price, ask_amount, 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+
+    let mut listener = env.listen(Some).await.unwrap();
+    let cancelled = c
+        .cancel_at_price(Side::Sell, price)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    assert_eq!(cancelled, 1);
+
+    // OrderCanceled comes first (eth and plain copies); the level itself stays.
+    let mut canceled = None;
+    for _ in 0..2 {
+        let (_, payload) = listener.next().await.unwrap();
+        if let Ok((service, event, _seq, order_id, side, order_price, remaining_base)) =
+            <(String, String, u64, u64, Side, u128, u128)>::decode(&mut payload.as_slice())
+        {
+            if service == "Orderbook" {
+                assert_eq!(event, "OrderCanceled");
+                canceled = Some((order_id, side, order_price, remaining_base));
+            }
+        }
+    }
+    assert_eq!(canceled, Some((old, Side::Sell, price, ask_amount)));
+
+    assert!(c.order_by_id(old).await.unwrap().is_none());
+    assert!(c.order_by_id(young).await.unwrap().is_some());
+    assert_balance(&program, seller(), eth_wei(1) - ask_amount, 0).await;
+}
+
+#[tokio::test]
+async fn create_rejects_identical_base_and_quote_tokens() {
+    let system = System::new();
+    system.init_logger();
+    system.mint_to(ADMIN_ID, 100_000_000_000_000_000);
+
+    let env = GtestEnv::new(system, ADMIN_ID.into());
+    let program_code_id = env.system().submit_code_file(ORDERBOOK_WASM);
+
+    let res = env
+        .deploy::<orderbook_client::OrderbookProgram>(program_code_id, b"same".to_vec())
+        .create(vault(), vault(), BASE_TOKEN_ID, BASE_TOKEN_ID, 1000, 1000)
+        .await;
+    assert!(res.is_err(), "Expected identical tokens to be rejected");
+
+    let res = env
+        .deploy::<orderbook_client::OrderbookProgram>(program_code_id, b"distinct".to_vec())
+        .create(vault(), vault(), BASE_TOKEN_ID, QUOTE_TOKEN_ID, 1000, 1000)
+        .await;
+    assert!(res.is_ok(), "Expected distinct tokens to be accepted");
+}
+
+#[tokio::test]
+async fn deposit_rejects_token_not_bound_to_market() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let foreign_token: TokenId = [99u8; 20];
+    let res = c
+        .deposit(buyer(), foreign_token, usdt_micro(1_000))
+        .with_actor_id(vault())
+        .await;
+    assert!(res.is_err(), "Expected deposit of a foreign token to fail");
+
+    assert_balance(&program, buyer(), 0, 0).await;
+}
+
+#[tokio::test]
+async fn required_funds_matches_reservation_taken_on_submit() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let price = price_fp_usdt_per_eth(2_000);
+    let bid_amount = eth_frac(1, 3); // leaves a remainder so the quote is ceiled
+
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    let (token, needed) = c
+        .required_funds(Side::Buy, OrderType::Limit, price, bid_amount, 0)
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+    assert_eq!(token, QUOTE_TOKEN_ID);
+    assert_eq!(needed, quote_ceil_atoms(bid_amount, price));
+    c.submit_order(Side::Buy, OrderType::Limit, price, bid_amount, 0)
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+    assert_balance(&program, buyer(), 0, usdt_micro(10_000) - needed).await;
+
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    let (token, needed) = c
+        .required_funds(Side::Sell, OrderType::Limit, price * 2, eth_frac(1, 4), 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    assert_eq!((token, needed), (BASE_TOKEN_ID, eth_frac(1, 4)));
+    c.submit_order(Side::Sell, OrderType::Limit, price * 2, eth_frac(1, 4), 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    assert_balance(&program, seller(), eth_wei(1) - needed, 0).await;
+
+    // Market buys reserve their whole budget.
+    let (token, needed) = c
+        .required_funds(
+            Side::Buy,
+            OrderType::Market,
+            0,
+            eth_frac(1, 10),
+            usdt_micro(500),
+        )
+        .await
+        .unwrap();
+    assert_eq!((token, needed), (QUOTE_TOKEN_ID, usdt_micro(500)));
+}
+
+#[tokio::test]
+async fn replace_orders_refreshes_ladder_in_one_call() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let rung = eth_frac(1, 10); // 0.1 ETH per level
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+
+    let mut old_ids = Vec::new();
+    for usdt in [2_000, 2_100, 2_200] {
+        old_ids.push(
+            c.submit_order(
+                Side::Sell,
+                OrderType::Limit,
+                price_fp_usdt_per_eth(usdt),
+                rung,
+                0,
+            )
+            .with_actor_id(seller())
+            .await
+            .unwrap(),
+        );
+    }
+
+    let places: Vec<_> = [2_050, 2_150, 2_250]
+        .into_iter()
+        .map(|usdt| {
+            (
+                Side::Sell,
+                OrderType::Limit,
+                price_fp_usdt_per_eth(usdt),
+                rung,
+                0u128,
+            )
+        })
+        .collect();
+
+    // Placements the caller cannot fund revert the cancels too.
+    let mut too_big = places.clone();
+    too_big.push((
+        Side::Sell,
+        OrderType::Limit,
+        price_fp_usdt_per_eth(2_300),
+        eth_wei(1),
+        0,
+    ));
+    let res = c
+        .replace_orders(old_ids.clone(), too_big)
+        .with_actor_id(seller())
+        .await;
+    assert!(res.is_err(), "Expected unfundable replace to fail");
+    for id in &old_ids {
+        assert!(c.order_by_id(*id).await.unwrap().is_some());
+    }
+
+    let new_ids = c
+        .replace_orders(old_ids.clone(), places)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    assert_eq!(new_ids.len(), 3);
+
+    for id in &old_ids {
+        assert!(c.order_by_id(*id).await.unwrap().is_none());
+    }
+    for id in &new_ids {
+        assert!(c.order_by_id(*id).await.unwrap().is_some());
+    }
+    assert_eq!(
+        c.best_ask_price().await.unwrap(),
+        price_fp_usdt_per_eth(2_050)
+    );
+    assert_balance(&program, seller(), eth_wei(1) - 3 * rung, 0).await;
+}
+
+#[tokio::test]
+async fn would_self_trade_reports_blocked_base_against_own_ask() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let price = price_fp_usdt_per_eth(2_000);
+    let ask_amount = eth_frac(1, 10); // 0.1 ETH
+
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.submit_order(Side::Sell, OrderType::Limit, price, ask_amount, 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+
+    let (blocked, blocked_base) = c
+        .would_self_trade(Side::Buy, price, eth_frac(1, 4))
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    assert!(blocked);
+    assert_eq!(blocked_base, ask_amount);
+
+    // Another account crossing the same ask is not affected.
+    let (blocked, blocked_base) = c
+        .would_self_trade(Side::Buy, price, eth_frac(1, 4))
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+    assert!(!blocked);
+    assert_eq!(blocked_base, 0);
+
+    // The query is read-only.
+    assert_balance(&program, seller(), eth_wei(1) - ask_amount, 0).await;
+    assert_eq!(c.best_ask_price().await.unwrap(), price);
+}
+
+#[tokio::test]
+async fn stp_cancel_resting_pulls_own_ask_instead_of_self_matching() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let price = price_fp_usdt_per_eth(2_000);
+    let ask_amount = eth_frac(1, 10); // 0.1 ETH
+    let bid_amount = eth_frac(1, 20); // 0.05 ETH
+    let quote_deposit = usdt_micro(1_000);
+
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.deposit(seller(), QUOTE_TOKEN_ID, quote_deposit)
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.submit_order(Side::Sell, OrderType::Limit, price, ask_amount, 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+
+    c.set_stp_mode(StpMode::CancelResting)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    assert_eq!(c.stp_mode(seller()).await.unwrap(), StpMode::CancelResting);
+    assert_eq!(c.stp_mode(buyer()).await.unwrap(), StpMode::None);
+
+    // The crossing buy cancels the own ask, then rests since nothing else is offered.
+    c.submit_order(Side::Buy, OrderType::Limit, price, bid_amount, 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+
+    assert_eq!(c.trades_count().await.unwrap(), 0);
+    assert_eq!(c.best_ask_price().await.unwrap(), 0);
+    assert_eq!(c.best_bid_price().await.unwrap(), price);
+    assert_balance(
+        &program,
+        seller(),
+        eth_wei(1),
+        quote_deposit - quote_ceil_atoms(bid_amount, price),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn price_exponent_scales_reserved_quote() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    assert_eq!(c.price_exponent().await.unwrap(), 30);
+
+    // Non-admin cannot change the exponent.
+    let res = c.set_price_exponent(18).with_actor_id(buyer()).await;
+    assert!(
+        res.is_err(),
+        "Expected non-admin set_price_exponent to fail"
+    );
+
+    c.set_price_exponent(18).await.unwrap();
+    assert_eq!(c.price_exponent().await.unwrap(), 18);
+
+    // 2000 USDT per ETH with a 1e18 scale: 2000 * 1e6 quote atoms per 1e18 base atoms.
+    let price = usdt_micro(2_000);
+    let bid_amount = eth_frac(1, 4); // 0.25 ETH
+    let reserved = usdt_micro(500);
+
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    let bid_id = c
+        .submit_order(Side::Buy, OrderType::Limit, price, bid_amount, 0)
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+
+    let order = c
+        .order_by_id(bid_id)
+        .await
+        .unwrap()
+        .expect("order must rest");
+    assert_eq!(order.reserved_quote, reserved);
+    assert_balance(&program, buyer(), 0, usdt_micro(10_000) - reserved).await;
+
+    // The exponent is frozen while orders rest.
+    let res = c.set_price_exponent(30).await;
+    assert!(
+        res.is_err(),
+        "Expected set_price_exponent on a non-empty book to fail"
+    );
+}
+
+#[tokio::test]
+async fn market_if_touched_buy_converts_to_market_on_breakout() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let price_2000 = price_fp_usdt_per_eth(2_000);
+    let price_2100 = price_fp_usdt_per_eth(2_100);
+    let ask1 = eth_frac(1, 10); // 0.1 ETH @ 2000
+    let ask2 = eth_frac(2, 10); // 0.2 ETH @ 2100
+    let mit_amount = eth_frac(1, 10); // 0.1 ETH
+    let budget = usdt_micro(1_000);
+
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    for who in [buyer(), buyer2()] {
+        c.deposit(who, QUOTE_TOKEN_ID, usdt_micro(10_000))
+            .with_actor_id(vault())
+            .await
+            .unwrap();
+    }
+    c.submit_order(Side::Sell, OrderType::Limit, price_2000, ask1, 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    c.submit_order(Side::Sell, OrderType::Limit, price_2100, ask2, 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+
+    let mit_id = c
+        .submit_market_if_touched(Side::Buy, price_2000, mit_amount, budget)
+        .with_actor_id(buyer2())
+        .await
+        .unwrap();
+
+    // Parked: budget locked, nothing traded yet.
+    assert_eq!(c.trades_count().await.unwrap(), 0);
+    assert_balance(&program, buyer2(), 0, usdt_micro(10_000) - budget).await;
+
+    // A trade at 2000 touches the trigger.
+    let half = eth_frac(1, 20); // 0.05 ETH
+    c.submit_order(Side::Buy, OrderType::Limit, price_2000, half, 0)
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+
+    // The MIT bought the rest of the 2000 level and half of the 2100 level.
+    let spent = quote_floor_atoms(half, price_2000) + quote_floor_atoms(half, price_2100);
+    assert_eq!(c.trades_count().await.unwrap(), 3);
+    let trades = c.trades(0, 3).await.unwrap();
+    assert_eq!(trades[1].2, mit_id);
+    assert_eq!(trades[2].2, mit_id);
+    assert_eq!(trades[2].5, price_2100);
+    assert_balance(&program, buyer2(), mit_amount, usdt_micro(10_000) - spent).await;
+    assert_eq!(c.best_ask_price().await.unwrap(), price_2100);
+}
+
+#[tokio::test]
+async fn max_order_base_accepts_cap_and_rejects_above() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let price = price_fp_usdt_per_eth(2_000);
+    let cap = eth_frac(1, 2); // 0.5 ETH
+
+    let res = c.set_max_order_base(cap).with_actor_id(seller()).await;
+    assert!(
+        res.is_err(),
+        "Expected non-admin set_max_order_base to fail"
+    );
+
+    c.set_max_order_base(cap).await.unwrap();
+    assert_eq!(c.max_order_base().await.unwrap(), cap);
+
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+
+    let res = c
+        .submit_order(Side::Sell, OrderType::Limit, price, cap + 1, 0)
+        .with_actor_id(seller())
+        .await;
+    assert!(res.is_err(), "Expected order above the cap to fail");
+    assert_balance(&program, seller(), eth_wei(1), 0).await;
+
+    let ask_id = c
+        .submit_order(Side::Sell, OrderType::Limit, price, cap, 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    assert!(c.order_by_id(ask_id).await.unwrap().is_some());
+    assert_balance(&program, seller(), eth_wei(1) - cap, 0).await;
+}
+
+#[tokio::test]
+async fn sweep_limit_caps_huge_taker_and_refunds_remainder() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let price = price_fp_usdt_per_eth(2_000);
+    let cap = eth_frac(1, 2); // 0.5 ETH
+
+    let res = c.set_sweep_limits(0, cap).with_actor_id(seller()).await;
+    assert!(res.is_err(), "Expected non-admin set_sweep_limits to fail");
+    c.set_sweep_limits(0, cap).await.unwrap();
+    assert_eq!(c.sweep_limits().await.unwrap(), (0, cap));
+
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(2))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    for _ in 0..4 {
+        c.submit_order(Side::Sell, OrderType::Limit, price, eth_frac(2, 5), 0)
+            .with_actor_id(seller())
+            .await
+            .unwrap();
+    }
+
+    // A limit buy for the whole book stops at the cap; nothing of it rests.
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    let buy_id = c
+        .submit_order(Side::Buy, OrderType::Limit, price, eth_frac(8, 5), 0)
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+
+    assert_eq!(c.trades_count().await.unwrap(), 2);
+    assert!(c.order_by_id(buy_id).await.unwrap().is_none());
+    assert_eq!(c.best_bid_price().await.unwrap(), 0);
+    assert_eq!(c.best_ask_price().await.unwrap(), price);
+
+    let spent =
+        quote_floor_atoms(eth_frac(2, 5), price) + quote_floor_atoms(eth_frac(1, 10), price);
+    assert_balance(&program, buyer(), cap, usdt_micro(10_000) - spent).await;
+}
+
+#[tokio::test]
+async fn improves_book_is_strict_per_side() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let bid = price_fp_usdt_per_eth(1_990);
+    let ask = price_fp_usdt_per_eth(2_010);
+
+    // Empty sides are improved by any price.
+    assert!(c.improves_book(Side::Buy, bid).await.unwrap());
+    assert!(c.improves_book(Side::Sell, ask).await.unwrap());
+
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.submit_order(Side::Buy, OrderType::Limit, bid, eth_frac(1, 10), 0)
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+    c.submit_order(Side::Sell, OrderType::Limit, ask, eth_frac(1, 10), 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+
+    // Improves
+    assert!(c.improves_book(Side::Buy, bid + 1).await.unwrap());
+    assert!(c.improves_book(Side::Sell, ask - 1).await.unwrap());
+    // Ties
+    assert!(!c.improves_book(Side::Buy, bid).await.unwrap());
+    assert!(!c.improves_book(Side::Sell, ask).await.unwrap());
+    // Worse
+    assert!(!c.improves_book(Side::Buy, bid - 1).await.unwrap());
+    assert!(!c.improves_book(Side::Sell, ask + 1).await.unwrap());
+}
+
+#[tokio::test]
+async fn taker_buy_below_limit_records_price_improvement() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let ask_price = price_fp_usdt_per_eth(1_990);
+    let limit = price_fp_usdt_per_eth(2_000);
+    let amount = eth_frac(1, 10); // 0.1 ETH
+
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.submit_order(Side::Sell, OrderType::Limit, ask_price, amount, 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    c.submit_order(Side::Buy, OrderType::Limit, limit, amount, 0)
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+
+    // 0.1 ETH * (2000 - 1990) USDT = 1 USDT saved.
+    let expected = quote_floor_atoms(amount, limit) - quote_floor_atoms(amount, ask_price);
+    assert_eq!(expected, usdt_micro(1));
+    assert_eq!(
+        c.get_price_improvement(buyer(), QUOTE_TOKEN_ID)
+            .await
+            .unwrap(),
+        expected
+    );
+    assert_eq!(
+        c.get_price_improvement(buyer(), BASE_TOKEN_ID)
+            .await
+            .unwrap(),
+        0
+    );
+    // Makers fill at their own price, so they have no improvement.
+    assert_eq!(
+        c.get_price_improvement(seller(), QUOTE_TOKEN_ID)
+            .await
+            .unwrap(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn all_orders_pages_through_book_by_id() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+
+    let mut placed = Vec::new();
+    for i in 0..3u128 {
+        let id = c
+            .submit_order(
+                Side::Sell,
+                OrderType::Limit,
+                price_fp_usdt_per_eth(2_000 + i),
+                eth_frac(1, 100),
+                0,
+            )
+            .with_actor_id(seller())
+            .await
+            .unwrap();
+        placed.push(id);
+        let id = c
+            .submit_order(
+                Side::Buy,
+                OrderType::Limit,
+                price_fp_usdt_per_eth(1_900 - i),
+                eth_frac(1, 100),
+                0,
+            )
+            .with_actor_id(buyer())
+            .await
+            .unwrap();
+        placed.push(id);
+    }
+    // Cancel one so the id sequence has a gap.
+    c.cancel_order(placed[2])
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    placed.remove(2);
+
+    let mut seen = Vec::new();
+    let mut cursor = 0;
+    loop {
+        let (page, next) = c.all_orders(cursor, 2).await.unwrap();
+        assert!(page.len() <= 2);
+        seen.extend(page.iter().map(|o| o.id));
+        if next == 0 {
+            break;
+        }
+        cursor = next;
+    }
+    assert_eq!(seen, placed);
+}
+
+#[tokio::test]
+async fn arena_footprint_reports_freed_slots_after_cancels() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    assert_eq!(c.arena_footprint().await.unwrap(), (0, 0, 0));
+
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    let mut ids = Vec::new();
+    for usdt in [2_010, 2_020, 2_030, 2_040, 2_050] {
+        ids.push(
+            c.submit_order(
+                Side::Sell,
+                OrderType::Limit,
+                price_fp_usdt_per_eth(usdt),
+                eth_frac(1, 10),
+                0,
+            )
+            .with_actor_id(seller())
+            .await
+            .unwrap(),
+        );
+    }
+    for id in &ids[..2] {
+        c.cancel_order(*id).with_actor_id(seller()).await.unwrap();
+    }
+
+    let (occupied, free, capacity) = c.arena_footprint().await.unwrap();
+    assert_eq!((occupied, free), (3, 2));
+    assert!(capacity >= occupied + free);
+
+    // The next order reuses a freed slot instead of growing the arena.
+    c.submit_order(
+        Side::Sell,
+        OrderType::Limit,
+        price_fp_usdt_per_eth(2_060),
+        eth_frac(1, 10),
+        0,
+    )
+    .with_actor_id(seller())
+    .await
+    .unwrap();
+    assert_eq!(c.arena_footprint().await.unwrap(), (4, 1, capacity));
+}
+
+#[tokio::test]
+async fn book_stats_track_levels_and_orders_per_side() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    assert_eq!(c.book_stats().await.unwrap(), (0, 0, 0, 0));
+
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(100_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(10))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+
+    // Bids: two at 1990, one at 1980. Asks: one each at 2010, 2020, 2030.
+    for usdt in [1_990, 1_990, 1_980] {
+        c.submit_order(
+            Side::Buy,
+            OrderType::Limit,
+            price_fp_usdt_per_eth(usdt),
+            eth_frac(1, 10),
+            0,
+        )
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+    }
+    let mut ask_ids = Vec::new();
+    for usdt in [2_010, 2_020, 2_030] {
+        let id = c
+            .submit_order(
+                Side::Sell,
+                OrderType::Limit,
+                price_fp_usdt_per_eth(usdt),
+                eth_frac(1, 10),
+                0,
+            )
+            .with_actor_id(seller())
+            .await
+            .unwrap();
+        ask_ids.push(id);
+    }
+    assert_eq!(c.book_stats().await.unwrap(), (2, 3, 3, 3));
+
+    // Cancelling the only order at a level drops the level too.
+    c.cancel_order(ask_ids[1])
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    assert_eq!(c.book_stats().await.unwrap(), (2, 3, 2, 2));
+
+    // A sell filling one of the two best bids keeps the level.
+    c.submit_order(Side::Sell, OrderType::Market, 0, eth_frac(1, 10), 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    assert_eq!(c.book_stats().await.unwrap(), (2, 2, 2, 2));
+}
+
+#[tokio::test]
+async fn filled_market_buy_emits_budget_refund() {
+    let (env, program) = setup_orderbook_with_env(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let price = price_fp_usdt_per_eth(2_000);
+    let amount = eth_frac(1, 2);
+    let locked_quote = usdt_micro(1_500);
+
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(2_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.submit_order(Side::Sell, OrderType::Limit, price, eth_wei(1), 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+
+    let mut listener = env.listen(Some).await.unwrap();
+    c.submit_order(Side::Buy, OrderType::Market, 0, amount, locked_quote)
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+
+    let taker_spent_quote = quote_floor_atoms(amount, price);
+    // The program emits both an eth-style and a plain event; take the plain one.
+    let mut refunded = None;
+    for _ in 0..2 {
+        let (from, payload) = listener.next().await.unwrap();
+        assert_eq!(from, program.id());
+        if let Ok((service, event, _seq, user, token, amount)) =
+            <(String, String, u64, [u8; 20], TokenId, u128)>::decode(&mut payload.as_slice())
+        {
+            if service == "Orderbook" && event == "BudgetRefunded" {
+                assert_eq!(user, clob_common::actor_to_eth(buyer()));
+                assert_eq!(token, QUOTE_TOKEN_ID);
+                refunded = Some(amount);
+                break;
+            }
+        }
+    }
+    assert_eq!(refunded, Some(locked_quote - taker_spent_quote));
+    assert_balance(
+        &program,
+        buyer(),
+        amount,
+        usdt_micro(2_000) - taker_spent_quote,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn withdrawable_excludes_funds_reserved_by_resting_orders() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let price = price_fp_usdt_per_eth(2_000);
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(5_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.submit_order(Side::Buy, OrderType::Limit, price, eth_wei(1), 0)
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+
+    let reserved = quote_ceil_atoms(eth_wei(1), price);
+    let withdrawable = c
+        .withdrawable(QUOTE_TOKEN_ID)
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+    assert_eq!(withdrawable, usdt_micro(5_000) - reserved);
+    assert_eq!(
+        c.withdrawable(BASE_TOKEN_ID)
+            .with_actor_id(buyer())
+            .await
+            .unwrap(),
+        0
+    );
+    // Another account sees only its own (empty) balance.
+    assert_eq!(
+        c.withdrawable(QUOTE_TOKEN_ID)
+            .with_actor_id(seller())
+            .await
+            .unwrap(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn volume_split_separates_maker_and_taker_quote() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let bid = price_fp_usdt_per_eth(1_990);
+    let ask = price_fp_usdt_per_eth(2_010);
+    let amount = eth_frac(1, 10);
+
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.deposit(buyer(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.deposit(seller(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+
+    // Buyer is maker: rests a bid that the seller hits.
+    c.submit_order(Side::Buy, OrderType::Limit, bid, amount, 0)
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+    c.submit_order(Side::Sell, OrderType::Limit, bid, amount, 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+
+    // Buyer is taker: lifts an ask the seller rests.
+    c.submit_order(Side::Sell, OrderType::Limit, ask, amount, 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    c.submit_order(Side::Buy, OrderType::Limit, ask, amount, 0)
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+
+    let as_maker = quote_floor_atoms(amount, bid);
+    let as_taker = quote_floor_atoms(amount, ask);
+    assert_eq!(c.volume_split(buyer()).await.unwrap(), (as_maker, as_taker));
+    assert_eq!(
+        c.volume_split(seller()).await.unwrap(),
+        (as_taker, as_maker)
+    );
+    assert_eq!(c.volume_split(buyer2()).await.unwrap(), (0, 0));
+}
+
+#[cfg(feature = "debug")]
+#[tokio::test]
+async fn simulate_matches_real_submission_without_committing() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.deposit(buyer(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    for usdt in [2_000, 2_010] {
+        c.submit_order(
+            Side::Sell,
+            OrderType::Limit,
+            price_fp_usdt_per_eth(usdt),
+            eth_frac(1, 10),
+            0,
+        )
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    }
+
+    let orders = vec![
+        // Sweeps the 2000 ask and part of 2010.
+        (
+            Side::Buy,
+            OrderType::Limit,
+            price_fp_usdt_per_eth(2_010),
+            eth_frac(15, 100),
+            0u128,
+        ),
+        // Rests a bid below the remaining ask.
+        (
+            Side::Buy,
+            OrderType::Limit,
+            price_fp_usdt_per_eth(1_990),
+            eth_frac(1, 10),
+            0,
+        ),
+        // Hits the buyer's own bid as IOC.
+        (
+            Side::Sell,
+            OrderType::ImmediateOrCancel,
+            price_fp_usdt_per_eth(1_990),
+            eth_frac(1, 20),
+            0,
+        ),
+    ];
+    let reports = c
+        .simulate(orders.clone())
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+    assert_eq!(reports.len(), orders.len());
+
+    // Nothing committed.
+    assert_eq!(c.trades_count().await.unwrap(), 0);
+    assert_eq!(
+        c.best_ask_price().await.unwrap(),
+        price_fp_usdt_per_eth(2_000)
+    );
+    assert_eq!(c.best_bid_price().await.unwrap(), 0);
+
+    // `simulate` still takes the u16 encoding; the same orders, typed.
+    let typed = [
+        (Side::Buy, OrderType::Limit),
+        (Side::Buy, OrderType::Limit),
+        (Side::Sell, OrderType::ImmediateOrCancel),
+    ];
+    for ((_, _, price, amount, max_quote), (side, kind)) in orders.into_iter().zip(typed) {
+        c.submit_order(side, kind, price, amount, max_quote)
+            .with_actor_id(buyer())
+            .await
+            .unwrap();
+    }
+
+    let simulated: Vec<_> = reports.into_iter().flat_map(|r| r.trades).collect();
+    let real = c.trades(0, 100).await.unwrap();
+    assert_eq!(simulated.len(), 3);
+    assert_eq!(simulated.len(), real.len());
+    for (sim, real) in simulated.iter().zip(real.iter()) {
+        assert_eq!(sim.maker_order_id, real.1);
+        assert_eq!(sim.taker_order_id, real.2);
+        assert_eq!(sim.maker, real.3);
+        assert_eq!(sim.taker, real.4);
+        assert_eq!(sim.price, U256::from(real.5));
+        assert_eq!(sim.amount_base, U256::from(real.6));
+        assert_eq!(sim.amount_quote, U256::from(real.7));
+    }
+}
+
+#[tokio::test]
+async fn consuming_best_ask_emits_best_ask_changed() {
+    let (env, program) = setup_orderbook_with_env(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let best = price_fp_usdt_per_eth(2_000);
+    let next = price_fp_usdt_per_eth(2_010);
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.submit_order(Side::Sell, OrderType::Limit, best, eth_frac(1, 10), 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    c.submit_order(Side::Sell, OrderType::Limit, next, eth_frac(2, 10), 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+
+    let mut listener = env.listen(Some).await.unwrap();
+    c.submit_order(Side::Buy, OrderType::Limit, best, eth_frac(1, 10), 0)
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+
+    // Only the ask side moved, announced once as an eth-style and once as a plain event.
+    let mut changed = None;
+    for _ in 0..2 {
+        let (from, payload) = listener.next().await.unwrap();
+        assert_eq!(from, program.id());
+        if let Ok((service, event, _seq, price, quantity)) =
+            <(String, String, u64, u128, u128)>::decode(&mut payload.as_slice())
+        {
+            if service == "Orderbook" {
+                assert_eq!(event, "BestAskChanged");
+                changed = Some((price, quantity));
+            }
+        }
+    }
+    assert_eq!(changed, Some((next, eth_frac(2, 10))));
+}
+
+#[tokio::test]
+async fn price_range_reports_min_and_max_per_side() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    assert_eq!(c.price_range().await.unwrap(), (None, None));
+
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(100_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(10))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+
+    for usdt in [1_990, 1_950, 1_970] {
+        c.submit_order(
+            Side::Buy,
+            OrderType::Limit,
+            price_fp_usdt_per_eth(usdt),
+            eth_frac(1, 10),
+            0,
+        )
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+    }
+    c.submit_order(
+        Side::Sell,
+        OrderType::Limit,
+        price_fp_usdt_per_eth(2_010),
+        eth_frac(1, 10),
+        0,
+    )
+    .with_actor_id(seller())
+    .await
+    .unwrap();
+
+    let (bids, asks) = c.price_range().await.unwrap();
+    assert_eq!(
+        bids,
+        Some((price_fp_usdt_per_eth(1_950), price_fp_usdt_per_eth(1_990)))
+    );
+    assert_eq!(
+        asks,
+        Some((price_fp_usdt_per_eth(2_010), price_fp_usdt_per_eth(2_010)))
+    );
+}
+
+#[tokio::test]
+async fn peg_to_best_rests_one_tick_inside_prior_best() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let bid = price_fp_usdt_per_eth(1_990);
+    let ask = price_fp_usdt_per_eth(2_010);
+    for who in [buyer(), buyer2()] {
+        c.deposit(who, QUOTE_TOKEN_ID, usdt_micro(10_000))
+            .with_actor_id(vault())
+            .await
+            .unwrap();
+    }
+    for who in [seller(), seller2()] {
+        c.deposit(who, BASE_TOKEN_ID, eth_wei(1))
+            .with_actor_id(vault())
+            .await
+            .unwrap();
+    }
+    c.submit_order(Side::Buy, OrderType::Limit, bid, eth_frac(1, 10), 0)
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+    c.submit_order(Side::Sell, OrderType::Limit, ask, eth_frac(1, 10), 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+
+    let peg_bid = c
+        .submit_order(Side::Buy, OrderType::PegToBest, 0, eth_frac(1, 10), 0)
+        .with_actor_id(buyer2())
+        .await
+        .unwrap();
+    let peg_ask = c
+        .submit_order(Side::Sell, OrderType::PegToBest, 0, eth_frac(1, 10), 0)
+        .with_actor_id(seller2())
+        .await
+        .unwrap();
+
+    let peg_bid = c.order_by_id(peg_bid).await.unwrap().unwrap();
+    let peg_ask = c.order_by_id(peg_ask).await.unwrap().unwrap();
+    assert_eq!(peg_bid.price, bid + 1);
+    assert_eq!(peg_ask.price, ask - 1);
+    assert_eq!(c.trades_count().await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn events_carry_increasing_sequence_numbers() {
+    let (env, program) = setup_orderbook_with_env(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+
+    let mut listener = env.listen(Some).await.unwrap();
+    // BestAskChanged
+    c.submit_order(
+        Side::Sell,
+        OrderType::Limit,
+        price_fp_usdt_per_eth(2_000),
+        eth_frac(1, 2),
+        0,
+    )
+    .with_actor_id(seller())
+    .await
+    .unwrap();
+    // BudgetRefunded, then BestAskChanged
+    c.submit_order(
+        Side::Buy,
+        OrderType::Market,
+        0,
+        eth_frac(1, 10),
+        usdt_micro(1_000),
+    )
+    .with_actor_id(buyer())
+    .await
+    .unwrap();
+
+    // Plain sails events lead with the (service, event) names, then `seq`.
+    let mut seqs = Vec::new();
+    for _ in 0..6 {
+        let (_, payload) = listener.next().await.unwrap();
+        if let Ok((service, _event, seq)) = <(String, String, u64)>::decode(&mut payload.as_slice())
+        {
+            if service == "Orderbook" {
+                seqs.push(seq);
+            }
+        }
+    }
+    assert_eq!(seqs.len(), 3);
+    assert!(seqs.windows(2).all(|w| w[1] == w[0] + 1), "{seqs:?}");
+}
+
+#[tokio::test]
+async fn unfillable_fok_emits_order_rejected() {
+    let (env, program) = setup_orderbook_with_env(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let price = price_fp_usdt_per_eth(2_000);
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.submit_order(Side::Sell, OrderType::Limit, price, eth_frac(1, 10), 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+
+    let mut listener = env.listen(Some).await.unwrap();
+    let requested = eth_frac(2, 10);
+    c.submit_order(Side::Buy, OrderType::FillOrKill, price, requested, 0)
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+
+    // The book is untouched, so the rejection is the only event (eth and plain copies).
+    let mut rejected = None;
+    for _ in 0..2 {
+        let (_, payload) = listener.next().await.unwrap();
+        // `reason` is a one-byte enum index; 0 is FillOrKillUnfillable.
+        if let Ok((service, event, _seq, reason, side, requested_base)) =
+            <(String, String, u64, u8, Side, u128)>::decode(&mut payload.as_slice())
+        {
+            if service == "Orderbook" {
+                assert_eq!(event, "OrderRejected");
+                rejected = Some((reason, side, requested_base));
+            }
+        }
+    }
+    assert_eq!(rejected, Some((0, Side::Buy, requested)));
+    assert_balance(&program, buyer(), 0, usdt_micro(10_000)).await;
+}
+
+#[tokio::test]
+async fn net_base_exposure_nets_resting_buys_and_sells() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    c.deposit(buyer(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+
+    // 1 ETH available; bid 0.5 ETH, offer 0.3 ETH (locked out of available base).
+    c.submit_order(
+        Side::Buy,
+        OrderType::Limit,
+        price_fp_usdt_per_eth(1_990),
+        eth_frac(5, 10),
+        0,
+    )
+    .with_actor_id(buyer())
+    .await
+    .unwrap();
+    c.submit_order(
+        Side::Sell,
+        OrderType::Limit,
+        price_fp_usdt_per_eth(2_010),
+        eth_frac(3, 10),
+        0,
+    )
+    .with_actor_id(buyer())
+    .await
+    .unwrap();
+
+    let expected = eth_frac(7, 10) as i128 + eth_frac(5, 10) as i128 - eth_frac(3, 10) as i128;
+    assert_eq!(c.net_base_exposure(buyer()).await.unwrap(), expected);
+    assert_eq!(c.net_base_exposure(seller()).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn pause_blocks_submits_but_not_cancels() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let price = price_fp_usdt_per_eth(2_000);
+    let ask_amount = eth_frac(1, 10); // 0.1 ETH
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    let ask = c
+        .submit_order(Side::Sell, OrderType::Limit, price, ask_amount, 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+
+    let res = c.pause(true).with_actor_id(seller()).await;
+    assert!(res.is_err(), "Expected non-admin pause to fail");
+    assert!(!c.is_paused().await.unwrap());
+
+    c.pause(true)
+        .with_actor_id(ActorId::from(ADMIN_ID))
+        .await
+        .unwrap();
+    assert!(c.is_paused().await.unwrap());
+
+    let res = c
+        .submit_order(Side::Sell, OrderType::Limit, price, ask_amount, 0)
+        .with_actor_id(seller())
+        .await;
+    assert!(res.is_err(), "Expected submit to fail while paused");
+
+    // Pulling liquidity is still allowed.
+    c.cancel_order(ask).with_actor_id(seller()).await.unwrap();
+    assert_balance(&program, seller(), eth_wei(1), 0).await;
+
+    c.pause(false)
+        .with_actor_id(ActorId::from(ADMIN_ID))
+        .await
+        .unwrap();
+    c.submit_order(Side::Sell, OrderType::Limit, price, ask_amount, 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    assert_eq!(c.best_ask_price().await.unwrap(), price);
+}
+
+#[tokio::test]
+async fn fee_is_withheld_from_the_quote_receiving_side() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let res = c.set_fee_rate_bps(30).with_actor_id(seller()).await;
+    assert!(res.is_err(), "Expected non-admin fee change to fail");
+    c.set_fee_rate_bps(30)
+        .with_actor_id(ActorId::from(ADMIN_ID))
+        .await
+        .unwrap();
+    assert_eq!(c.fee_rate_bps().await.unwrap(), 30);
+
+    let price = price_fp_usdt_per_eth(2_000);
+    let amount = eth_frac(1, 10);
+    let quote = quote_floor_atoms(amount, price);
+    let fee = quote * 30 / 10_000;
+
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+
+    // Taker buys from a resting ask: the maker receives quote and pays the fee.
+    c.submit_order(Side::Sell, OrderType::Limit, price, amount, 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    c.submit_order(Side::Buy, OrderType::Market, 0, amount, usdt_micro(10_000))
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+    assert_balance(&program, seller(), eth_wei(1) - amount, quote - fee).await;
+    assert_balance(&program, buyer(), amount, usdt_micro(10_000) - quote).await;
+    assert_eq!(c.protocol_fee_quote().await.unwrap(), fee);
+
+    // Taker sells into a resting bid: now the taker receives quote and pays.
+    c.submit_order(Side::Buy, OrderType::Limit, price, amount, 0)
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+    c.submit_order(Side::Sell, OrderType::Limit, price, amount, 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    assert_balance(
+        &program,
+        seller(),
+        eth_wei(1) - 2 * amount,
+        2 * (quote - fee),
+    )
+    .await;
+    assert_balance(
+        &program,
+        buyer(),
+        2 * amount,
+        usdt_micro(10_000) - 2 * quote,
+    )
+    .await;
+    assert_eq!(c.protocol_fee_quote().await.unwrap(), 2 * fee);
+}
+
+#[tokio::test]
+async fn admin_claims_accrued_fees_into_their_quote_balance() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+    let admin = ActorId::from(ADMIN_ID);
+
+    c.set_fee_rate_bps(30).with_actor_id(admin).await.unwrap();
+
+    let price = price_fp_usdt_per_eth(2_000);
+    let amount = eth_frac(1, 10);
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.submit_order(Side::Sell, OrderType::Limit, price, amount, 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    c.submit_order(Side::Buy, OrderType::Market, 0, amount, usdt_micro(10_000))
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+
+    let fee = quote_floor_atoms(amount, price) * 30 / 10_000;
+    assert_eq!(c.protocol_fee_quote().await.unwrap(), fee);
+
+    let res = c.claim_protocol_fees().with_actor_id(seller()).await;
+    assert!(res.is_err(), "Expected non-admin claim to fail");
+    assert_eq!(c.protocol_fee_quote().await.unwrap(), fee);
+
+    assert_balance(&program, admin, 0, 0).await;
+    let claimed = c.claim_protocol_fees().with_actor_id(admin).await.unwrap();
+    assert_eq!(claimed, fee);
+    assert_balance(&program, admin, 0, fee).await;
+    assert_eq!(c.protocol_fee_quote().await.unwrap(), 0);
+
+    // Nothing left to claim the second time round.
+    assert_eq!(
+        c.claim_protocol_fees().with_actor_id(admin).await.unwrap(),
+        0
+    );
+    assert_balance(&program, admin, 0, fee).await;
+}
+
+#[tokio::test]
+async fn admin_handover_needs_acceptance_by_the_latest_proposal() {
+    let (env, program) = setup_orderbook_with_env(1000, 1000).await;
+    let mut c = program.orderbook();
+    let admin = ActorId::from(ADMIN_ID);
+
+    let res = c.propose_admin(seller()).with_actor_id(buyer()).await;
+    assert!(res.is_err(), "Expected non-admin proposal to fail");
+
+    c.propose_admin(seller())
+        .with_actor_id(admin)
+        .await
+        .unwrap();
+    // A second proposal replaces the first before it is accepted.
+    c.propose_admin(buyer()).with_actor_id(admin).await.unwrap();
+    let res = c.accept_admin().with_actor_id(seller()).await;
+    assert!(res.is_err(), "Expected the replaced proposal to be refused");
+    let res = c.accept_admin().with_actor_id(admin).await;
+    assert!(
+        res.is_err(),
+        "Expected acceptance by the current admin to fail"
+    );
+
+    let mut listener = env.listen(Some).await.unwrap();
+    c.accept_admin().with_actor_id(buyer()).await.unwrap();
+    let mut changed = None;
+    for _ in 0..2 {
+        let (_, payload) = listener.next().await.unwrap();
+        if let Ok((service, event, _seq, previous, new)) =
+            <(String, String, u64, [u8; 20], [u8; 20])>::decode(&mut payload.as_slice())
+        {
+            if service == "Orderbook" && event == "AdminChanged" {
+                changed = Some((previous, new));
+                break;
+            }
+        }
+    }
+    assert_eq!(
+        changed,
+        Some((
+            clob_common::actor_to_eth(admin),
+            clob_common::actor_to_eth(buyer())
+        ))
+    );
+
+    let res = c.pause(true).with_actor_id(admin).await;
+    assert!(res.is_err(), "Expected the previous admin to lose access");
+    c.pause(true).with_actor_id(buyer()).await.unwrap();
+    assert!(c.is_paused().await.unwrap());
+    let res = c.accept_admin().with_actor_id(buyer()).await;
+    assert!(res.is_err(), "Expected the pending slot to be cleared");
+}
+
+#[tokio::test]
+async fn emergency_exit_debits_free_balance_only_while_paused() {
+    let (env, program) = setup_orderbook_with_env(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(1_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    // 200 USDT of it is reserved by a resting bid.
+    c.submit_order(
+        Side::Buy,
+        OrderType::Limit,
+        price_fp_usdt_per_eth(2_000),
+        eth_frac(1, 10),
+        0,
+    )
+    .with_actor_id(buyer())
+    .await
+    .unwrap();
+
+    let res = c
+        .register_emergency_exit(QUOTE_TOKEN_ID, usdt_micro(100))
+        .with_actor_id(buyer())
+        .await;
+    assert!(
+        res.is_err(),
+        "Expected emergency exit to fail while trading"
+    );
+
+    c.pause(true)
+        .with_actor_id(ActorId::from(ADMIN_ID))
+        .await
+        .unwrap();
+
+    let res = c
+        .register_emergency_exit(QUOTE_TOKEN_ID, usdt_micro(900))
+        .with_actor_id(buyer())
+        .await;
+    assert!(res.is_err(), "Expected exit above the free balance to fail");
+    let res = c
+        .register_emergency_exit([99u8; 20], usdt_micro(100))
+        .with_actor_id(buyer())
+        .await;
+    assert!(res.is_err(), "Expected exit in a foreign token to fail");
+
+    let mut listener = env.listen(Some).await.unwrap();
+    c.register_emergency_exit(QUOTE_TOKEN_ID, usdt_micro(300))
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+
+    let mut exit = None;
+    for _ in 0..2 {
+        let (_, payload) = listener.next().await.unwrap();
+        if let Ok((service, event, _seq, _user, token, amount)) =
+            <(String, String, u64, [u8; 20], [u8; 20], u128)>::decode(&mut payload.as_slice())
+        {
+            if service == "Orderbook" {
+                assert_eq!(event, "EmergencyExit");
+                exit = Some((token, amount));
+            }
+        }
+    }
+    assert_eq!(exit, Some((QUOTE_TOKEN_ID, usdt_micro(300))));
+    assert_balance(&program, buyer(), 0, usdt_micro(500)).await;
+}
+
+#[tokio::test]
+async fn pause_with_cancel_on_pause_empties_book_and_refunds() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+    let admin = ActorId::from(ADMIN_ID);
+
+    let res = c.set_cancel_on_pause(true).with_actor_id(seller()).await;
+    assert!(res.is_err(), "Expected non-admin to be refused");
+    c.set_cancel_on_pause(true)
+        .with_actor_id(admin)
+        .await
+        .unwrap();
+    assert!(c.cancel_on_pause().await.unwrap());
+
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    for usdt in [2_000, 2_010, 2_020] {
+        c.submit_order(
+            Side::Sell,
+            OrderType::Limit,
+            price_fp_usdt_per_eth(usdt),
+            eth_frac(1, 10),
+            0,
+        )
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    }
+    for usdt in [1_900, 1_890] {
+        c.submit_order(
+            Side::Buy,
+            OrderType::Limit,
+            price_fp_usdt_per_eth(usdt),
+            eth_frac(1, 10),
+            0,
+        )
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+    }
+
+    assert_eq!(c.pause(true).with_actor_id(admin).await.unwrap(), 5);
+    // Nothing left, so another round finds nothing to cancel.
+    assert_eq!(c.pause(true).with_actor_id(admin).await.unwrap(), 0);
+    assert!(c.is_paused().await.unwrap());
+
+    assert_eq!(c.best_bid_price().await.unwrap(), 0);
+    assert_eq!(c.best_ask_price().await.unwrap(), 0);
+    assert!(c.open_orders_of(seller()).await.unwrap().is_empty());
+    assert!(c.open_orders_of(buyer()).await.unwrap().is_empty());
+    assert_balance(&program, seller(), eth_wei(1), 0).await;
+    assert_balance(&program, buyer(), 0, usdt_micro(10_000)).await;
+}
+
+#[tokio::test]
+async fn admin_zero_balance_writes_off_free_balance_only_while_paused() {
+    let (env, program) = setup_orderbook_with_env(1000, 1000).await;
+    let mut c = program.orderbook();
+    let admin = ActorId::from(ADMIN_ID);
+
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(1_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    // 200 USDT of it is reserved by a resting bid.
+    c.submit_order(
+        Side::Buy,
+        OrderType::Limit,
+        price_fp_usdt_per_eth(2_000),
+        eth_frac(1, 10),
+        0,
+    )
+    .with_actor_id(buyer())
+    .await
+    .unwrap();
+
+    let res = c
+        .admin_zero_balance(buyer(), QUOTE_TOKEN_ID)
+        .with_actor_id(admin)
+        .await;
+    assert!(res.is_err(), "Expected zeroing to fail while trading");
+
+    c.pause(true).with_actor_id(admin).await.unwrap();
+
+    let res = c
+        .admin_zero_balance(buyer(), QUOTE_TOKEN_ID)
+        .with_actor_id(buyer())
+        .await;
+    assert!(res.is_err(), "Expected non-admin zeroing to fail");
+
+    let mut listener = env.listen(Some).await.unwrap();
+    c.admin_zero_balance(buyer(), QUOTE_TOKEN_ID)
+        .with_actor_id(admin)
+        .await
+        .unwrap();
+
+    let mut zeroed = None;
+    for _ in 0..2 {
+        let (_, payload) = listener.next().await.unwrap();
+        if let Ok((service, event, _seq, user, token, amount)) =
+            <(String, String, u64, [u8; 20], [u8; 20], u128)>::decode(&mut payload.as_slice())
+        {
+            if service == "Orderbook" {
+                assert_eq!(event, "BalanceZeroed");
+                zeroed = Some((user, token, amount));
+            }
+        }
+    }
+    assert_eq!(
+        zeroed,
+        Some((actor_to_eth(buyer()), QUOTE_TOKEN_ID, usdt_micro(800)))
+    );
+    // The reserved quote still backs the bid.
+    assert_balance(&program, buyer(), 0, 0).await;
+    assert_eq!(
+        c.best_bid_price().await.unwrap(),
+        price_fp_usdt_per_eth(2_000)
+    );
+}
+
+#[tokio::test]
+async fn cancel_cooldown_throttles_repeated_cancels() {
+    let (env, program) = setup_orderbook_with_env(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    c.set_cancel_cooldown(5)
+        .with_actor_id(ActorId::from(ADMIN_ID))
+        .await
+        .unwrap();
+    assert_eq!(c.cancel_cooldown().await.unwrap(), 5);
+
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    let mut ids = Vec::new();
+    for usdt in [2_010, 2_020, 2_030] {
+        let id = c
+            .submit_order(
+                Side::Sell,
+                OrderType::Limit,
+                price_fp_usdt_per_eth(usdt),
+                eth_frac(1, 10),
+                0,
+            )
+            .with_actor_id(seller())
+            .await
+            .unwrap();
+        ids.push(id);
+    }
+
+    c.cancel_order(ids[0])
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    // Placing stays unthrottled, cancelling again right away does not.
+    c.submit_order(
+        Side::Sell,
+        OrderType::Limit,
+        price_fp_usdt_per_eth(2_040),
+        eth_frac(1, 10),
+        0,
+    )
+    .with_actor_id(seller())
+    .await
+    .unwrap();
+    let res = c.cancel_order(ids[1]).with_actor_id(seller()).await;
+    assert!(res.is_err(), "cancel within the cooldown must be rejected");
+
+    for _ in 0..5 {
+        env.system().run_next_block();
+    }
+    c.cancel_order(ids[1])
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    assert!(c.order_by_id(ids[1]).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn order_by_id_reports_the_block_each_order_started_resting() {
+    let (env, program) = setup_orderbook_with_env(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    let first = c
+        .submit_order(
+            Side::Sell,
+            OrderType::Limit,
+            price_fp_usdt_per_eth(2_000),
+            eth_frac(1, 10),
+            0,
+        )
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    for _ in 0..3 {
+        env.system().run_next_block();
+    }
+    let second = c
+        .submit_order(
+            Side::Sell,
+            OrderType::Limit,
+            price_fp_usdt_per_eth(2_010),
+            eth_frac(1, 10),
+            0,
+        )
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+
+    let first_at = c.order_by_id(first).await.unwrap().unwrap().created_at;
+    let second_at = c.order_by_id(second).await.unwrap().unwrap().created_at;
+    assert!(first_at > 0);
+    assert!(
+        second_at > first_at,
+        "{second_at} should be after {first_at}"
+    );
+
+    let (page, _) = c.all_orders(0, 10).await.unwrap();
+    let listed: Vec<_> = page.iter().map(|o| (o.id, o.created_at)).collect();
+    assert_eq!(listed, vec![(first, first_at), (second, second_at)]);
+}
+
+#[tokio::test]
+async fn reduce_only_limit_refunds_its_remainder_instead_of_resting() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+    let price = price_fp_usdt_per_eth(2_000);
+
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.submit_order(Side::Sell, OrderType::Limit, price, eth_frac(1, 10), 0)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+
+    // Asks for 0.3 ETH against 0.1 ETH of liquidity: the crossing part fills, the rest goes back.
+    let order_id = c
+        .submit_reduce_only(Side::Buy, price, eth_frac(3, 10))
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+
+    let trades = c.trades(0, 10).await.unwrap();
+    assert_eq!(trades.len(), 1);
+    assert!(c.order_by_id(order_id).await.unwrap().is_none());
+    assert!(c.open_orders_of(buyer()).await.unwrap().is_empty());
+    assert_eq!(c.best_bid_price().await.unwrap(), 0);
+    assert_balance(
+        &program,
+        buyer(),
+        eth_frac(1, 10),
+        usdt_micro(10_000) - usdt_micro(200),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn order_lifetime_reports_submission_and_expiry() {
+    let (env, program) = setup_orderbook_with_env(1000, 1000).await;
+    let mut c = program.orderbook();
+    let amount = eth_frac(1, 10);
+
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+
+    let submitted_at = env.system().block_timestamp();
+    let expires_at = submitted_at + 60_000;
+    let ask = c
+        .submit_limit_until(Side::Sell, price_fp_usdt_per_eth(2_000), amount, expires_at)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    let accepted_at = env.system().block_timestamp();
+
+    let (found, created_at, lifetime_expiry, now) = c.order_lifetime(ask).await.unwrap();
+    assert!(found);
+    // Stamped with the timestamp of the block that took the order, on the same clock
+    // as the expiry.
+    assert!(created_at >= submitted_at && created_at <= accepted_at);
+    assert_eq!(lifetime_expiry, expires_at);
+    assert!(lifetime_expiry - created_at <= 60_000);
+    assert!(now >= created_at && now < expires_at);
+
+    env.system().run_next_block();
+    let (_, _, _, later) = c.order_lifetime(ask).await.unwrap();
+    assert!(later > now, "the clock should follow the block timestamp");
+
+    c.cancel_order(ask).with_actor_id(seller()).await.unwrap();
+    let (found, created_at, lifetime_expiry, _) = c.order_lifetime(ask).await.unwrap();
+    assert!(!found);
+    assert_eq!((created_at, lifetime_expiry), (0, 0));
+}
+
+#[tokio::test]
+async fn expired_orders_are_skipped_by_takers_and_purged_by_keepers() {
+    let (env, program) = setup_orderbook_with_env(1000, 1000).await;
+    let mut c = program.orderbook();
+    let amount = eth_frac(1, 10);
+
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+
+    let now = env.system().block_timestamp();
+    let res = c
+        .submit_limit_until(Side::Sell, price_fp_usdt_per_eth(2_000), amount, now)
+        .with_actor_id(seller())
+        .await;
+    assert!(res.is_err(), "Expected an expiry in the past to be refused");
+
+    let expires_at = now + 60_000;
+    let stale_ask = c
+        .submit_limit_until(Side::Sell, price_fp_usdt_per_eth(2_000), amount, expires_at)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    let live_ask = c
+        .submit_order(
+            Side::Sell,
+            OrderType::Limit,
+            price_fp_usdt_per_eth(2_010),
+            amount,
+            0,
+        )
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    let stale_bid = c
+        .submit_limit_until(Side::Buy, price_fp_usdt_per_eth(1_900), amount, expires_at)
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+    let live_bid = c
+        .submit_order(
+            Side::Buy,
+            OrderType::Limit,
+            price_fp_usdt_per_eth(1_890),
+            amount,
+            0,
+        )
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+    let view = c.order_by_id(stale_ask).await.unwrap().unwrap();
+    assert_eq!(view.expires_at, expires_at);
+
+    while env.system().block_timestamp() <= expires_at {
+        env.system().run_next_block();
+    }
+
+    // Lazy skip: the buy passes over the lapsed 2000 ask and fills the live one at 2010.
+    c.submit_order(
+        Side::Buy,
+        OrderType::Limit,
+        price_fp_usdt_per_eth(2_010),
+        amount,
+        0,
+    )
+    .with_actor_id(buyer())
+    .await
+    .unwrap();
+    let trades = c.trades(0, 10).await.unwrap();
+    assert_eq!(trades.len(), 1);
+    assert_eq!(trades[0].1, live_ask);
+    assert!(c.order_by_id(stale_ask).await.unwrap().is_none());
+    let sold_quote = quote_floor_atoms(amount, price_fp_usdt_per_eth(2_010));
+    assert_balance(&program, seller(), eth_wei(1) - amount, sold_quote).await;
+
+    // Proactive purge: anyone can clear the lapsed bid; the live one stays.
+    let purged = c.purge_expired(10).with_actor_id(seller2()).await.unwrap();
+    assert_eq!(purged, 1);
+    assert!(c.order_by_id(stale_bid).await.unwrap().is_none());
+    assert!(c.order_by_id(live_bid).await.unwrap().is_some());
+    assert_eq!(
+        c.best_bid_price().await.unwrap(),
+        price_fp_usdt_per_eth(1_890)
+    );
+    assert_balance(
+        &program,
+        buyer(),
+        amount,
+        usdt_micro(10_000) - sold_quote - quote_ceil_atoms(amount, price_fp_usdt_per_eth(1_890)),
+    )
+    .await;
+    assert_eq!(
+        c.purge_expired(10).with_actor_id(seller2()).await.unwrap(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn min_order_lifetime_holds_back_early_cancels() {
+    let (env, program) = setup_orderbook_with_env(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    c.set_min_order_lifetime(3)
+        .with_actor_id(ActorId::from(ADMIN_ID))
+        .await
+        .unwrap();
+    assert_eq!(c.min_order_lifetime().await.unwrap(), 3);
+
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    let ask_id = c
+        .submit_order(
+            Side::Sell,
+            OrderType::Limit,
+            price_fp_usdt_per_eth(2_010),
+            eth_frac(1, 10),
+            0,
+        )
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+
+    let res = c.cancel_order(ask_id).with_actor_id(seller()).await;
+    assert!(
+        res.is_err(),
+        "cancel before the minimum lifetime must be rejected"
+    );
+    let res = c
+        .reduce_order(ask_id, eth_frac(1, 20))
+        .with_actor_id(seller())
+        .await;
+    assert!(
+        res.is_err(),
+        "reduce before the minimum lifetime must be rejected"
+    );
+    assert!(c.order_by_id(ask_id).await.unwrap().is_some());
+
+    for _ in 0..3 {
+        env.system().run_next_block();
+    }
+    c.cancel_order(ask_id)
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    assert!(c.order_by_id(ask_id).await.unwrap().is_none());
+    assert_balance(&program, seller(), eth_wei(1), 0).await;
+}
+
+#[tokio::test]
+async fn preview_limit_rest_matches_partial_fill_then_rest() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    for usdt in [2_000, 2_010, 2_050] {
+        c.submit_order(
+            Side::Sell,
+            OrderType::Limit,
+            price_fp_usdt_per_eth(usdt),
+            eth_frac(1, 10),
+            0,
+        )
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    }
+
+    let limit = price_fp_usdt_per_eth(2_020);
+    let amount = eth_frac(1, 2);
+    let (filled, rest, rest_price) = c
+        .preview_limit_rest(Side::Buy, limit, amount)
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+    assert_eq!(filled, eth_frac(1, 5));
+    assert_eq!(rest, amount - filled);
+    assert_eq!(rest_price, limit);
+
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    let bid_id = c
+        .submit_order(Side::Buy, OrderType::Limit, limit, amount, 0)
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+
+    let resting = c
+        .order_by_id(bid_id)
+        .await
+        .unwrap()
+        .expect("remainder rests");
+    assert_eq!(resting.price, rest_price);
+    assert_eq!(resting.remaining_base, rest);
+    assert_balance(
+        &program,
+        buyer(),
+        filled,
+        usdt_micro(10_000)
+            - quote_floor_atoms(eth_frac(1, 10), price_fp_usdt_per_eth(2_000))
+            - quote_floor_atoms(eth_frac(1, 10), price_fp_usdt_per_eth(2_010))
+            - resting.reserved_quote,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn max_resting_orders_rejects_new_rest_but_not_marketable_orders() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    let res = c.set_max_resting_orders(2).with_actor_id(seller()).await;
+    assert!(
+        res.is_err(),
+        "Expected non-admin set_max_resting_orders to fail"
+    );
+    c.set_max_resting_orders(2).await.unwrap();
+    assert_eq!(c.max_resting_orders().await.unwrap(), 2);
+
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    for usdt in [2_010, 2_020] {
+        c.submit_order(
+            Side::Sell,
+            OrderType::Limit,
+            price_fp_usdt_per_eth(usdt),
+            eth_frac(1, 10),
+            0,
+        )
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    }
+
+    let res = c
+        .submit_order(
+            Side::Sell,
+            OrderType::Limit,
+            price_fp_usdt_per_eth(2_030),
+            eth_frac(1, 10),
+            0,
+        )
+        .with_actor_id(seller())
+        .await;
+    assert!(
+        res.is_err(),
+        "Expected a third resting order to be rejected"
+    );
+    assert_eq!(c.book_stats().await.unwrap(), (0, 0, 2, 2));
+    assert_balance(&program, seller(), eth_wei(1) - eth_frac(2, 10), 0).await;
+
+    // A limit buy that fills completely never rests, so the cap does not apply.
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    c.submit_order(
+        Side::Buy,
+        OrderType::Limit,
+        price_fp_usdt_per_eth(2_010),
+        eth_frac(1, 10),
+        0,
+    )
+    .with_actor_id(buyer())
+    .await
+    .unwrap();
+    assert_eq!(c.trades_count().await.unwrap(), 1);
+    assert_eq!(c.book_stats().await.unwrap(), (0, 0, 1, 1));
+}
+
+#[tokio::test]
+async fn max_resting_orders_keeps_fills_of_a_partly_marketable_order() {
+    let program = setup_orderbook(1000, 1000).await;
+    let mut c = program.orderbook();
+
+    c.deposit(seller(), BASE_TOKEN_ID, eth_wei(1))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    for usdt in [2_010, 2_020] {
+        c.submit_order(
+            Side::Sell,
+            OrderType::Limit,
+            price_fp_usdt_per_eth(usdt),
+            eth_frac(1, 10),
+            0,
+        )
+        .with_actor_id(seller())
+        .await
+        .unwrap();
+    }
+    // The cap drops below what the book already holds.
+    c.set_max_resting_orders(1).await.unwrap();
+
+    // Takes the 2010 ask and would rest 0.05 ETH next to the 2020 one, past the cap.
+    c.deposit(buyer(), QUOTE_TOKEN_ID, usdt_micro(10_000))
+        .with_actor_id(vault())
+        .await
+        .unwrap();
+    let bid = c
+        .submit_order(
+            Side::Buy,
+            OrderType::Limit,
+            price_fp_usdt_per_eth(2_010),
+            eth_frac(15, 100),
+            0,
+        )
+        .with_actor_id(buyer())
+        .await
+        .unwrap();
+
+    assert_eq!(c.trades_count().await.unwrap(), 1);
+    assert!(c.order_by_id(bid).await.unwrap().is_none());
+    assert_eq!(c.book_stats().await.unwrap(), (0, 0, 1, 1));
+    assert_eq!(c.best_bid_price().await.unwrap(), 0);
+    // The fill stands and the unfilled reservation is back.
+    assert_balance(
+        &program,
+        buyer(),
+        eth_frac(1, 10),
+        usdt_micro(10_000) - usdt_micro(201),
+    )
+    .await;
+}
@@ -1,13 +1,19 @@
 use clob_common::{eth_to_actor, TokenId};
 use orderbook_client::orderbook::Orderbook; // Explicit trait import
-use orderbook_client::{orderbook::OrderbookImpl, OrderbookCtors, OrderbookProgram};
+use orderbook_client::{
+    orderbook::OrderbookImpl, DepositError, OrderType, OrderbookCtors, OrderbookProgram, Side,
+};
 use sails_rs::{
     client::{Deployment, GtestEnv, Service},
-    gtest::{Program, System},
+    gtest::{Program, System, WasmProgram},
     hex,
     prelude::*,
     ActorId,
 };
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
 
 pub(crate) const ORDERBOOK_WASM: &str = "../../target/wasm32-gear/release/orderbook.opt.wasm";
 pub(crate) const VAULT_WASM: &str = "../../target/wasm32-gear/release/vault_app.opt.wasm";
@@ -150,6 +156,26 @@ fn get_vault_balance(system: &System, vault_id: ActorId, user: ActorId) -> u128
     })
 }
 
+// Helper to query how much of `token` the vault counts against its deposit cap
+fn get_total_deposited(system: &System, vault_id: ActorId, token: TokenId) -> u128 {
+    let payload = ("Vault", "TotalDeposited", (token,)).encode();
+    let program = system
+        .get_program(vault_id)
+        .expect("Vault program not found");
+    let mid = program.send_bytes(ADMIN_ID, payload);
+    let res = system.run_next_block();
+    assert!(res.succeed.contains(&mid));
+
+    let log = res
+        .log
+        .iter()
+        .find(|l| l.destination() == ADMIN_ID.into() && l.source() == vault_id)
+        .expect("No reply log found");
+    let (_, _, total) =
+        <(String, String, u128)>::decode(&mut log.payload()).expect("Bad TotalDeposited reply");
+    total
+}
+
 #[tokio::test]
 async fn test_transfer_to_market_insufficient_funds() {
     let (remoting, _base_vault_id, quote_vault_id, orderbook_id) = setup_programs().await;
@@ -205,11 +231,13 @@ async fn test_orderbook_deposit_unauthorized_direct() {
     let (remoting, _base_vault_id, _quote_vault_id, orderbook_id) = setup_programs().await;
     let mut orderbook_buyer = orderbook_service_for(&remoting, orderbook_id, buyer());
 
-    // Buyer tries to call deposit directly on OrderBook (fails)
-    orderbook_buyer
+    // Buyer tries to call deposit directly on OrderBook (refused, nothing credited)
+    let res = orderbook_buyer
         .deposit(buyer(), TOKEN_QUOTE, 100u128)
         .await
-        .unwrap_err();
+        .unwrap();
+    assert_eq!(res, Err(DepositError::UnauthorizedCaller));
+    assert_eq!(orderbook_buyer.balance_of(buyer()).await.unwrap(), (0, 0));
 }
 
 #[tokio::test]
@@ -254,6 +282,239 @@ async fn test_full_cycle_consistency() {
     assert_eq!(ob_quote_after, 250);
 }
 
+#[tokio::test]
+async fn test_market_round_trip_is_not_counted_twice_against_deposit_cap() {
+    let (remoting, _base_vault_id, quote_vault_id, orderbook_id) = setup_programs().await;
+    let system = remoting.system();
+    let mut orderbook_buyer = orderbook_service_for(&remoting, orderbook_id, buyer());
+
+    send_vault(
+        system,
+        ADMIN_ID,
+        quote_vault_id,
+        "SetDepositCap",
+        (TOKEN_QUOTE, 1000u128),
+    );
+    send_vault(
+        system,
+        ADMIN_ID,
+        quote_vault_id,
+        "VaultDeposit",
+        (buyer(), 1000u128),
+    );
+
+    // Vault -> market takes the funds off the total ...
+    send_vault(
+        system,
+        BUYER_ID,
+        quote_vault_id,
+        "TransferToMarket",
+        (orderbook_id, 400u128),
+    );
+    assert_eq!(
+        get_total_deposited(system, quote_vault_id, TOKEN_QUOTE),
+        600
+    );
+
+    // ... so market -> vault fits back under the cap.
+    orderbook_buyer.withdraw_quote(400u128).await.unwrap();
+    assert_eq!(get_vault_balance(system, quote_vault_id, buyer()), 1000);
+    assert_eq!(
+        get_total_deposited(system, quote_vault_id, TOKEN_QUOTE),
+        1000
+    );
+
+    // A full round trip works just as well, repeatedly.
+    send_vault(
+        system,
+        BUYER_ID,
+        quote_vault_id,
+        "TransferToMarket",
+        (orderbook_id, 1000u128),
+    );
+    orderbook_buyer.withdraw_quote(1000u128).await.unwrap();
+    assert_eq!(get_vault_balance(system, quote_vault_id, buyer()), 1000);
+    assert_eq!(
+        get_total_deposited(system, quote_vault_id, TOKEN_QUOTE),
+        1000
+    );
+
+    // Fresh deposits are still held to the cap.
+    send_vault_fail(
+        system,
+        ADMIN_ID,
+        quote_vault_id,
+        "VaultDeposit",
+        (buyer(), 1u128),
+    );
+}
+
+#[tokio::test]
+async fn test_market_withdrawal_succeeds_after_deposit_cap_is_lowered() {
+    let (remoting, _base_vault_id, quote_vault_id, orderbook_id) = setup_programs().await;
+    let system = remoting.system();
+    let mut orderbook_buyer = orderbook_service_for(&remoting, orderbook_id, buyer());
+
+    send_vault(
+        system,
+        ADMIN_ID,
+        quote_vault_id,
+        "SetDepositCap",
+        (TOKEN_QUOTE, 1000u128),
+    );
+    send_vault(
+        system,
+        ADMIN_ID,
+        quote_vault_id,
+        "VaultDeposit",
+        (buyer(), 1000u128),
+    );
+    send_vault(
+        system,
+        BUYER_ID,
+        quote_vault_id,
+        "TransferToMarket",
+        (orderbook_id, 1000u128),
+    );
+
+    // Lowering the cap while the funds sit with the market must not trap them there.
+    send_vault(
+        system,
+        ADMIN_ID,
+        quote_vault_id,
+        "SetDepositCap",
+        (TOKEN_QUOTE, 100u128),
+    );
+    orderbook_buyer.withdraw_quote(1000u128).await.unwrap();
+    assert_eq!(get_vault_balance(system, quote_vault_id, buyer()), 1000);
+    assert_eq!(orderbook_buyer.balance_of(buyer()).await.unwrap(), (0, 0));
+    assert_eq!(
+        get_total_deposited(system, quote_vault_id, TOKEN_QUOTE),
+        1000
+    );
+
+    // Deposits from outside are held to the lowered cap.
+    send_vault_fail(
+        system,
+        ADMIN_ID,
+        quote_vault_id,
+        "VaultDeposit",
+        (buyer(), 1u128),
+    );
+}
+
+#[tokio::test]
+async fn test_cancel_and_withdraw_returns_unfilled_quote_to_vault() {
+    let (remoting, base_vault_id, quote_vault_id, orderbook_id) = setup_programs().await;
+    let system = remoting.system();
+    let mut orderbook_buyer = orderbook_service_for(&remoting, orderbook_id, buyer());
+    let mut orderbook_seller = orderbook_service_for(&remoting, orderbook_id, ADMIN_ID.into());
+    // 1 quote atom per base atom.
+    let price = 1_000_000_000_000_000_000_000_000_000_000u128;
+
+    send_vault(
+        system,
+        ADMIN_ID,
+        quote_vault_id,
+        "VaultDeposit",
+        (buyer(), 1000u128),
+    );
+    send_vault(
+        system,
+        BUYER_ID,
+        quote_vault_id,
+        "TransferToMarket",
+        (orderbook_id, 400u128),
+    );
+    send_vault(
+        system,
+        ADMIN_ID,
+        base_vault_id,
+        "VaultDeposit",
+        (ActorId::from(ADMIN_ID), 150u128),
+    );
+    send_vault(
+        system,
+        ADMIN_ID,
+        base_vault_id,
+        "TransferToMarket",
+        (orderbook_id, 150u128),
+    );
+
+    // Buy 400 resting, then partially filled by a 150 sell.
+    let order_id = orderbook_buyer
+        .submit_order(Side::Buy, OrderType::Limit, price, 400, 0)
+        .await
+        .unwrap();
+    orderbook_seller
+        .submit_order(Side::Sell, OrderType::Limit, price, 150, 0)
+        .await
+        .unwrap();
+
+    let withdrawn = orderbook_buyer.cancel_and_withdraw(order_id).await.unwrap();
+    assert_eq!(withdrawn, 250);
+
+    // Order is gone and the unfilled reservation is back in the vault.
+    assert!(orderbook_buyer
+        .order_by_id(order_id)
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(get_vault_balance(system, quote_vault_id, buyer()), 850);
+    let (ob_base, ob_quote) = orderbook_buyer.balance_of(buyer()).await.unwrap();
+    assert_eq!(ob_base, 150);
+    assert_eq!(ob_quote, 0);
+}
+
+#[tokio::test]
+async fn test_fund_and_order_places_order_or_rolls_back() {
+    let (remoting, _base_vault_id, quote_vault_id, orderbook_id) = setup_programs().await;
+    let system = remoting.system();
+    let orderbook_buyer = orderbook_service_for(&remoting, orderbook_id, buyer());
+    // 1 quote atom per base atom.
+    let price = 1_000_000_000_000_000_000_000_000_000_000u128;
+
+    send_vault(
+        system,
+        ADMIN_ID,
+        quote_vault_id,
+        "VaultDeposit",
+        (buyer(), 1000u128),
+    );
+
+    // Funded and placed in one call.
+    send_vault(
+        system,
+        BUYER_ID,
+        quote_vault_id,
+        "FundAndOrder",
+        (
+            orderbook_id,
+            400u128,
+            (Side::Buy, OrderType::Limit, price, 400u128, 0u128),
+        ),
+    );
+    assert_eq!(get_vault_balance(system, quote_vault_id, buyer()), 600);
+    assert_eq!(orderbook_buyer.best_bid_price().await.unwrap(), price);
+    assert_eq!(orderbook_buyer.balance_of(buyer()).await.unwrap(), (0, 0));
+
+    // A zero-size order fails validation: neither the deposit nor the order stick.
+    send_vault(
+        system,
+        BUYER_ID,
+        quote_vault_id,
+        "FundAndOrder",
+        (
+            orderbook_id,
+            300u128,
+            (Side::Buy, OrderType::Limit, price, 0u128, 0u128),
+        ),
+    );
+    assert_eq!(get_vault_balance(system, quote_vault_id, buyer()), 600);
+    assert_eq!(orderbook_buyer.balance_of(buyer()).await.unwrap(), (0, 0));
+    assert_eq!(orderbook_buyer.book_stats().await.unwrap(), (1, 1, 0, 0));
+}
+
 #[tokio::test]
 async fn test_transfer_to_market_rolls_back_when_market_does_not_reply() {
     let (remoting, base_vault_id, quote_vault_id, _orderbook_id) = setup_programs().await;
@@ -304,3 +565,150 @@ async fn test_transfer_to_market_rolls_back_when_market_does_not_reply() {
         "Expected rollback to restore available funds"
     );
 }
+
+/// Market stand-in that fails the first deposit and acks every later one.
+#[derive(Debug, Clone, Default)]
+struct FlakyMarket {
+    deliveries: Arc<AtomicU32>,
+    credits: Arc<AtomicU32>,
+}
+
+impl WasmProgram for FlakyMarket {
+    fn clone_boxed(&self) -> Box<dyn WasmProgram> {
+        Box::new(self.clone())
+    }
+
+    fn init(&mut self, _payload: Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        Ok(None)
+    }
+
+    fn handle(&mut self, _payload: Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        if self.deliveries.fetch_add(1, Ordering::SeqCst) == 0 {
+            return Err("transient failure");
+        }
+        self.credits.fetch_add(1, Ordering::SeqCst);
+        Ok(Some(("Orderbook", "Deposit", true).encode()))
+    }
+
+    fn handle_reply(&mut self, _payload: Vec<u8>) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn handle_signal(&mut self, _payload: Vec<u8>) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<u8>, &'static str> {
+        Ok(Vec::new())
+    }
+}
+
+#[tokio::test]
+async fn test_transfer_to_market_retries_once_after_error_reply() {
+    let (remoting, _base_vault_id, quote_vault_id, _orderbook_id) = setup_programs().await;
+    let system = remoting.system();
+
+    let market = FlakyMarket::default();
+    let deliveries = market.deliveries.clone();
+    let credits = market.credits.clone();
+    let market_program = Program::mock(system, market);
+    let market_id = market_program.id();
+    market_program.send_bytes(ADMIN_ID, b"init".to_vec());
+    system.run_next_block();
+
+    send_vault(
+        system,
+        ADMIN_ID,
+        quote_vault_id,
+        "VaultDeposit",
+        (buyer(), 1000u128),
+    );
+    send_vault(system, ADMIN_ID, quote_vault_id, "AddMarket", (market_id,));
+
+    send_vault(
+        system,
+        BUYER_ID,
+        quote_vault_id,
+        "TransferToMarket",
+        (market_id, 400u128),
+    );
+
+    for _ in 0..20 {
+        if deliveries.load(Ordering::SeqCst) >= 2 {
+            break;
+        }
+        system.run_next_block();
+    }
+    system.run_next_block();
+
+    assert_eq!(deliveries.load(Ordering::SeqCst), 2);
+    assert_eq!(credits.load(Ordering::SeqCst), 1);
+    assert_eq!(get_vault_balance(system, quote_vault_id, buyer()), 600);
+}
+
+/// Market stand-in that accepts every message but answers in a shape the vault can't decode.
+#[derive(Debug, Clone, Default)]
+struct GarbledMarket;
+
+impl WasmProgram for GarbledMarket {
+    fn clone_boxed(&self) -> Box<dyn WasmProgram> {
+        Box::new(self.clone())
+    }
+
+    fn init(&mut self, _payload: Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        Ok(None)
+    }
+
+    fn handle(&mut self, _payload: Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        Ok(Some(("Orderbook", "Deposit", true).encode()))
+    }
+
+    fn handle_reply(&mut self, _payload: Vec<u8>) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn handle_signal(&mut self, _payload: Vec<u8>) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<u8>, &'static str> {
+        Ok(Vec::new())
+    }
+}
+
+#[tokio::test]
+async fn test_fund_and_order_keeps_debit_on_undecodable_success_reply() {
+    let (remoting, _base_vault_id, quote_vault_id, _orderbook_id) = setup_programs().await;
+    let system = remoting.system();
+
+    let market_program = Program::mock(system, GarbledMarket);
+    let market_id = market_program.id();
+    market_program.send_bytes(ADMIN_ID, b"init".to_vec());
+    system.run_next_block();
+
+    send_vault(
+        system,
+        ADMIN_ID,
+        quote_vault_id,
+        "VaultDeposit",
+        (buyer(), 1000u128),
+    );
+    send_vault(system, ADMIN_ID, quote_vault_id, "AddMarket", (market_id,));
+
+    // The market accepted the call, so the vault must not credit the amount back.
+    send_vault(
+        system,
+        BUYER_ID,
+        quote_vault_id,
+        "FundAndOrder",
+        (
+            market_id,
+            400u128,
+            (Side::Buy, OrderType::Limit, 1u128, 400u128, 0u128),
+        ),
+    );
+    for _ in 0..5 {
+        system.run_next_block();
+    }
+    assert_eq!(get_vault_balance(system, quote_vault_id, buyer()), 600);
+}
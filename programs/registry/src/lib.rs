@@ -1,8 +1,18 @@
 #![no_std]
 
-use clob_common::TokenId;
+use clob_common::{actor_to_eth, parse_version, TokenId, Version};
 use sails_rs::{cell::RefCell, collections::HashMap, gstd::msg, prelude::*};
 
+/// Routing status of a market. Only the registry entry changes; the orderbook is untouched.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Encode, Decode, TypeInfo)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub enum MarketStatus {
+    Active,
+    Paused,
+    Delisted,
+}
+
 #[derive(Clone, Debug, PartialEq, Encode, Decode, TypeInfo)]
 #[codec(crate = sails_rs::scale_codec)]
 #[scale_info(crate = sails_rs::scale_info)]
@@ -10,12 +20,56 @@ pub struct MarketInfo {
     pub orderbook_id: ActorId,
     pub base_vault_id: ActorId,
     pub quote_vault_id: ActorId,
+    pub status: MarketStatus,
+}
+
+#[sails_rs::event]
+#[derive(Clone, Debug, PartialEq, Encode, TypeInfo)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub enum Events {
+    MarketRegistered {
+        base_token: TokenId,
+        quote_token: TokenId,
+        orderbook_id: [u8; 20],
+        base_vault_id: [u8; 20],
+        quote_vault_id: [u8; 20],
+    },
+    /// An existing pair was repointed by `update_market`.
+    MarketUpdated {
+        base_token: TokenId,
+        quote_token: TokenId,
+        orderbook_id: [u8; 20],
+        base_vault_id: [u8; 20],
+        quote_vault_id: [u8; 20],
+    },
+    MarketRemoved {
+        base_token: TokenId,
+        quote_token: TokenId,
+    },
+    /// The pending admin accepted and replaced `previous_admin`.
+    AdminChanged {
+        previous_admin: [u8; 20],
+        new_admin: [u8; 20],
+    },
 }
 
+const VERSION: Version = parse_version(
+    env!("CARGO_PKG_VERSION_MAJOR"),
+    env!("CARGO_PKG_VERSION_MINOR"),
+    env!("CARGO_PKG_VERSION_PATCH"),
+);
+
 #[derive(Default)]
 pub struct RegistryState {
     pub markets: HashMap<(TokenId, TokenId), MarketInfo>,
+    /// Quote tokens listed against each base, in registration order.
+    pub quotes_by_base: HashMap<TokenId, Vec<TokenId>>,
+    /// Display decimals per token, set by the admin.
+    pub token_decimals: HashMap<TokenId, u8>,
     pub admin: Option<ActorId>,
+    /// Proposed by `propose_admin`; becomes admin once it calls `accept_admin`.
+    pub pending_admin: Option<ActorId>,
 }
 
 pub struct RegistryProgram {
@@ -58,10 +112,38 @@ impl<'a> RegistryService<'a> {
     fn get(&self) -> sails_rs::cell::Ref<'_, RegistryState> {
         self.state.borrow()
     }
+
+    /// Whether `vault` holds any available or quarantined `token` for `user`. A vault that
+    /// can't be reached or answers with something unreadable counts as holding nothing.
+    async fn vault_holds(vault: ActorId, user: ActorId, token: TokenId) -> bool {
+        let payload = ("Vault", "GetBalances", (vec![(user, token)],)).encode();
+        let Ok(pending) = msg::send_bytes_for_reply(vault, payload, 0) else {
+            return false;
+        };
+        let Ok(reply) = pending.await else {
+            return false;
+        };
+        <(String, String, Vec<(u128, u128)>)>::decode(&mut reply.as_slice()).is_ok_and(
+            |(_, _, balances)| {
+                balances
+                    .first()
+                    .is_some_and(|(available, quarantined)| *available > 0 || *quarantined > 0)
+            },
+        )
+    }
 }
 
-#[service]
+#[service(events = Events)]
 impl<'a> RegistryService<'a> {
+    /// Emits `event` both as an eth event and as a plain sails event.
+    fn publish(&self, event: Events) {
+        self.emit_eth_event(event.clone()).expect("EmitEventFailed");
+        let mut emitter = self.emitter();
+        emitter.emit_event(event).expect("EmitEventFailed");
+    }
+
+    /// Adds a new pair as `Active`. An already registered pair is refused; repoint it with
+    /// `update_market` instead.
     #[export]
     pub fn register_market(
         &mut self,
@@ -75,6 +157,12 @@ impl<'a> RegistryService<'a> {
         if state.admin != Some(msg::source()) {
             panic!("Unauthorized");
         }
+        if base_token == quote_token {
+            panic!("InvalidTokenPair");
+        }
+        if state.markets.contains_key(&(base_token, quote_token)) {
+            panic!("MarketAlreadyRegistered");
+        }
 
         state.markets.insert(
             (base_token, quote_token),
@@ -82,12 +170,222 @@ impl<'a> RegistryService<'a> {
                 orderbook_id,
                 base_vault_id,
                 quote_vault_id,
+                status: MarketStatus::Active,
             },
         );
+        state
+            .quotes_by_base
+            .entry(base_token)
+            .or_default()
+            .push(quote_token);
+        drop(state);
+
+        self.publish(Events::MarketRegistered {
+            base_token,
+            quote_token,
+            orderbook_id: actor_to_eth(orderbook_id),
+            base_vault_id: actor_to_eth(base_vault_id),
+            quote_vault_id: actor_to_eth(quote_vault_id),
+        });
+    }
+
+    #[export]
+    pub fn set_market_status(
+        &mut self,
+        base_token: TokenId,
+        quote_token: TokenId,
+        status: MarketStatus,
+    ) {
+        let mut state = self.get_mut();
+        if state.admin != Some(msg::source()) {
+            panic!("Unauthorized");
+        }
+        let Some(info) = state.markets.get_mut(&(base_token, quote_token)) else {
+            panic!("MarketNotFound");
+        };
+        info.status = status;
+    }
+
+    /// Repoints an already registered pair, e.g. at an upgraded orderbook. It never creates
+    /// a pair and keeps the market's status.
+    #[export]
+    pub fn update_market(
+        &mut self,
+        base_token: TokenId,
+        quote_token: TokenId,
+        orderbook_id: ActorId,
+        base_vault_id: ActorId,
+        quote_vault_id: ActorId,
+    ) {
+        let mut state = self.get_mut();
+        if state.admin != Some(msg::source()) {
+            panic!("Unauthorized");
+        }
+        let Some(info) = state.markets.get_mut(&(base_token, quote_token)) else {
+            panic!("MarketNotFound");
+        };
+        info.orderbook_id = orderbook_id;
+        info.base_vault_id = base_vault_id;
+        info.quote_vault_id = quote_vault_id;
+        drop(state);
+
+        self.publish(Events::MarketUpdated {
+            base_token,
+            quote_token,
+            orderbook_id: actor_to_eth(orderbook_id),
+            base_vault_id: actor_to_eth(base_vault_id),
+            quote_vault_id: actor_to_eth(quote_vault_id),
+        });
     }
 
+    /// Delists a pair. Returns whether it was registered; removing an unknown pair is a no-op.
+    #[export]
+    pub fn remove_market(&mut self, base_token: TokenId, quote_token: TokenId) -> bool {
+        let mut state = self.get_mut();
+        if state.admin != Some(msg::source()) {
+            panic!("Unauthorized");
+        }
+        if state.markets.remove(&(base_token, quote_token)).is_none() {
+            return false;
+        }
+        if let Some(quotes) = state.quotes_by_base.get_mut(&base_token) {
+            quotes.retain(|quote| *quote != quote_token);
+            if quotes.is_empty() {
+                state.quotes_by_base.remove(&base_token);
+            }
+        }
+        drop(state);
+
+        self.publish(Events::MarketRemoved {
+            base_token,
+            quote_token,
+        });
+        true
+    }
+
+    /// Registered `(base, quote)` pairs where `user` has a non-zero vault balance in either
+    /// token, sorted. Asks each vault once, so it runs as a message rather than a query.
+    #[export]
+    pub async fn markets_with_balance(&mut self, user: ActorId) -> Vec<(TokenId, TokenId)> {
+        let markets: Vec<((TokenId, TokenId), MarketInfo)> = self
+            .get()
+            .markets
+            .iter()
+            .map(|(pair, info)| (*pair, info.clone()))
+            .collect();
+
+        let mut funded: HashMap<ActorId, bool> = HashMap::new();
+        let mut pairs = Vec::new();
+        for ((base, quote), info) in markets {
+            let mut holds = false;
+            for (vault, token) in [(info.base_vault_id, base), (info.quote_vault_id, quote)] {
+                let vault_funded = match funded.get(&vault) {
+                    Some(cached) => *cached,
+                    None => {
+                        let fresh = Self::vault_holds(vault, user, token).await;
+                        funded.insert(vault, fresh);
+                        fresh
+                    }
+                };
+                holds |= vault_funded;
+            }
+            if holds {
+                pairs.push((base, quote));
+            }
+        }
+        pairs.sort();
+        pairs
+    }
+
+    /// First step of an admin handover: records `new_admin`, who then has to call
+    /// `accept_admin`. A new proposal replaces one that wasn't accepted yet.
+    #[export]
+    pub fn propose_admin(&mut self, new_admin: ActorId) {
+        let mut state = self.get_mut();
+        if state.admin != Some(msg::source()) {
+            panic!("Unauthorized");
+        }
+        state.pending_admin = Some(new_admin);
+    }
+
+    /// Completes the handover started by `propose_admin`. Only the proposed admin may call it.
+    #[export]
+    pub fn accept_admin(&mut self) {
+        let caller = msg::source();
+        let previous = {
+            let mut state = self.get_mut();
+            if state.pending_admin != Some(caller) {
+                panic!("Unauthorized");
+            }
+            state.pending_admin = None;
+            state.admin.replace(caller).unwrap_or_default()
+        };
+
+        self.publish(Events::AdminChanged {
+            previous_admin: actor_to_eth(previous),
+            new_admin: actor_to_eth(caller),
+        });
+    }
+
+    #[export]
+    pub fn set_token_decimals(&mut self, token: TokenId, decimals: u8) {
+        let mut state = self.get_mut();
+        if state.admin != Some(msg::source()) {
+            panic!("Unauthorized");
+        }
+        state.token_decimals.insert(token, decimals);
+    }
+
+    #[export]
     pub fn get_market(&self, base_token: TokenId, quote_token: TokenId) -> Option<MarketInfo> {
         let state = self.get();
         state.markets.get(&(base_token, quote_token)).cloned()
     }
+
+    /// Markets frontends should route to, sorted by pair.
+    #[export]
+    pub fn active_markets(&self) -> Vec<((TokenId, TokenId), MarketInfo)> {
+        let mut markets: Vec<_> = self
+            .get()
+            .markets
+            .iter()
+            .filter(|(_, info)| info.status == MarketStatus::Active)
+            .map(|(pair, info)| (*pair, info.clone()))
+            .collect();
+        markets.sort_by_key(|(pair, _)| *pair);
+        markets
+    }
+
+    /// All markets listed for `base`, as `(quote_token, market)` pairs.
+    #[export]
+    pub fn quotes_for_base(&self, base: TokenId) -> Vec<(TokenId, MarketInfo)> {
+        let state = self.get();
+        let Some(quotes) = state.quotes_by_base.get(&base) else {
+            return Vec::new();
+        };
+        quotes
+            .iter()
+            .filter_map(|quote| {
+                let info = state.markets.get(&(base, *quote))?;
+                Some((*quote, info.clone()))
+            })
+            .collect()
+    }
+
+    /// Display decimals of `token`, if the admin has set them.
+    #[export]
+    pub fn get_token_decimals(&self, token: TokenId) -> Option<u8> {
+        self.get().token_decimals.get(&token).copied()
+    }
+
+    #[export]
+    pub fn version(&self) -> Version {
+        VERSION
+    }
+
+    /// True once the program has been created with an admin.
+    #[export]
+    pub fn health(&self) -> bool {
+        self.get().admin.is_some()
+    }
 }
@@ -0,0 +1,454 @@
+use clob_common::eth_to_actor;
+use registry::{MarketInfo, MarketStatus};
+use sails_rs::{
+    gtest::{Program, System},
+    prelude::*,
+    ActorId,
+};
+
+pub(crate) const REGISTRY_WASM: &str = "../../target/wasm32-gear/release/registry.opt.wasm";
+pub(crate) const VAULT_WASM: &str = "../../target/wasm32-gear/release/vault_app.opt.wasm";
+
+pub(crate) const ADMIN_ID: u64 = 100;
+pub(crate) const BASE_TOKEN: [u8; 20] = [1u8; 20];
+pub(crate) const QUOTE_A: [u8; 20] = [2u8; 20];
+pub(crate) const QUOTE_B: [u8; 20] = [3u8; 20];
+pub(crate) const OTHER_BASE: [u8; 20] = [4u8; 20];
+
+fn setup_registry(system: &System) -> Program<'_> {
+    system.init_logger();
+    system.mint_to(ADMIN_ID, 100_000_000_000_000_000);
+
+    let program = Program::from_file(system, REGISTRY_WASM);
+    let mid = program.send_bytes(ADMIN_ID, "Create".encode());
+    let res = system.run_next_block();
+    assert!(res.succeed.contains(&mid), "Registry init failed");
+    program
+}
+
+fn market(n: u64) -> MarketInfo {
+    MarketInfo {
+        orderbook_id: ActorId::from(n),
+        base_vault_id: ActorId::from(n + 1),
+        quote_vault_id: ActorId::from(n + 2),
+        status: MarketStatus::Active,
+    }
+}
+
+// Helper for raw registry commands
+fn send_registry(system: &System, program: &Program<'_>, method: &str, args: impl Encode) {
+    let payload = ("Registry", method, args).encode();
+    let mid = program.send_bytes(ADMIN_ID, payload);
+    let res = system.run_next_block();
+    assert!(
+        res.succeed.contains(&mid),
+        "Registry call {} failed",
+        method
+    );
+}
+
+// Helper for raw registry queries; replies carry the (service, method) prefix.
+fn query_registry<R: Decode>(
+    system: &System,
+    program: &Program<'_>,
+    method: &str,
+    args: impl Encode,
+) -> R {
+    let payload = ("Registry", method, args).encode();
+    let mid = program.send_bytes(ADMIN_ID, payload);
+    let res = system.run_next_block();
+    assert!(
+        res.succeed.contains(&mid),
+        "Registry query {} failed",
+        method
+    );
+
+    let log = res
+        .log
+        .iter()
+        .find(|l| l.destination() == ADMIN_ID.into() && l.source() == program.id())
+        .expect("No reply log found");
+    let (_, _, value) = <(String, String, R)>::decode(&mut log.payload()).expect("Bad reply");
+    value
+}
+
+fn register(
+    system: &System,
+    program: &Program<'_>,
+    base: [u8; 20],
+    quote: [u8; 20],
+    info: &MarketInfo,
+) {
+    send_registry(
+        system,
+        program,
+        "RegisterMarket",
+        (
+            base,
+            quote,
+            info.orderbook_id,
+            info.base_vault_id,
+            info.quote_vault_id,
+        ),
+    );
+}
+
+#[test]
+fn quotes_for_base_lists_every_quote_market() {
+    let system = System::new();
+    let program = setup_registry(&system);
+
+    register(&system, &program, BASE_TOKEN, QUOTE_A, &market(10));
+    register(&system, &program, BASE_TOKEN, QUOTE_B, &market(20));
+    register(&system, &program, OTHER_BASE, QUOTE_A, &market(30));
+    // Repointing a pair updates it without duplicating the index entry.
+    let info = market(40);
+    send_registry(
+        &system,
+        &program,
+        "UpdateMarket",
+        (
+            BASE_TOKEN,
+            QUOTE_A,
+            info.orderbook_id,
+            info.base_vault_id,
+            info.quote_vault_id,
+        ),
+    );
+
+    let quotes: Vec<([u8; 20], MarketInfo)> =
+        query_registry(&system, &program, "QuotesForBase", (BASE_TOKEN,));
+    assert_eq!(quotes, vec![(QUOTE_A, market(40)), (QUOTE_B, market(20))]);
+
+    let none: Vec<([u8; 20], MarketInfo)> =
+        query_registry(&system, &program, "QuotesForBase", (QUOTE_B,));
+    assert!(none.is_empty());
+}
+
+#[test]
+fn token_decimals_are_stored_per_token() {
+    let system = System::new();
+    let program = setup_registry(&system);
+
+    send_registry(&system, &program, "SetTokenDecimals", (BASE_TOKEN, 18u8));
+    send_registry(&system, &program, "SetTokenDecimals", (QUOTE_A, 6u8));
+
+    let base: Option<u8> = query_registry(&system, &program, "GetTokenDecimals", (BASE_TOKEN,));
+    let quote: Option<u8> = query_registry(&system, &program, "GetTokenDecimals", (QUOTE_A,));
+    let unset: Option<u8> = query_registry(&system, &program, "GetTokenDecimals", (QUOTE_B,));
+    assert_eq!(base, Some(18));
+    assert_eq!(quote, Some(6));
+    assert_eq!(unset, None);
+}
+
+#[test]
+fn register_market_rejects_identical_tokens() {
+    let system = System::new();
+    let program = setup_registry(&system);
+    let info = market(10);
+
+    let payload = (
+        "Registry",
+        "RegisterMarket",
+        (
+            BASE_TOKEN,
+            BASE_TOKEN,
+            info.orderbook_id,
+            info.base_vault_id,
+            info.quote_vault_id,
+        ),
+    )
+        .encode();
+    let mid = program.send_bytes(ADMIN_ID, payload);
+    let res = system.run_next_block();
+    assert!(
+        res.failed.contains(&mid),
+        "Identical tokens must be rejected"
+    );
+
+    let listed: Vec<([u8; 20], MarketInfo)> =
+        query_registry(&system, &program, "QuotesForBase", (BASE_TOKEN,));
+    assert!(listed.is_empty());
+
+    register(&system, &program, BASE_TOKEN, QUOTE_A, &info);
+    let listed: Vec<([u8; 20], MarketInfo)> =
+        query_registry(&system, &program, "QuotesForBase", (BASE_TOKEN,));
+    assert_eq!(listed, vec![(QUOTE_A, info)]);
+}
+
+#[test]
+fn register_market_refuses_an_existing_pair() {
+    let system = System::new();
+    let program = setup_registry(&system);
+    register(&system, &program, BASE_TOKEN, QUOTE_A, &market(10));
+    send_registry(
+        &system,
+        &program,
+        "SetMarketStatus",
+        (BASE_TOKEN, QUOTE_A, MarketStatus::Paused),
+    );
+
+    let info = market(40);
+    let payload = (
+        "Registry",
+        "RegisterMarket",
+        (
+            BASE_TOKEN,
+            QUOTE_A,
+            info.orderbook_id,
+            info.base_vault_id,
+            info.quote_vault_id,
+        ),
+    )
+        .encode();
+    let mid = program.send_bytes(ADMIN_ID, payload);
+    let res = system.run_next_block();
+    assert!(
+        res.failed.contains(&mid),
+        "Re-registering a pair must be rejected"
+    );
+
+    // The pair keeps its routing and its paused status.
+    let stored: Option<MarketInfo> =
+        query_registry(&system, &program, "GetMarket", (BASE_TOKEN, QUOTE_A));
+    assert_eq!(
+        stored,
+        Some(MarketInfo {
+            status: MarketStatus::Paused,
+            ..market(10)
+        })
+    );
+}
+
+fn deploy_vault(system: &System, token: [u8; 20]) -> Program<'_> {
+    let program = Program::from_file(system, VAULT_WASM);
+    let mid = program.send_bytes(ADMIN_ID, ("Create", (eth_to_actor(token),)).encode());
+    let res = system.run_next_block();
+    assert!(res.succeed.contains(&mid), "Vault init failed");
+    program
+}
+
+#[test]
+fn markets_with_balance_lists_only_funded_pairs() {
+    let system = System::new();
+    let program = setup_registry(&system);
+
+    let base_vault = deploy_vault(&system, BASE_TOKEN);
+    let quote_a_vault = deploy_vault(&system, QUOTE_A);
+    let other_base_vault = deploy_vault(&system, OTHER_BASE);
+    let quote_b_vault = deploy_vault(&system, QUOTE_B);
+
+    let funded = MarketInfo {
+        orderbook_id: ActorId::from(600u64),
+        base_vault_id: base_vault.id(),
+        quote_vault_id: quote_a_vault.id(),
+        status: MarketStatus::Active,
+    };
+    let unfunded = MarketInfo {
+        orderbook_id: ActorId::from(601u64),
+        base_vault_id: other_base_vault.id(),
+        quote_vault_id: quote_b_vault.id(),
+        status: MarketStatus::Active,
+    };
+    register(&system, &program, BASE_TOKEN, QUOTE_A, &funded);
+    register(&system, &program, OTHER_BASE, QUOTE_B, &unfunded);
+    // The registry can't answer a vault query, so this pair's lookups fail outright.
+    let broken = MarketInfo {
+        orderbook_id: ActorId::from(602u64),
+        base_vault_id: program.id(),
+        quote_vault_id: program.id(),
+        status: MarketStatus::Active,
+    };
+    register(&system, &program, QUOTE_A, QUOTE_B, &broken);
+
+    let user = ActorId::from(700u64);
+    let mid = quote_a_vault.send_bytes(
+        ADMIN_ID,
+        ("Vault", "VaultDeposit", (user, 1_000u128)).encode(),
+    );
+    let res = system.run_next_block();
+    assert!(res.succeed.contains(&mid), "Vault deposit failed");
+
+    let pairs: Vec<([u8; 20], [u8; 20])> =
+        query_registry(&system, &program, "MarketsWithBalance", (user,));
+    assert_eq!(pairs, vec![(BASE_TOKEN, QUOTE_A)]);
+
+    let stranger: Vec<([u8; 20], [u8; 20])> = query_registry(
+        &system,
+        &program,
+        "MarketsWithBalance",
+        (ActorId::from(701u64),),
+    );
+    assert!(stranger.is_empty());
+}
+
+#[test]
+fn remove_market_reports_whether_the_pair_existed() {
+    let system = System::new();
+    let program = setup_registry(&system);
+    register(&system, &program, BASE_TOKEN, QUOTE_A, &market(10));
+    register(&system, &program, BASE_TOKEN, QUOTE_B, &market(20));
+
+    let removed: bool = query_registry(&system, &program, "RemoveMarket", (OTHER_BASE, QUOTE_A));
+    assert!(!removed, "Unknown pair must not be reported as removed");
+
+    let removed: bool = query_registry(&system, &program, "RemoveMarket", (BASE_TOKEN, QUOTE_A));
+    assert!(removed);
+    let quotes: Vec<([u8; 20], MarketInfo)> =
+        query_registry(&system, &program, "QuotesForBase", (BASE_TOKEN,));
+    assert_eq!(quotes, vec![(QUOTE_B, market(20))]);
+
+    let removed: bool = query_registry(&system, &program, "RemoveMarket", (BASE_TOKEN, QUOTE_A));
+    assert!(!removed, "Second removal must be a no-op");
+}
+
+#[test]
+fn update_market_repoints_only_registered_pairs() {
+    let system = System::new();
+    let program = setup_registry(&system);
+    let update = |info: &MarketInfo| {
+        let payload = (
+            "Registry",
+            "UpdateMarket",
+            (
+                BASE_TOKEN,
+                QUOTE_A,
+                info.orderbook_id,
+                info.base_vault_id,
+                info.quote_vault_id,
+            ),
+        )
+            .encode();
+        let mid = program.send_bytes(ADMIN_ID, payload);
+        (mid, system.run_next_block())
+    };
+
+    let (mid, res) = update(&market(50));
+    assert!(
+        res.failed.contains(&mid),
+        "Updating an unregistered pair must be rejected"
+    );
+    let quotes: Vec<([u8; 20], MarketInfo)> =
+        query_registry(&system, &program, "QuotesForBase", (BASE_TOKEN,));
+    assert!(quotes.is_empty());
+
+    register(&system, &program, BASE_TOKEN, QUOTE_A, &market(10));
+    let (mid, res) = update(&market(50));
+    assert!(res.succeed.contains(&mid), "Repoint failed");
+    let announced = res.log.iter().any(|l| {
+        <(String, String)>::decode(&mut l.payload())
+            .is_ok_and(|(service, event)| service == "Registry" && event == "MarketUpdated")
+    });
+    assert!(announced, "MarketUpdated event not emitted");
+
+    let quotes: Vec<([u8; 20], MarketInfo)> =
+        query_registry(&system, &program, "QuotesForBase", (BASE_TOKEN,));
+    assert_eq!(quotes, vec![(QUOTE_A, market(50))]);
+}
+
+#[test]
+fn market_status_controls_the_active_list() {
+    let system = System::new();
+    let program = setup_registry(&system);
+    register(&system, &program, BASE_TOKEN, QUOTE_A, &market(10));
+    register(&system, &program, BASE_TOKEN, QUOTE_B, &market(20));
+    register(&system, &program, OTHER_BASE, QUOTE_A, &market(30));
+
+    let status_of = |base: [u8; 20], quote: [u8; 20]| {
+        let info: Option<MarketInfo> =
+            query_registry(&system, &program, "GetMarket", (base, quote));
+        info.expect("market registered").status
+    };
+    assert_eq!(status_of(BASE_TOKEN, QUOTE_A), MarketStatus::Active);
+
+    send_registry(
+        &system,
+        &program,
+        "SetMarketStatus",
+        (BASE_TOKEN, QUOTE_B, MarketStatus::Paused),
+    );
+    send_registry(
+        &system,
+        &program,
+        "SetMarketStatus",
+        (OTHER_BASE, QUOTE_A, MarketStatus::Delisted),
+    );
+    assert_eq!(status_of(BASE_TOKEN, QUOTE_B), MarketStatus::Paused);
+    assert_eq!(status_of(OTHER_BASE, QUOTE_A), MarketStatus::Delisted);
+
+    let active: Vec<(([u8; 20], [u8; 20]), MarketInfo)> =
+        query_registry(&system, &program, "ActiveMarkets", ());
+    assert_eq!(active, vec![((BASE_TOKEN, QUOTE_A), market(10))]);
+
+    // un-pausing puts the market back on the list
+    send_registry(
+        &system,
+        &program,
+        "SetMarketStatus",
+        (BASE_TOKEN, QUOTE_B, MarketStatus::Active),
+    );
+    let active: Vec<(([u8; 20], [u8; 20]), MarketInfo)> =
+        query_registry(&system, &program, "ActiveMarkets", ());
+    assert_eq!(
+        active,
+        vec![
+            ((BASE_TOKEN, QUOTE_A), market(10)),
+            ((BASE_TOKEN, QUOTE_B), market(20)),
+        ]
+    );
+
+    let payload = (
+        "Registry",
+        "SetMarketStatus",
+        (QUOTE_A, QUOTE_B, MarketStatus::Paused),
+    )
+        .encode();
+    let mid = program.send_bytes(ADMIN_ID, payload);
+    let res = system.run_next_block();
+    assert!(
+        res.failed.contains(&mid),
+        "Status of an unknown pair must be rejected"
+    );
+}
+
+#[test]
+fn admin_handover_needs_acceptance_by_the_latest_proposal() {
+    let system = System::new();
+    let program = setup_registry(&system);
+    let (first, second) = (200u64, 201u64);
+    system.mint_to(first, 100_000_000_000_000_000);
+    system.mint_to(second, 100_000_000_000_000_000);
+
+    let send_as = |from: u64, method: &str, args: Vec<u8>| {
+        let mut payload = ("Registry", method).encode();
+        payload.extend(args);
+        let mid = program.send_bytes(from, payload);
+        let res = system.run_next_block();
+        (res.succeed.contains(&mid), res)
+    };
+
+    send_registry(&system, &program, "ProposeAdmin", (ActorId::from(first),));
+    // a second proposal replaces the first before it is accepted
+    send_registry(&system, &program, "ProposeAdmin", (ActorId::from(second),));
+
+    let (ok, _) = send_as(first, "AcceptAdmin", Vec::new());
+    assert!(!ok, "Replaced proposal must not be accepted");
+    let (ok, _) = send_as(ADMIN_ID, "AcceptAdmin", Vec::new());
+    assert!(!ok, "Only the proposed admin may accept");
+
+    let (ok, res) = send_as(second, "AcceptAdmin", Vec::new());
+    assert!(ok, "Proposed admin failed to accept");
+    let announced = res.log.iter().any(|l| {
+        <(String, String)>::decode(&mut l.payload())
+            .is_ok_and(|(service, event)| service == "Registry" && event == "AdminChanged")
+    });
+    assert!(announced, "AdminChanged event not emitted");
+
+    let decimals = (BASE_TOKEN, 18u8).encode();
+    let (ok, _) = send_as(ADMIN_ID, "SetTokenDecimals", decimals.clone());
+    assert!(!ok, "Previous admin must lose access");
+    let (ok, _) = send_as(second, "SetTokenDecimals", decimals);
+    assert!(ok, "New admin must have access");
+    let (ok, _) = send_as(second, "AcceptAdmin", Vec::new());
+    assert!(!ok, "Pending slot must be cleared after acceptance");
+}
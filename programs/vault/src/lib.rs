@@ -1,6 +1,6 @@
 #![no_std]
 
-use clob_common::{actor_to_eth, TokenId};
+use clob_common::{actor_to_eth, parse_version, DepositError, TokenId, Version};
 #[cfg(feature = "debug")]
 use clob_common::{eth_to_actor, SHOWCASE_PREFUNDED_ETH_ADDRESSES};
 use sails_rs::{cell::RefCell, gstd::debug, gstd::exec, gstd::msg, prelude::*};
@@ -37,12 +37,52 @@ pub enum Events {
         amount: u128,
         balance_after: u128,
     },
+    /// The pending admin accepted and replaced `previous_admin`.
+    AdminChanged {
+        previous_admin: [u8; 20],
+        new_admin: [u8; 20],
+    },
 }
 
 pub struct VaultProgram {
     state: RefCell<VaultState>,
 }
 
+const VERSION: Version = parse_version(
+    env!("CARGO_PKG_VERSION_MAJOR"),
+    env!("CARGO_PKG_VERSION_MINOR"),
+    env!("CARGO_PKG_VERSION_PATCH"),
+);
+
+/// Order side, encoded the same way as the orderbook's `Side`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+/// Order type, encoded the same way as the orderbook's `OrderType`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub enum OrderType {
+    Limit,
+    Market,
+    FillOrKill,
+    ImmediateOrCancel,
+    PegToBest,
+}
+
+/// `(side, kind, limit_price, amount_base, max_quote)` as `Orderbook::deposit_and_submit`
+/// takes them.
+type OrderParams = (Side, OrderType, u128, u128, u128);
+
+const DEFAULT_TRANSFER_RETRIES: u8 = 1;
+const MAX_BALANCE_BATCH: usize = 100;
+const MAX_TRANSFER_RETRIES: u8 = 3;
+
 #[cfg(feature = "debug")]
 const SHOWCASE_VAULT_PREFUND_ATOMS: u128 = 10_000_000_000_000;
 
@@ -56,6 +96,22 @@ fn actor_addr(actor: ActorId) -> [u8; 20] {
 }
 
 fn decode_orderbook_deposit_ack(reply: &[u8]) -> bool {
+    // Current markets answer `Result<bool, DepositError>`; an `Err` means nothing was credited.
+    type DepositReply = Result<bool, DepositError>;
+    let mut wrapped = reply;
+    if let Ok((service, method, ack)) = <(String, String, DepositReply)>::decode(&mut wrapped) {
+        if wrapped.is_empty() {
+            return service == "Orderbook" && method == "Deposit" && ack == Ok(true);
+        }
+    }
+
+    let mut raw = reply;
+    if let Ok(ack) = DepositReply::decode(&mut raw) {
+        if raw.is_empty() {
+            return ack == Ok(true);
+        }
+    }
+
     let mut wrapped = reply;
     if let Ok((service, method, ack)) = <(String, String, bool)>::decode(&mut wrapped) {
         return wrapped.is_empty() && service == "Orderbook" && method == "Deposit" && ack;
@@ -69,6 +125,21 @@ fn decode_orderbook_deposit_ack(reply: &[u8]) -> bool {
     false
 }
 
+fn decode_orderbook_order_id(reply: &[u8]) -> Option<u64> {
+    let mut wrapped = reply;
+    if let Ok((service, method, order_id)) = <(String, String, u64)>::decode(&mut wrapped) {
+        return (wrapped.is_empty() && service == "Orderbook" && method == "DepositAndSubmit")
+            .then_some(order_id);
+    }
+
+    let mut raw = reply;
+    if let Ok(order_id) = u64::decode(&mut raw) {
+        return raw.is_empty().then_some(order_id);
+    }
+
+    None
+}
+
 #[cfg(feature = "debug")]
 fn seed_showcase_prefunds(state: &mut VaultState) {
     for address in SHOWCASE_PREFUNDED_ETH_ADDRESSES {
@@ -86,12 +157,14 @@ impl VaultProgram {
         let mut state = VaultState {
             admin: Some(msg::source()),
             token: actor_to_eth(token_id),
+            transfer_retries: DEFAULT_TRANSFER_RETRIES,
             ..VaultState::default()
         };
         #[cfg(not(feature = "debug"))]
         let state = VaultState {
             admin: Some(msg::source()),
             token: actor_to_eth(token_id),
+            transfer_retries: DEFAULT_TRANSFER_RETRIES,
             ..VaultState::default()
         };
         #[cfg(feature = "debug")]
@@ -183,6 +256,84 @@ impl<'a> VaultService<'a> {
         reply_ok();
     }
 
+    // Admin function to revoke an OrderBook program
+    #[export]
+    pub fn remove_market(&mut self, program_id: ActorId) {
+        let mut state = self.get_mut();
+        if state.admin != Some(msg::source()) {
+            panic!("Unauthorized: Not Admin");
+        }
+        state.registered_orderbooks.remove(&program_id);
+        reply_ok();
+    }
+
+    /// First step of an admin handover: records `new_admin`, who then has to call
+    /// `accept_admin`. A new proposal replaces one that wasn't accepted yet.
+    #[export]
+    pub fn propose_admin(&mut self, new_admin: ActorId) {
+        let mut state = self.get_mut();
+        if state.admin != Some(msg::source()) {
+            panic!("Unauthorized: Not Admin");
+        }
+        state.pending_admin = Some(new_admin);
+        reply_ok();
+    }
+
+    /// Completes the handover started by `propose_admin`. Only the proposed admin may call it.
+    #[export]
+    pub fn accept_admin(&mut self) {
+        let caller = msg::source();
+        let previous = {
+            let mut state = self.get_mut();
+            if state.pending_admin != Some(caller) {
+                panic!("Unauthorized: Not Pending Admin");
+            }
+            state.pending_admin = None;
+            state.admin.replace(caller).unwrap_or_default()
+        };
+
+        let event = Events::AdminChanged {
+            previous_admin: actor_addr(previous),
+            new_admin: actor_addr(caller),
+        };
+        self.emit_eth_event(event.clone()).expect("EmitEventFailed");
+        let mut emitter = self.emitter();
+        emitter.emit_event(event).expect("EmitEventFailed");
+        reply_ok();
+    }
+
+    /// Halts or resumes every movement of funds: deposits, withdrawals, force exits and
+    /// transfers to markets are refused while paused. Admin only.
+    #[export]
+    pub fn set_paused(&mut self, paused: bool) {
+        let mut state = self.get_mut();
+        if state.admin != Some(msg::source()) {
+            panic!("Unauthorized: Not Admin");
+        }
+        state.paused = paused;
+        reply_ok();
+    }
+
+    /// Caps how much of `token` the vault may hold, counted as deposits minus withdrawals and
+    /// transfers to markets. Zero removes the cap. A cap below the current total only blocks
+    /// new deposits; funds coming back from registered markets are never capped. Admin only.
+    #[export]
+    pub fn set_deposit_cap(&mut self, token: TokenId, cap: u128) {
+        let mut state = self.get_mut();
+        if state.admin != Some(msg::source()) {
+            panic!("Unauthorized: Not Admin");
+        }
+        if token != state.token {
+            panic!("InvalidToken");
+        }
+        if cap == 0 {
+            state.deposit_caps.remove(&token);
+        } else {
+            state.deposit_caps.insert(token, cap);
+        }
+        reply_ok();
+    }
+
     #[export]
     pub fn update_fee_rate(&mut self, new_rate: u128) {
         let mut state = self.get_mut();
@@ -206,6 +357,19 @@ impl<'a> VaultService<'a> {
         reply_ok();
     }
 
+    #[export]
+    pub fn set_transfer_retries(&mut self, retries: u8) {
+        let mut state = self.get_mut();
+        if state.admin != Some(msg::source()) {
+            panic!("Unauthorized: Not Admin");
+        }
+        if retries > MAX_TRANSFER_RETRIES {
+            panic!("InvalidRetryCount");
+        }
+        state.transfer_retries = retries;
+        reply_ok();
+    }
+
     // Admin function to claim accumulated fees
     #[export]
     pub fn claim_fees(&mut self) {
@@ -249,6 +413,12 @@ impl<'a> VaultService<'a> {
         }
     }
 
+    fn ensure_not_paused(&self) {
+        if self.get().paused {
+            panic!("Paused");
+        }
+    }
+
     fn ensure_authorized_program_or_user(&self, user: ActorId) {
         let caller = sails_rs::gstd::msg::source();
         if caller == user {
@@ -284,9 +454,32 @@ impl<'a> VaultService<'a> {
     }
 
     fn vault_deposit_unchecked(&mut self, user: ActorId, amount: u128) {
+        self.ensure_not_paused();
         let mut state = self.get_mut();
         let token = state.token;
 
+        let total = state
+            .total_deposited
+            .get(&token)
+            .copied()
+            .unwrap_or(0)
+            .checked_add(amount)
+            .expect("MathOverflow");
+        // Funds a market hands back left the vault under the cap already; a lowered cap
+        // must not trap them there.
+        let from_market = state
+            .registered_orderbooks
+            .contains(&sails_rs::gstd::msg::source());
+        if !from_market
+            && state
+                .deposit_caps
+                .get(&token)
+                .is_some_and(|cap| total > *cap)
+        {
+            panic!("DepositCapExceeded");
+        }
+        state.total_deposited.insert(token, total);
+
         debug!(
             "Vault::vault_deposit caller={:?} user={:?} token={:?} amount={}",
             sails_rs::gstd::msg::source(),
@@ -339,6 +532,7 @@ impl<'a> VaultService<'a> {
     #[export]
     pub fn vault_withdraw(&mut self, user: ActorId, amount: u128) {
         self.ensure_authorized_program_or_user(user);
+        self.ensure_not_paused();
         self.release_matured_quarantine();
         self.vault_withdraw_unchecked(user, amount);
     }
@@ -353,6 +547,7 @@ impl<'a> VaultService<'a> {
         }
 
         *balance = balance.checked_sub(amount).expect("MathOverflow");
+        state.record_withdrawal(amount);
 
         self.emit_eth_event(Events::Withdrawal {
             user: actor_addr(user),
@@ -377,9 +572,10 @@ impl<'a> VaultService<'a> {
     pub async fn transfer_to_market(&mut self, market_id: ActorId, amount: u128) {
         let user = msg::source();
         self.ensure_authorized_program_or_user(user);
+        self.ensure_not_paused();
 
         self.release_matured_quarantine();
-        let token = {
+        let (token, retries) = {
             let mut state = self.get_mut();
             if !state.registered_orderbooks.contains(&market_id) {
                 panic!("UnauthorizedMarket");
@@ -393,21 +589,29 @@ impl<'a> VaultService<'a> {
             }
 
             *balance = balance.checked_sub(amount).expect("MathOverflow");
-            state.token
+            (state.token, state.transfer_retries)
         };
 
         // 2. Send deposit message to OrderBook using the current service envelope.
         // Payload is ("Orderbook", "Deposit", (user, token, amount)).
         let payload = ("Orderbook", "Deposit", (user, token, amount)).encode();
 
-        let result = msg::send_bytes_for_reply(market_id, payload, 0)
-            .expect("SendFailed")
-            .await;
-
-        let deposit_acked = match result {
-            Ok(reply) => decode_orderbook_deposit_ack(&reply),
-            Err(_) => false,
-        };
+        // Only error replies are retried: the market's handler was reverted, so nothing was
+        // credited there. A successful reply is final, whatever its ack says.
+        let mut deposit_acked = false;
+        for _ in 0..=retries {
+            let result = msg::send_bytes_for_reply(market_id, payload.clone(), 0)
+                .expect("SendFailed")
+                .await;
+
+            match result {
+                Ok(reply) => {
+                    deposit_acked = decode_orderbook_deposit_ack(&reply);
+                    break;
+                }
+                Err(_) => debug!("OrderbookDepositAttemptFailed"),
+            }
+        }
 
         if !deposit_acked {
             let mut state = self.get_mut();
@@ -419,12 +623,84 @@ impl<'a> VaultService<'a> {
             return;
         }
 
+        // The funds now sit with the market and count again when it deposits them back.
+        self.get_mut().record_withdrawal(amount);
         reply_ok();
     }
 
+    /// Moves `amount` to `market_id` and places `order` there for the caller in one flow.
+    /// `order` is `(side, kind, limit_price, amount_base, max_quote)` as in `submit_order`.
+    /// Returns the order id, or `None` after restoring the balance if the market refused.
+    /// A success reply that doesn't decode also returns `None`, but the balance stays with
+    /// the market, which has already credited it.
+    #[export]
+    pub async fn fund_and_order(
+        &mut self,
+        market_id: ActorId,
+        amount: u128,
+        order: OrderParams,
+    ) -> Option<u64> {
+        let user = msg::source();
+        self.ensure_not_paused();
+        self.release_matured_quarantine();
+        let token = {
+            let mut state = self.get_mut();
+            if !state.registered_orderbooks.contains(&market_id) {
+                panic!("UnauthorizedMarket");
+            }
+
+            let balance = state.balances.get_mut(&user).expect("UserNotFound");
+            if *balance < amount {
+                panic!("InsufficientBalance");
+            }
+            *balance = balance.checked_sub(amount).expect("MathOverflow");
+            state.token
+        };
+
+        let (side, kind, limit_price, amount_base, max_quote) = order;
+        let payload = (
+            "Orderbook",
+            "DepositAndSubmit",
+            (
+                user,
+                token,
+                amount,
+                side,
+                kind,
+                limit_price,
+                amount_base,
+                max_quote,
+            ),
+        )
+            .encode();
+        let result = msg::send_bytes_for_reply(market_id, payload, 0)
+            .expect("SendFailed")
+            .await;
+
+        match result {
+            Ok(reply) => {
+                self.get_mut().record_withdrawal(amount);
+                let order_id = decode_orderbook_order_id(&reply);
+                if order_id.is_none() {
+                    debug!("OrderbookFundAndOrderUnreadableReply");
+                }
+                order_id
+            }
+            // An error reply means the market reverted both the deposit and the order.
+            Err(_) => {
+                let mut state = self.get_mut();
+                let balance = state.balances.get_mut(&user).expect("UserNotFound");
+                *balance = balance.checked_add(amount).expect("MathOverflow");
+                debug!("OrderbookFundAndOrderFailed");
+                None
+            }
+        }
+    }
+
     #[export]
     pub fn vault_force_exit(&mut self, user: ActorId, amount: u128) {
         self.ensure_authorized_program_or_user(user);
+        self.ensure_not_paused();
         self.release_matured_quarantine();
         let mut state = self.get_mut();
         let token = state.token;
@@ -433,6 +709,7 @@ impl<'a> VaultService<'a> {
         let to_deduct = if *balance < amount { *balance } else { amount };
 
         *balance = balance.checked_sub(to_deduct).expect("MathOverflow");
+        state.record_withdrawal(to_deduct);
 
         self.emit_eth_event(Events::Withdrawal {
             user: actor_addr(user),
@@ -467,21 +744,93 @@ impl<'a> VaultService<'a> {
         state.admin == Some(program_id) || state.registered_orderbooks.contains(&program_id)
     }
 
+    /// Every actor that passes `is_authorized`: the admin and all registered markets, sorted.
+    #[export]
+    pub fn authorized_programs(&self) -> Vec<ActorId> {
+        let state = self.get();
+        let mut programs = state.registered_orderbooks.clone();
+        programs.extend(state.admin);
+        programs.into_iter().collect()
+    }
+
     #[export]
     pub fn get_balance(&self, user: ActorId) -> u128 {
         let state = self.get();
         state.balances.get(&user).copied().unwrap_or(0)
     }
 
+    /// Balances for many `(user, token)` pairs in input order, as `(available, quarantined)`.
+    /// Tokens other than this vault's token resolve to `(0, 0)`.
+    #[export]
+    pub fn get_balances(&self, pairs: Vec<(ActorId, TokenId)>) -> Vec<(u128, u128)> {
+        if pairs.len() > MAX_BALANCE_BATCH {
+            panic!("TooManyPairs");
+        }
+        let state = self.get();
+        pairs
+            .iter()
+            .map(|(user, token)| {
+                if *token != state.token {
+                    return (0, 0);
+                }
+                let available = state.balances.get(user).copied().unwrap_or(0);
+                let quarantined = state
+                    .quarantined_deposits
+                    .iter()
+                    .filter(|d| d.user == *user)
+                    .map(|d| d.amount)
+                    .sum();
+                (available, quarantined)
+            })
+            .collect()
+    }
+
     #[export]
     pub fn get_treasury(&self) -> u128 {
         self.get().treasury
     }
+
+    /// Deposit cap of `token`, or 0 when it is uncapped.
+    #[export]
+    pub fn deposit_cap(&self, token: TokenId) -> u128 {
+        self.get().deposit_caps.get(&token).copied().unwrap_or(0)
+    }
+
+    /// Amount of `token` deposited and not yet withdrawn or moved to a market, as counted
+    /// against its cap.
+    #[export]
+    pub fn total_deposited(&self, token: TokenId) -> u128 {
+        self.get().total_deposited.get(&token).copied().unwrap_or(0)
+    }
+
+    #[export]
+    pub fn is_paused(&self) -> bool {
+        self.get().paused
+    }
+
+    /// Fee rate in basis points, as last set by `update_fee_rate`.
+    #[export]
+    pub fn fee_rate(&self) -> u128 {
+        self.get().fee_rate_bps
+    }
+
+    #[export]
+    pub fn version(&self) -> Version {
+        VERSION
+    }
+
+    /// True once the program has been created with an admin and while it isn't paused.
+    #[export]
+    pub fn health(&self) -> bool {
+        let state = self.get();
+        state.admin.is_some() && !state.paused
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::decode_orderbook_deposit_ack;
+    use super::{decode_orderbook_deposit_ack, decode_orderbook_order_id};
+    use clob_common::DepositError;
     use sails_rs::prelude::*;
 
     #[test]
@@ -505,6 +854,27 @@ mod tests {
         assert!(!decode_orderbook_deposit_ack(&wrong_method));
     }
 
+    #[test]
+    fn decode_ack_accepts_ok_result_and_rejects_deposit_error() {
+        let ok = (
+            String::from("Orderbook"),
+            String::from("Deposit"),
+            Ok::<bool, DepositError>(true),
+        )
+            .encode();
+        let refused = (
+            String::from("Orderbook"),
+            String::from("Deposit"),
+            Err::<bool, DepositError>(DepositError::UnauthorizedCaller),
+        )
+            .encode();
+        assert!(decode_orderbook_deposit_ack(&ok));
+        assert!(!decode_orderbook_deposit_ack(&refused));
+        assert!(decode_orderbook_deposit_ack(
+            &Ok::<bool, DepositError>(true).encode()
+        ));
+    }
+
     #[test]
     fn decode_ack_accepts_valid_raw_bool() {
         let reply = true.encode();
@@ -523,4 +893,25 @@ mod tests {
         let reply = vec![0xFF, 0xAA, 0x10];
         assert!(!decode_orderbook_deposit_ack(&reply));
     }
+
+    #[test]
+    fn decode_order_id_accepts_wrapped_and_raw() {
+        let wrapped = (
+            String::from("Orderbook"),
+            String::from("DepositAndSubmit"),
+            7u64,
+        )
+            .encode();
+        assert_eq!(decode_orderbook_order_id(&wrapped), Some(7));
+        assert_eq!(decode_orderbook_order_id(&9u64.encode()), Some(9));
+    }
+
+    #[test]
+    fn decode_order_id_rejects_wrong_method_and_trailing_bytes() {
+        let wrong_method = (String::from("Orderbook"), String::from("Deposit"), 7u64).encode();
+        assert_eq!(decode_orderbook_order_id(&wrong_method), None);
+        let mut trailing = 7u64.encode();
+        trailing.push(0x01);
+        assert_eq!(decode_orderbook_order_id(&trailing), None);
+    }
 }
@@ -38,8 +38,29 @@ pub struct VaultState {
     pub quarantine_period: u64,
     /// Admin
     pub admin: Option<ActorId>,
+    /// Proposed by `propose_admin`; becomes admin once it calls `accept_admin`
+    pub pending_admin: Option<ActorId>,
     /// Treasury for fees - kept from original (implied)
     pub treasury: u128,
     /// Fee rate in BPS
     pub fee_rate_bps: u128,
+    /// Extra deposit attempts `transfer_to_market` makes after an error reply
+    pub transfer_retries: u8,
+    /// Set by the admin during an incident: deposits, withdrawals and transfers are refused
+    pub paused: bool,
+    /// Most of each token the vault will take in through deposits; absent means uncapped
+    pub deposit_caps: BTreeMap<TokenId, u128>,
+    /// Deposited minus withdrawn or moved to a market per token, checked against `deposit_caps`
+    pub total_deposited: BTreeMap<TokenId, u128>,
+}
+
+impl VaultState {
+    /// Takes `amount` off this token's deposit total once it has left the vault, whether
+    /// withdrawn or credited to a market. Balances credited before totals were tracked were
+    /// never counted, so the total floors at zero.
+    pub fn record_withdrawal(&mut self, amount: u128) {
+        if let Some(total) = self.total_deposited.get_mut(&self.token) {
+            *total = total.saturating_sub(amount);
+        }
+    }
 }
@@ -177,3 +177,268 @@ async fn test_transfer_to_market_requires_registered_market() {
     let avail = service_client.get_balance(actor(USER_1)).await.unwrap();
     assert_eq!(avail, 1000);
 }
+
+#[tokio::test]
+async fn test_version_and_health() {
+    let system = System::new();
+    system.init_logger();
+    system.mint_to(ADMIN_ID, 1_000_000_000_000_000);
+
+    let remoting = GtestEnv::new(system, ADMIN_ID.into());
+    let program_id = deploy_vault(&remoting, TOKEN_BASE).await;
+    let mut service_client = Service::<VaultImpl, _>::new(remoting.clone(), program_id, "Vault");
+
+    let expected = (
+        env!("CARGO_PKG_VERSION_MAJOR").parse::<u16>().unwrap(),
+        env!("CARGO_PKG_VERSION_MINOR").parse::<u16>().unwrap(),
+        env!("CARGO_PKG_VERSION_PATCH").parse::<u16>().unwrap(),
+    );
+    assert_eq!(service_client.version().await.unwrap(), expected);
+    assert!(service_client.health().await.unwrap());
+
+    service_client.set_paused(true).await.unwrap();
+    assert!(!service_client.health().await.unwrap());
+    service_client.set_paused(false).await.unwrap();
+    assert!(service_client.health().await.unwrap());
+}
+
+#[tokio::test]
+async fn test_get_balances_preserves_input_order() {
+    let system = System::new();
+    system.init_logger();
+    system.mint_to(ADMIN_ID, 1_000_000_000_000_000);
+
+    let remoting = GtestEnv::new(system, ADMIN_ID.into());
+    let program_id = deploy_vault(&remoting, TOKEN_BASE).await;
+
+    let mut service_client = Service::<VaultImpl, _>::new(remoting.clone(), program_id, "Vault");
+
+    let user_2 = actor([2u8; 20]);
+    service_client
+        .vault_deposit(actor(USER_1), 1000u128)
+        .await
+        .unwrap();
+    service_client.vault_deposit(user_2, 250u128).await.unwrap();
+
+    let balances = service_client
+        .get_balances(vec![
+            (user_2, TOKEN_BASE),
+            (actor([3u8; 20]), TOKEN_BASE),
+            (actor(USER_1), TOKEN_BASE),
+        ])
+        .await
+        .unwrap();
+    assert_eq!(balances, vec![(250, 0), (0, 0), (1000, 0)]);
+}
+
+#[tokio::test]
+async fn test_authorized_programs_follow_market_registration() {
+    let system = System::new();
+    system.init_logger();
+    system.mint_to(ADMIN_ID, 1_000_000_000_000_000);
+
+    let remoting = GtestEnv::new(system, ADMIN_ID.into());
+    let program_id = deploy_vault(&remoting, TOKEN_BASE).await;
+
+    let mut service_client = Service::<VaultImpl, _>::new(remoting.clone(), program_id, "Vault");
+    let admin = ActorId::from(ADMIN_ID);
+    let market = ActorId::from(999u64);
+
+    assert_eq!(
+        service_client.authorized_programs().await.unwrap(),
+        vec![admin]
+    );
+
+    service_client.add_market(market).await.unwrap();
+    let mut expected = vec![admin, market];
+    expected.sort();
+    assert_eq!(
+        service_client.authorized_programs().await.unwrap(),
+        expected
+    );
+
+    service_client.remove_market(market).await.unwrap();
+    assert_eq!(
+        service_client.authorized_programs().await.unwrap(),
+        vec![admin]
+    );
+    assert!(!service_client.is_authorized(market).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_fee_rate_reflects_update() {
+    let system = System::new();
+    system.init_logger();
+    system.mint_to(ADMIN_ID, 1_000_000_000_000_000);
+
+    let remoting = GtestEnv::new(system, ADMIN_ID.into());
+    let program_id = deploy_vault(&remoting, TOKEN_BASE).await;
+
+    let mut service_client = Service::<VaultImpl, _>::new(remoting.clone(), program_id, "Vault");
+
+    service_client.update_fee_rate(45).await.unwrap();
+    assert_eq!(service_client.fee_rate().await.unwrap(), 45);
+}
+
+#[tokio::test]
+async fn test_admin_handover_requires_acceptance() {
+    let system = System::new();
+    system.init_logger();
+    system.mint_to(ADMIN_ID, 1_000_000_000_000_000);
+    system.mint_to(200, 1_000_000_000_000_000);
+    system.mint_to(201, 1_000_000_000_000_000);
+
+    let remoting = GtestEnv::new(system, ADMIN_ID.into());
+    let program_id = deploy_vault(&remoting, TOKEN_BASE).await;
+
+    let mut admin_service = Service::<VaultImpl, _>::new(remoting.clone(), program_id, "Vault");
+    let service_as = |id: u64| {
+        Service::<VaultImpl, _>::new(
+            remoting.clone().with_actor_id(ActorId::from(id)),
+            program_id,
+            "Vault",
+        )
+    };
+    let (first, second) = (ActorId::from(200u64), ActorId::from(201u64));
+
+    admin_service.propose_admin(first).await.unwrap();
+    // a second proposal replaces the first before it is accepted
+    admin_service.propose_admin(second).await.unwrap();
+
+    assert!(service_as(200).accept_admin().await.is_err());
+    assert!(admin_service.accept_admin().await.is_err());
+    assert_eq!(
+        admin_service.admin().await.unwrap(),
+        ActorId::from(ADMIN_ID)
+    );
+
+    service_as(201).accept_admin().await.unwrap();
+    assert_eq!(admin_service.admin().await.unwrap(), second);
+    assert!(admin_service.update_fee_rate(10).await.is_err());
+    service_as(201).update_fee_rate(10).await.unwrap();
+    assert!(service_as(201).accept_admin().await.is_err());
+}
+
+#[tokio::test]
+async fn test_paused_vault_refuses_fund_movements() {
+    let system = System::new();
+    system.init_logger();
+    system.mint_to(ADMIN_ID, 1_000_000_000_000_000);
+    system.mint_to(100, 1_000_000_000_000_000);
+
+    let remoting = GtestEnv::new(system, ADMIN_ID.into());
+    let program_id = deploy_vault(&remoting, TOKEN_BASE).await;
+
+    let mut service_client = Service::<VaultImpl, _>::new(remoting.clone(), program_id, "Vault");
+    let admin = ActorId::from(ADMIN_ID);
+    let market = ActorId::from(999u64);
+    service_client
+        .vault_deposit(actor(USER_1), 1000u128)
+        .await
+        .unwrap();
+    service_client.vault_deposit(admin, 1000u128).await.unwrap();
+    service_client.add_market(market).await.unwrap();
+
+    let user_remoting = remoting.clone().with_actor_id(ActorId::from(100u64));
+    let mut user_service = Service::<VaultImpl, _>::new(user_remoting, program_id, "Vault");
+    assert!(user_service.set_paused(true).await.is_err());
+    assert!(!service_client.is_paused().await.unwrap());
+
+    service_client.set_paused(true).await.unwrap();
+    assert!(service_client.is_paused().await.unwrap());
+    assert!(service_client
+        .vault_withdraw(actor(USER_1), 100u128)
+        .await
+        .is_err());
+    assert!(service_client
+        .vault_force_exit(actor(USER_1), 100u128)
+        .await
+        .is_err());
+    assert!(service_client
+        .vault_deposit(actor(USER_1), 100u128)
+        .await
+        .is_err());
+    assert!(service_client
+        .transfer_to_market(market, 100u128)
+        .await
+        .is_err());
+    assert_eq!(
+        service_client.get_balance(actor(USER_1)).await.unwrap(),
+        1000
+    );
+    assert_eq!(service_client.get_balance(admin).await.unwrap(), 1000);
+
+    service_client.set_paused(false).await.unwrap();
+    service_client
+        .vault_withdraw(actor(USER_1), 100u128)
+        .await
+        .unwrap();
+    assert_eq!(
+        service_client.get_balance(actor(USER_1)).await.unwrap(),
+        900
+    );
+}
+
+#[tokio::test]
+async fn test_deposit_cap_limits_net_deposits() {
+    let system = System::new();
+    system.init_logger();
+    system.mint_to(ADMIN_ID, 1_000_000_000_000_000);
+    system.mint_to(100, 1_000_000_000_000_000);
+
+    let remoting = GtestEnv::new(system, ADMIN_ID.into());
+    let program_id = deploy_vault(&remoting, TOKEN_BASE).await;
+
+    let mut service_client = Service::<VaultImpl, _>::new(remoting.clone(), program_id, "Vault");
+    let user_remoting = remoting.clone().with_actor_id(ActorId::from(100u64));
+    let mut user_service = Service::<VaultImpl, _>::new(user_remoting, program_id, "Vault");
+    assert!(user_service
+        .set_deposit_cap(TOKEN_BASE, 1000u128)
+        .await
+        .is_err());
+    assert_eq!(service_client.deposit_cap(TOKEN_BASE).await.unwrap(), 0);
+
+    service_client
+        .set_deposit_cap(TOKEN_BASE, 1000u128)
+        .await
+        .unwrap();
+    assert_eq!(service_client.deposit_cap(TOKEN_BASE).await.unwrap(), 1000);
+    service_client
+        .vault_deposit(actor(USER_1), 600u128)
+        .await
+        .unwrap();
+    assert!(service_client
+        .vault_deposit(actor(USER_1), 500u128)
+        .await
+        .is_err());
+    service_client
+        .vault_deposit(actor(USER_1), 400u128)
+        .await
+        .unwrap();
+    assert_eq!(
+        service_client.total_deposited(TOKEN_BASE).await.unwrap(),
+        1000
+    );
+
+    // Lowering the cap below the total still lets funds leave.
+    service_client
+        .set_deposit_cap(TOKEN_BASE, 500u128)
+        .await
+        .unwrap();
+    service_client
+        .vault_withdraw(actor(USER_1), 300u128)
+        .await
+        .unwrap();
+    assert!(service_client
+        .vault_deposit(actor(USER_1), 1u128)
+        .await
+        .is_err());
+    assert_eq!(
+        service_client.total_deposited(TOKEN_BASE).await.unwrap(),
+        700
+    );
+    assert_eq!(
+        service_client.get_balance(actor(USER_1)).await.unwrap(),
+        700
+    );
+}
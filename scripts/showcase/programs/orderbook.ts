@@ -4,18 +4,19 @@ import {
   ISigner,
   type VaraEthApi,
 } from "@vara-eth/api";
-import { Address, hexToBytes, PublicClient } from "viem";
+import { Address, hexToBytes, PublicClient, zeroAddress } from "viem";
 
 import type { Codec } from "../codec.js";
 import { BaseProgram } from "./base.js";
 import { logger } from "../logger.js";
 import { actorIdToAddress, addressToActorId } from "./util.js";
 
+// Variant names of the IDL `OrderType` enum taken by `SubmitOrder`.
 export enum OrderKind {
-  Limit = 0,
-  Market = 1,
-  FillOrKill = 2,
-  ImmediateOrCancel = 3,
+  Limit = "Limit",
+  Market = "Market",
+  FillOrKill = "FillOrKill",
+  ImmediateOrCancel = "ImmediateOrCancel",
 }
 
 const SERVICE = "Orderbook";
@@ -41,8 +42,13 @@ enum QueryMethod {
 }
 
 export enum Side {
-  Buy = 0,
-  Sell = 1,
+  Buy = "Buy",
+  Sell = "Sell",
+}
+
+// Views return `Side` by variant name; callers here index sides as 0 = buy, 1 = sell.
+function sideIndex(side: unknown): number {
+  return side === Side.Sell ? 1 : 0;
 }
 
 export class Orderbook extends BaseProgram {
@@ -244,14 +250,26 @@ export class Orderbook extends BaseProgram {
 
     const state = await this.readState(payload);
 
+    if (state === null || state === undefined) {
+      return {
+        exists: false,
+        id: 0n,
+        owner: zeroAddress,
+        side: 0,
+        limitPrice: 0n,
+        amountBase: 0n,
+        filledBase: 0n,
+      };
+    }
+
     return {
-      exists: state[0],
-      id: BigInt(state[1]),
-      owner: state[2] as Address,
-      side: Number(state[3]),
-      limitPrice: BigInt(state[4]),
-      amountBase: BigInt(state[5]),
-      filledBase: BigInt(state[6]),
+      exists: true,
+      id: BigInt(state.id),
+      owner: state.owner as Address,
+      side: sideIndex(state.side),
+      limitPrice: BigInt(state.price),
+      amountBase: BigInt(state.remaining_base),
+      filledBase: BigInt(state.reserved_quote),
     };
   }
 
@@ -277,7 +295,7 @@ export class Orderbook extends BaseProgram {
       return {
         id: BigInt(item[0] as bigint),
         owner: item[1] as Address,
-        side: Number(item[2]),
+        side: sideIndex(item[2]),
         limitPrice: BigInt(item[3] as bigint),
         amountBase: BigInt(item[4] as bigint),
         reservedQuote: BigInt(item[5] as bigint),
@@ -307,7 +325,7 @@ export class Orderbook extends BaseProgram {
       return {
         id: BigInt(item[0] as bigint),
         owner: item[1] as Address,
-        side: Number(item[2]),
+        side: sideIndex(item[2]),
         limitPrice: BigInt(item[3] as bigint),
         amountBase: BigInt(item[4] as bigint),
         reservedQuote: BigInt(item[5] as bigint),